@@ -12,6 +12,8 @@
 // }}}
 // {{{ Crates
 
+use std::cmp::Reverse;
+use std::env;
 use std::fs::File;
 use std::io::Read;
 use std::str::FromStr;
@@ -19,10 +21,10 @@ use std::str::FromStr;
 // }}}
 // {{{ Presents
 
-/// A present, represented by the surfaces of its three sides (in square feet).
+/// A present, represented by the dimensions of its box (in feet).
 #[derive(Debug)]
 struct Present {
-    sides: [i32; 3],
+    dims: [u64; 3],
 }
 
 impl FromStr for Present {
@@ -33,48 +35,237 @@ impl FromStr for Present {
     /// The input string should contains three integers separated by x.
     /// Example: 2x3x4
     fn from_str(s: &str) -> Result<Present, String> {
-        let sides: Vec<i32> = s.split('x')
-                              .map(|side| side.parse().unwrap())
-                              .collect();
-        assert!(sides.len() == 3, "invalid number of dimensions");
-        Ok(Present { sides: [sides[0], sides[1], sides[2]] })
+        let dims = s.split('x')
+                    .map(|dim| dim.parse().map_err(|_| {
+                        format!("invalid dimension: {:?}", dim)
+                    }))
+                    .collect::<Result<Vec<u64>, _>>()?;
+        if dims.len() != 3 {
+            return Err(format!("expected 3 dimensions, got {}", dims.len()));
+        }
+        Ok(Present { dims: [dims[0], dims[1], dims[2]] })
     }
 }
 
 impl Present {
     /// Computes the paper area required to wrap the present (in square feet).
     ///
-    /// The wrapping paper area required for a present is 2*l*w + 2*w*h + 2*h*l.
-    /// A little extra paper (the area of the smallest side) for each present is
-    /// also required.
-    fn package_area(&self) -> i32 {
+    /// The wrapping paper area required for a present is 2*l*w + 2*w*h + 2*h*l,
+    /// plus some slack. Returns `None` on overflow.
+    fn package_area(&self, slack: Slack) -> Option<u64> {
         let areas = [
-            self.sides[0]*self.sides[1],
-            self.sides[1]*self.sides[2],
-            self.sides[0]*self.sides[2]
+            self.dims[0].checked_mul(self.dims[1])?,
+            self.dims[1].checked_mul(self.dims[2])?,
+            self.dims[0].checked_mul(self.dims[2])?,
         ];
-        let smallest = *areas.iter().min().unwrap();
-        areas.iter().map(|x| 2 * x).sum::<i32>() + smallest
+        let area = areas.iter().try_fold(0u64, |sum, &x| {
+            sum.checked_add(x.checked_mul(2)?)
+        })?;
+        let extra = match slack {
+            Slack::None            => 0,
+            Slack::SmallestSide    => *areas.iter().min().unwrap(),
+            Slack::Percent(ratio)  => area.checked_mul(ratio)?.div_ceil(100),
+            Slack::Fixed(extra)    => extra,
+        };
+        area.checked_add(extra)
     }
 
     /// Computes the ribbon length required to wrap a present (in feet).
     ///
     /// The ribbon length required for a present is the shortest distance around
-    /// its sides, or the smallest perimeter of any one face.
-    /// Each present also requires a bow made out of ribbon as well; the feet of
-    /// ribbon required for the perfect bow is equal to the cubic feet of volume
-    /// of the present.
-    fn ribbon_length(&self) -> i32 {
-        let perimeters = [
-            2*self.sides[0] + 2*self.sides[1],
-            2*self.sides[1] + 2*self.sides[2],
-            2*self.sides[0] + 2*self.sides[2]
-        ];
-        let smallest = *perimeters.iter().min().unwrap();
-        self.sides.iter().product::<i32>() + smallest
+    /// its sides, or the smallest perimeter of any one face, plus the bow.
+    /// Returns `None` on overflow.
+    fn ribbon_length(&self, bow: Bow) -> Option<u64> {
+        let mut dims = self.dims;
+        dims.sort();
+        let perimeter = dims[0].checked_add(dims[1])?.checked_mul(2)?;
+        let bow = match bow {
+            Bow::None       => 0,
+            Bow::Volume     => dims.iter().try_fold(1u64, |product, &x| {
+                product.checked_mul(x)
+            })?,
+            Bow::Fixed(len) => len,
+        };
+        perimeter.checked_add(bow)
+    }
+
+    /// Computes the price of the paper and ribbon for the present.
+    ///
+    /// Returns `None` on overflow.
+    fn cost(&self, pricing: &Pricing) -> Option<u64> {
+        let paper  = self.package_area(pricing.slack)?
+                         .checked_mul(pricing.paper_price)?;
+        let ribbon = self.ribbon_length(pricing.bow)?
+                         .checked_mul(pricing.ribbon_price)?;
+        paper.checked_add(ribbon)
     }
 }
 
+// }}}
+// {{{ Pricing
+
+/// Extra wrapping paper required for each present.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Slack {
+    /// No extra paper.
+    None,
+    /// The area of the smallest side.
+    SmallestSide,
+    /// A percentage of the paper area (rounded up).
+    Percent(u64),
+    /// A fixed area, in square feet.
+    Fixed(u64),
+}
+
+impl FromStr for Slack {
+    type Err = String;
+
+    /// Builds a `Slack` from a string.
+    ///
+    /// Valid inputs are `none`, `smallest-side`, a percentage (e.g. `5%`) or
+    /// an area (e.g. `12`).
+    fn from_str(s: &str) -> Result<Slack, String> {
+        let invalid = || format!("invalid slack policy: {:?}", s);
+        match s {
+            "none"          => Ok(Slack::None),
+            "smallest-side" => Ok(Slack::SmallestSide),
+            _ if s.ends_with('%') => {
+                s[..s.len() - 1].parse().map(Slack::Percent)
+                                        .map_err(|_| invalid())
+            },
+            _ => s.parse().map(Slack::Fixed).map_err(|_| invalid()),
+        }
+    }
+}
+
+/// Ribbon required for the bow of each present.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Bow {
+    /// No bow.
+    None,
+    /// As many feet as the cubic feet of volume of the present.
+    Volume,
+    /// A fixed length, in feet.
+    Fixed(u64),
+}
+
+impl FromStr for Bow {
+    type Err = String;
+
+    /// Builds a `Bow` from a string.
+    ///
+    /// Valid inputs are `none`, `volume` or a length (e.g. `3`).
+    fn from_str(s: &str) -> Result<Bow, String> {
+        match s {
+            "none"   => Ok(Bow::None),
+            "volume" => Ok(Bow::Volume),
+            _ => s.parse().map(Bow::Fixed)
+                          .map_err(|_| format!("invalid bow policy: {:?}", s)),
+        }
+    }
+}
+
+/// How the elves compute the materials, and their price.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Pricing {
+    slack:        Slack,
+    bow:          Bow,
+    paper_price:  u64,  // Price of a square foot of wrapping paper.
+    ribbon_price: u64,  // Price of a foot of ribbon.
+}
+
+impl Default for Pricing {
+    /// The elves' rules, with every unit costing 1.
+    fn default() -> Pricing {
+        Pricing {
+            slack:        Slack::SmallestSide,
+            bow:          Bow::Volume,
+            paper_price:  1,
+            ribbon_price: 1,
+        }
+    }
+}
+
+impl FromStr for Pricing {
+    type Err = String;
+
+    /// Builds a `Pricing` from a config file.
+    ///
+    /// Each line is a `key = value` pair, where the keys are `slack`, `bow`,
+    /// `paper_price` and `ribbon_price`. Everything after a `#` is ignored,
+    /// and missing keys keep their default value.
+    fn from_str(s: &str) -> Result<Pricing, String> {
+        let mut pricing = Pricing::default();
+
+        for (i, line) in s.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (key, value) = line.split_once('=').ok_or_else(|| {
+                format!("line {}: expected key = value", i + 1)
+            })?;
+            let value  = value.trim();
+            let price  = || value.parse().map_err(|_| {
+                format!("invalid price: {:?}", value)
+            });
+            let result = match key.trim() {
+                "slack"        => value.parse().map(|v| pricing.slack = v),
+                "bow"          => value.parse().map(|v| pricing.bow = v),
+                "paper_price"  => price().map(|v| pricing.paper_price = v),
+                "ribbon_price" => price().map(|v| pricing.ribbon_price = v),
+                key            => Err(format!("unknown key: {:?}", key)),
+            };
+            result.map_err(|err| format!("line {}: {}", i + 1, err))?;
+        }
+        Ok(pricing)
+    }
+}
+
+// }}}
+// {{{ Order
+
+/// What the elves should order for a batch of presents.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct Order {
+    paper:  u64,    // Wrapping paper, in square feet.
+    ribbon: u64,    // Ribbon, in feet.
+    cost:   u64,
+}
+
+impl Order {
+    /// Computes the order for the presents.
+    ///
+    /// Returns `None` on overflow.
+    fn new(presents: &[Present], pricing: &Pricing) -> Option<Order> {
+        presents.iter().try_fold(Order::default(), |order, present| {
+            Some(Order {
+                paper:  order.paper.checked_add(
+                    present.package_area(pricing.slack)?)?,
+                ribbon: order.ribbon.checked_add(
+                    present.ribbon_length(pricing.bow)?)?,
+                cost:   order.cost.checked_add(present.cost(pricing)?)?,
+            })
+        })
+    }
+}
+
+/// Returns the `count` most expensive presents (by index), the most expensive
+/// first.
+///
+/// Presents whose cost overflows (`None`) come first.
+fn most_expensive(presents: &[Present], pricing: &Pricing, count: usize)
+    -> Vec<(usize, Option<u64>)> {
+    let mut costs = presents.iter()
+                            .map(|present| present.cost(pricing))
+                            .enumerate()
+                            .collect::<Vec<_>>();
+    // Stable sort: ties stay in the input order.
+    costs.sort_by_key(|&(_, cost)| Reverse((cost.is_none(), cost)));
+    costs.truncate(count);
+    costs
+}
+
 // }}}
 
 fn main() {
@@ -82,12 +273,32 @@ fn main() {
     let mut input = String::new();
 
     file.read_to_string(&mut input).unwrap();
-    let (paper, ribbon) = input.lines()
-                               .map(|l| l.parse::<Present>().unwrap())
-                               .map(|p| (p.package_area(), p.ribbon_length()))
-                               .fold((0, 0), |sum, v| (sum.0+v.0, sum.1+v.1));
-    println!("The elves should order {} square feet of wrapping paper.", paper);
-    println!("The elves should order {} feet of ribbon.", ribbon);
+    let presents = input.lines().enumerate().map(|(i, line)| {
+        line.parse::<Present>()
+            .map_err(|err| format!("line {}: {}", i + 1, err))
+    }).collect::<Result<Vec<_>, _>>().unwrap();
+
+    // Use the pricing from the config file, if any.
+    let args    = env::args().collect::<Vec<_>>();
+    let pricing = if args.len() == 3 && args[1] == "--config" {
+        let mut config = String::new();
+        File::open(&args[2]).expect("cannot open the config file")
+                            .read_to_string(&mut config).unwrap();
+        config.parse::<Pricing>().unwrap()
+    } else {
+        Pricing::default()
+    };
+
+    let order = Order::new(&presents, &pricing).expect("order overflow");
+    println!("The elves should order {} square feet of wrapping paper.",
+             order.paper);
+    println!("The elves should order {} feet of ribbon.", order.ribbon);
+    println!("The whole order costs {}, the most expensive presents being:",
+             order.cost);
+    for (i, cost) in most_expensive(&presents, &pricing, 3) {
+        println!("- {:?} (line {}) for {}", presents[i].dims, i + 1,
+                 cost.unwrap());
+    }
 }
 
 // {{{ Tests
@@ -96,13 +307,84 @@ fn main() {
 fn examples() {
     let present = "2x3x4".parse::<Present>();
     assert!(present.is_ok());
-    assert_eq!(present.as_ref().unwrap().package_area(),  58);
-    assert_eq!(present.as_ref().unwrap().ribbon_length(), 34);
+    assert_eq!(present.as_ref().unwrap().package_area(Slack::SmallestSide),
+               Some(58));
+    assert_eq!(present.as_ref().unwrap().ribbon_length(Bow::Volume),
+               Some(34));
 
     let present = "1x1x10".parse::<Present>();
     assert!(present.is_ok());
-    assert_eq!(present.as_ref().unwrap().package_area(),  43);
-    assert_eq!(present.as_ref().unwrap().ribbon_length(), 14);
+    assert_eq!(present.as_ref().unwrap().package_area(Slack::SmallestSide),
+               Some(43));
+    assert_eq!(present.as_ref().unwrap().ribbon_length(Bow::Volume),
+               Some(14));
+}
+
+#[test]
+fn invalid_presents() {
+    assert_eq!("2x3".parse::<Present>().unwrap_err(),
+               "expected 3 dimensions, got 2");
+    assert_eq!("2x-3x4".parse::<Present>().unwrap_err(),
+               "invalid dimension: \"-3\"");
+}
+
+#[test]
+fn policies() {
+    let present = "2x3x4".parse::<Present>().unwrap();
+    assert_eq!(present.package_area(Slack::None),        Some(52));
+    assert_eq!(present.package_area(Slack::Percent(10)), Some(58));
+    assert_eq!(present.package_area(Slack::Fixed(1)),    Some(53));
+    assert_eq!(present.ribbon_length(Bow::None),         Some(10));
+    assert_eq!(present.ribbon_length(Bow::Fixed(3)),     Some(13));
+
+    let huge = "4294967296x4294967296x2".parse::<Present>().unwrap();
+    assert_eq!(huge.package_area(Slack::None), None);
+    assert_eq!(huge.ribbon_length(Bow::None),  Some(8589934596));
+    assert_eq!(huge.ribbon_length(Bow::Volume), None);
+}
+
+#[test]
+fn config() {
+    let pricing = "\
+# Cheap elves.
+slack = 10%
+bow   = none # Who needs a bow?
+
+paper_price = 3
+".parse::<Pricing>().unwrap();
+    assert_eq!(pricing, Pricing {
+        slack:        Slack::Percent(10),
+        bow:          Bow::None,
+        paper_price:  3,
+        ribbon_price: 1,
+    });
+    assert_eq!("2x3x4".parse::<Present>().unwrap().cost(&pricing),
+               Some(3 * 58 + 10));
+
+    assert_eq!("slack = lots".parse::<Pricing>().unwrap_err(),
+               "line 1: invalid slack policy: \"lots\"");
+    assert_eq!("\nbow".parse::<Pricing>().unwrap_err(),
+               "line 2: expected key = value");
+    assert_eq!("glitter = 1".parse::<Pricing>().unwrap_err(),
+               "line 1: unknown key: \"glitter\"");
+    assert_eq!("paper_price = -1".parse::<Pricing>().unwrap_err(),
+               "line 1: invalid price: \"-1\"");
+}
+
+#[test]
+fn batch() {
+    let presents = ["2x3x4", "1x1x10", "4294967296x4294967296x2", "3x2x4"]
+        .iter().map(|s| s.parse::<Present>().unwrap()).collect::<Vec<_>>();
+    let pricing = Pricing::default();
+
+    assert_eq!(Order::new(&presents[..2], &pricing), Some(Order {
+        paper:  58 + 43,
+        ribbon: 34 + 14,
+        cost:   58 + 43 + 34 + 14,
+    }));
+    assert_eq!(Order::new(&presents, &pricing), None);
+    assert_eq!(most_expensive(&presents, &pricing, 3),
+               vec![(2, None), (0, Some(92)), (3, Some(92))]);
 }
 
 // }}}
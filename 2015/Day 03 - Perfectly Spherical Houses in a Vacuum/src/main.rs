@@ -12,7 +12,9 @@
 // }}}
 // {{{ Crates
 
-use std::collections::HashSet;
+use std::cmp;
+use std::collections::{HashMap, HashSet};
+use std::env;
 use std::fs::File;
 use std::io::BufRead;
 use std::io::BufReader;
@@ -66,6 +68,124 @@ impl Point {
     }
 }
 
+// }}}
+// {{{ Delivery
+
+/// Keeps track of the presents delivered by Santa's crew.
+#[derive(Debug)]
+struct Delivery {
+    agents:   Vec<Point>,             // Current location of each agent.
+    visits:   HashMap<Point, u32>,    // Presents received by each house.
+    coverage: Vec<HashSet<Point>>,    // Houses visited by each agent.
+}
+
+impl Delivery {
+    /// Starts a delivery, each agent leaving a present at its location.
+    fn new(agents: &[Point]) -> Delivery {
+        let mut delivery = Delivery {
+            agents:   agents.to_vec(),
+            visits:   HashMap::new(),
+            coverage: vec![HashSet::new(); agents.len()],
+        };
+        for (idx, &house) in agents.iter().enumerate() {
+            delivery.drop_present(idx, house);
+        }
+        delivery
+    }
+
+    /// Moves the agents according to the elf's directions.
+    ///
+    /// `dispatch` picks the agent (by index) that follows the i-th direction.
+    fn deliver<F>(&mut self, instructions: &str, mut dispatch: F)
+        where F: FnMut(usize, Direction) -> usize {
+        let instructions = instructions.chars().map(Direction::from);
+        for (i, direction) in instructions.enumerate() {
+            let idx = dispatch(i, direction);
+            assert!(idx < self.agents.len(), "invalid agent: {}", idx);
+            self.agents[idx].mv(direction);
+            let house = self.agents[idx];
+            self.drop_present(idx, house);
+        }
+    }
+
+    /// Leaves a present at `house`, on behalf of the given agent.
+    fn drop_present(&mut self, agent: usize, house: Point) {
+        *self.visits.entry(house).or_insert(0) += 1;
+        self.coverage[agent].insert(house);
+    }
+
+    /// Returns the current location of the agents.
+    fn agents(&self) -> &[Point] {
+        &self.agents
+    }
+
+    /// Returns the number of presents received by each visited house.
+    fn visits(&self) -> &HashMap<Point, u32> {
+        &self.visits
+    }
+
+    /// Returns the number of presents received by the least visited house.
+    fn min_visits(&self) -> u32 {
+        self.visits.values().cloned().min().unwrap_or(0)
+    }
+
+    /// Returns the number of houses visited by each agent.
+    fn coverage(&self) -> Vec<usize> {
+        self.coverage.iter().map(HashSet::len).collect()
+    }
+
+    /// Renders the number of presents received by each house, North up.
+    ///
+    /// The darker the cell, the more presents were received.
+    fn heat_map(&self) -> String {
+        const SHADES: &[u8] = b" .:-=+*#%@";
+        let max = self.visits.values().cloned().max().unwrap_or(0);
+        let (xmin, xmax, ymin, ymax) = self.visits.keys().fold(
+            (i32::MAX, i32::MIN, i32::MAX, i32::MIN),
+            |(xmin, xmax, ymin, ymax), p| {
+                (cmp::min(xmin, p.x), cmp::max(xmax, p.x),
+                 cmp::min(ymin, p.y), cmp::max(ymax, p.y))
+            });
+        let mut map = String::new();
+
+        for y in (ymin..ymax + 1).rev() {
+            for x in xmin..xmax + 1 {
+                let count = self.visits.get(&Point { x, y }).cloned()
+                                       .unwrap_or(0);
+                // Any visited house gets at least the lightest shade.
+                let shade = (count * (SHADES.len() as u32 - 1)).div_ceil(max);
+                map.push(char::from(SHADES[shade as usize]));
+            }
+            map.push('\n');
+        }
+        map
+    }
+}
+
+/// Returns a dispatch rule dealing the directions to the agents in turn.
+///
+/// There must be at least one agent.
+fn round_robin(agents: usize) -> impl FnMut(usize, Direction) -> usize {
+    assert!(agents > 0, "no agent to dispatch the directions to");
+    move |i, _| i % agents
+}
+
+/// Runs a delivery with `agents` agents taking turns, from the same house.
+fn simulate(instructions: &str, agents: usize) -> Delivery {
+    let mut delivery = Delivery::new(&vec![Point { x: 0, y: 0 }; agents]);
+    delivery.deliver(instructions, round_robin(agents));
+    delivery
+}
+
+/// Returns the smallest crew for which every visited house receives at least
+/// `presents` presents (up to `max_agents` agents).
+fn smallest_crew(instructions: &str, presents: u32, max_agents: usize)
+    -> Option<usize> {
+    (1..max_agents + 1).find(|&agents| {
+        simulate(instructions, agents).min_visits() >= presents
+    })
+}
+
 // }}}
 
 /// Moves Santa's crew according to the elf's directions, and keep track of the
@@ -73,16 +193,11 @@ impl Point {
 fn follow_instructions(instructions: &str,
                        santa_crew:   &mut[Point],
                        houses:       &mut HashSet<Point>) {
-    // Everyone starts at the same position, we only need to insert the first.
-    houses.insert(santa_crew[0]);
-
-    let instructions = instructions.chars().map(Direction::from);
-    for (crew, direction) in instructions.enumerate() {
-        let worker_idx = crew % santa_crew.len();
-        let worker     = &mut santa_crew[worker_idx];
-        worker.mv(direction);
-        houses.insert(*worker);
-    }
+    let mut delivery = Delivery::new(santa_crew);
+    delivery.deliver(instructions, round_robin(santa_crew.len()));
+
+    santa_crew.copy_from_slice(delivery.agents());
+    houses.extend(delivery.visits().keys());
 }
 
 fn main() {
@@ -93,6 +208,15 @@ fn main() {
     BufReader::new(&file).read_line(&mut input).unwrap();
     assert!(!input.is_empty());
 
+    // Render the delivery of a bigger crew.
+    let args = env::args().collect::<Vec<_>>();
+    if args.len() == 3 && args[1] == "--heat-map" {
+        let agents = args[2].parse().ok().filter(|&agents| agents > 0)
+                            .expect("invalid number of agents");
+        print!("{}", simulate(&input, agents).heat_map());
+        return;
+    }
+
     let mut houses = HashSet::new();
     let mut santa  = [Point { x: 0, y: 0 }; 1];
     follow_instructions(&input, &mut santa, &mut houses);
@@ -104,6 +228,15 @@ fn main() {
     follow_instructions(&input, &mut santa_and_bot, &mut houses);
     println!("{} houses received at least one present from Santa's crew.",
              houses.len());
+
+    match smallest_crew(&input, 2, 64) {
+        Some(agents) => {
+            println!("With {} agents, every house gets at least two presents \
+                      (coverage: {:?}).",
+                     agents, simulate(&input, agents).coverage());
+        },
+        None => println!("Even 64 agents leave some houses with one present."),
+    }
 }
 
 // {{{ Tests
@@ -144,4 +277,47 @@ fn examples_part2() {
     assert_eq!(houses.len(), 11);
 }
 
+#[test]
+fn crew() {
+    // Santa goes North then West, his robot East then North, and the third
+    // one South.
+    let delivery = simulate("^>v<^", 3);
+    assert_eq!(delivery.agents(), &[Point { x: -1, y: 1 },
+                                    Point { x: 1, y: 1 },
+                                    Point { x: 0, y: -1 }]);
+    assert_eq!(delivery.visits().len(), 6);
+    assert_eq!(delivery.visits()[&Point { x: 0, y: 0 }], 3);
+    assert_eq!(delivery.min_visits(), 1);
+    assert_eq!(delivery.coverage(), vec![3, 3, 2]);
+
+    // Going back and forth, Santa visits both houses twice by himself.
+    assert_eq!(smallest_crew("^v^v", 2, 10), Some(1));
+    assert_eq!(smallest_crew("^^^>", 2, 3), None);
+}
+
+#[test]
+fn custom_dispatch() {
+    // Vertical moves for Santa, horizontal ones for Robo-Santa.
+    let mut delivery = Delivery::new(&[Point { x: 0, y: 0 }; 2]);
+    delivery.deliver("^>^<<", |_, direction| match direction {
+        Direction::North | Direction::South => 0,
+        Direction::East  | Direction::West  => 1,
+    });
+    assert_eq!(delivery.agents(), &[Point { x: 0, y: 2 },
+                                    Point { x: -1, y: 0 }]);
+    assert_eq!(delivery.coverage(), vec![3, 3]);
+}
+
+#[test]
+fn heat_map() {
+    let delivery = simulate("^>v<^", 3);
+    assert_eq!(delivery.heat_map(), "---\n @-\n - \n");
+}
+
+#[test]
+#[should_panic(expected = "no agent to dispatch the directions to")]
+fn empty_crew() {
+    simulate("^>v<", 0);
+}
+
 // }}}
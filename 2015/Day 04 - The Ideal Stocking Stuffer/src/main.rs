@@ -9,238 +9,182 @@
         unused_qualifications
 )]
 
-#![allow(inline_always)]
-
 // }}}
 // {{{ Crates
 
-extern crate byteorder;
+extern crate md5;
 
 use std::fs::File;
 use std::io::BufRead;
 use std::io::BufReader;
+use std::thread;
 
 // }}}
-// {{{ MD5
-
-mod md5 {
-    use std::io::Cursor;
-    use std::mem;
-    use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
-
-    #[cfg_attr(feature = "cargo-clippy", allow(many_single_char_names))]
-    pub fn digest(bytes : &[u8]) -> [u8; 16] {
-        let mut digest : [u8; 16] = unsafe { mem::uninitialized() };
-        let mut buf               = bytes.to_vec();
 
-        // {{{ 1. Padding
+// {{{ Miner
+
+/// A parallel AdventCoin miner.
+mod miner {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::mpsc;
+    use std::thread;
+    use std::time::{Duration, Instant};
+    use md5::{self, Md5, LANES};
+
+    /// Number of consecutive nonces handed out to a worker at once.
+    const CHUNK_SIZE: u64 = 4096;
+
+    /// Delay between two progress reports.
+    const PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+    /// Statistics about a mining run.
+    #[derive(Clone, Copy, Debug)]
+    pub struct Report {
+        /// The smallest valid nonce (`None` while searching, or if there is
+        /// no solution).
+        pub nonce:   Option<u64>,
+        /// Number of hashes computed so far.
+        pub hashes:  u64,
+        /// Time elapsed since the beginning of the run.
+        pub elapsed: Duration,
+    }
 
-        buf.push(0x80u8);
-        if buf.len()%64 > 56 {
-            let buf_len = buf.len();
-            buf.extend(vec![0; 64 - buf_len%64].iter());
+    impl Report {
+        /// Returns the number of hashes computed per second.
+        pub fn hash_rate(&self) -> f64 {
+            let secs = self.elapsed.as_secs_f64();
+            if secs > 0.0 { self.hashes as f64 / secs } else { 0.0 }
         }
-        let buf_len = buf.len();
-        buf.extend(vec![0; 56 - buf_len%64].iter());
-
-        // }}}
-        // {{{ 2. Append length
-
-        let lo : u32 = ((bytes.len() * 8) & 0x0000_FFFF) as u32;
-        let hi : u32 = ((bytes.len() * 8) >> 32)         as u32;
-        buf.write_u32::<LittleEndian>(lo).unwrap();
-        buf.write_u32::<LittleEndian>(hi).unwrap();
-        let buf_len = buf.len() as u64;
-        assert_eq!(buf_len % 64, 0,
-                   "message should be an exact multiple of 512 bits");
-
-        // }}}
-        // {{{ 3. Initialize MD Buffer
-
-        let mut state = [0x6745_2301, 0xefcd_ab89, 0x98ba_dcfe, 0x1032_5476];
-
-        // }}}
-        // {{{ 4. Process Message in 16-Word Blocks
-
-        let mut rdr = Cursor::new(buf);
-        // Process each 16-word block.
-        while rdr.position() < buf_len {
-            let mut x : [u32; 16] = unsafe { mem::uninitialized() };
-            // Copy current block into X.
-            for item in &mut x {
-                *item = rdr.read_u32::<LittleEndian>().unwrap();
-            }
+    }
 
-            let mut a = state[0];
-            let mut b = state[1];
-            let mut c = state[2];
-            let mut d = state[3];
-            // {{{ Round 1
-
-            a = apply(function_f, a, b, c, d, x[ 0],  7, 0xd76a_a478); // 1
-            d = apply(function_f, d, a, b, c, x[ 1], 12, 0xe8c7_b756); // 2
-            c = apply(function_f, c, d, a, b, x[ 2], 17, 0x2420_70db); // 3
-            b = apply(function_f, b, c, d, a, x[ 3], 22, 0xc1bd_ceee); // 4
-            a = apply(function_f, a, b, c, d, x[ 4],  7, 0xf57c_0faf); // 5
-            d = apply(function_f, d, a, b, c, x[ 5], 12, 0x4787_c62a); // 6
-            c = apply(function_f, c, d, a, b, x[ 6], 17, 0xa830_4613); // 7
-            b = apply(function_f, b, c, d, a, x[ 7], 22, 0xfd46_9501); // 8
-            a = apply(function_f, a, b, c, d, x[ 8],  7, 0x6980_98d8); // 9
-            d = apply(function_f, d, a, b, c, x[ 9], 12, 0x8b44_f7af); // 10
-            c = apply(function_f, c, d, a, b, x[10], 17, 0xffff_5bb1); // 11
-            b = apply(function_f, b, c, d, a, x[11], 22, 0x895c_d7be); // 12
-            a = apply(function_f, a, b, c, d, x[12],  7, 0x6b90_1122); // 13
-            d = apply(function_f, d, a, b, c, x[13], 12, 0xfd98_7193); // 14
-            c = apply(function_f, c, d, a, b, x[14], 17, 0xa679_438e); // 15
-            b = apply(function_f, b, c, d, a, x[15], 22, 0x49b4_0821); // 16
-
-            // }}}
-            // {{{ Round 2
-
-            a = apply(function_g, a, b, c, d, x[ 1],  5, 0xf61e_2562); // 17
-            d = apply(function_g, d, a, b, c, x[ 6],  9, 0xc040_b340); // 18
-            c = apply(function_g, c, d, a, b, x[11], 14, 0x265e_5a51); // 19
-            b = apply(function_g, b, c, d, a, x[ 0], 20, 0xe9b6_c7aa); // 20
-            a = apply(function_g, a, b, c, d, x[ 5],  5, 0xd62f_105d); // 21
-            d = apply(function_g, d, a, b, c, x[10],  9, 0x0244_1453); // 22
-            c = apply(function_g, c, d, a, b, x[15], 14, 0xd8a1_e681); // 23
-            b = apply(function_g, b, c, d, a, x[ 4], 20, 0xe7d3_fbc8); // 24
-            a = apply(function_g, a, b, c, d, x[ 9],  5, 0x21e1_cde6); // 25
-            d = apply(function_g, d, a, b, c, x[14],  9, 0xc337_07d6); // 26
-            c = apply(function_g, c, d, a, b, x[ 3], 14, 0xf4d5_0d87); // 27
-            b = apply(function_g, b, c, d, a, x[ 8], 20, 0x455a_14ed); // 28
-            a = apply(function_g, a, b, c, d, x[13],  5, 0xa9e3_e905); // 29
-            d = apply(function_g, d, a, b, c, x[ 2],  9, 0xfcef_a3f8); // 30
-            c = apply(function_g, c, d, a, b, x[ 7], 14, 0x676f_02d9); // 31
-            b = apply(function_g, b, c, d, a, x[12], 20, 0x8d2a_4c8a); // 32
-
-            // }}}
-            // {{{ Round 3
-
-            a = apply(function_h, a, b, c, d, x[ 5],  4, 0xfffa_3942); // 33
-            d = apply(function_h, d, a, b, c, x[ 8], 11, 0x8771_f681); // 34
-            c = apply(function_h, c, d, a, b, x[11], 16, 0x6d9d_6122); // 35
-            b = apply(function_h, b, c, d, a, x[14], 23, 0xfde5_380c); // 36
-            a = apply(function_h, a, b, c, d, x[ 1],  4, 0xa4be_ea44); // 37
-            d = apply(function_h, d, a, b, c, x[ 4], 11, 0x4bde_cfa9); // 38
-            c = apply(function_h, c, d, a, b, x[ 7], 16, 0xf6bb_4b60); // 39
-            b = apply(function_h, b, c, d, a, x[10], 23, 0xbebf_bc70); // 40
-            a = apply(function_h, a, b, c, d, x[13],  4, 0x289b_7ec6); // 41
-            d = apply(function_h, d, a, b, c, x[ 0], 11, 0xeaa1_27fa); // 42
-            c = apply(function_h, c, d, a, b, x[ 3], 16, 0xd4ef_3085); // 43
-            b = apply(function_h, b, c, d, a, x[ 6], 23, 0x0488_1d05); // 44
-            a = apply(function_h, a, b, c, d, x[ 9],  4, 0xd9d4_d039); // 45
-            d = apply(function_h, d, a, b, c, x[12], 11, 0xe6db_99e5); // 46
-            c = apply(function_h, c, d, a, b, x[15], 16, 0x1fa2_7cf8); // 47
-            b = apply(function_h, b, c, d, a, x[ 2], 23, 0xc4ac_5665); // 48
-
-            // }}}
-            // {{{ Round 4
-
-            a = apply(function_i, a, b, c, d, x[ 0],  6, 0xf429_2244); // 49
-            d = apply(function_i, d, a, b, c, x[ 7], 10, 0x432a_ff97); // 50
-            c = apply(function_i, c, d, a, b, x[14], 15, 0xab94_23a7); // 51
-            b = apply(function_i, b, c, d, a, x[ 5], 21, 0xfc93_a039); // 52
-            a = apply(function_i, a, b, c, d, x[12],  6, 0x655b_59c3); // 53
-            d = apply(function_i, d, a, b, c, x[ 3], 10, 0x8f0c_cc92); // 54
-            c = apply(function_i, c, d, a, b, x[10], 15, 0xffef_f47d); // 55
-            b = apply(function_i, b, c, d, a, x[ 1], 21, 0x8584_5dd1); // 56
-            a = apply(function_i, a, b, c, d, x[ 8],  6, 0x6fa8_7e4f); // 57
-            d = apply(function_i, d, a, b, c, x[15], 10, 0xfe2c_e6e0); // 58
-            c = apply(function_i, c, d, a, b, x[ 6], 15, 0xa301_4314); // 59
-            b = apply(function_i, b, c, d, a, x[13], 21, 0x4e08_11a1); // 60
-            a = apply(function_i, a, b, c, d, x[ 4],  6, 0xf753_7e82); // 61
-            d = apply(function_i, d, a, b, c, x[11], 10, 0xbd3a_f235); // 62
-            c = apply(function_i, c, d, a, b, x[ 2], 15, 0x2ad7_d2bb); // 63
-            b = apply(function_i, b, c, d, a, x[ 9], 21, 0xeb86_d391); // 64
-
-            // }}}
-            state[0] = state[0].wrapping_add(a);
-            state[1] = state[1].wrapping_add(b);
-            state[2] = state[2].wrapping_add(c);
-            state[3] = state[3].wrapping_add(d);
+    /// Returns a predicate that checks if the hexadecimal representation of a
+    /// hash starts with (at least) `count` zeros.
+    ///
+    /// A hash only has 32 hexadecimal characters: for a greater `count`, the
+    /// predicate is always false.
+    pub fn leading_zeros(count: usize) -> impl Fn(&[u8; 16]) -> bool {
+        // As one byte == two hex characters, we test the whole bytes and then
+        // the 4 high bits of the next one if `count` is odd.
+        move |hash| {
+            count <= 2 * hash.len()
+                && hash[..count / 2].iter().all(|&byte| byte == 0)
+                && (count.is_multiple_of(2) || hash[count / 2] >> 4 == 0)
         }
-
-        // }}}
-        // {{{ 5. Output
-
-        let mut buf = Vec::with_capacity(16);
-
-        buf.write_u32::<LittleEndian>(state[0]).unwrap();
-        buf.write_u32::<LittleEndian>(state[1]).unwrap();
-        buf.write_u32::<LittleEndian>(state[2]).unwrap();
-        buf.write_u32::<LittleEndian>(state[3]).unwrap();
-
-        assert_eq!(buf.len(), 16);
-
-        digest.clone_from_slice(&buf);
-
-        // }}}
-
-        digest
     }
 
-    #[inline(always)]
-    fn function_f(x: u32, y : u32, z: u32) -> u32 {
-        (x & y) | (!x & z)
-    }
+    /// Find the smallest nonce, below `limit`, such that the hash of `key`
+    /// followed by the nonce satisfies `pred`.
+    ///
+    /// The nonces are split in chunks, handed out in order to `threads`
+    /// workers which stop as soon as no chunk can contain a smaller solution,
+    /// or once every nonce below `limit` has been tried.
+    /// `progress` is called periodically while the search is running.
+    pub fn mine<F, P>(key: &str, threads: usize, limit: u64, pred: F,
+                      mut progress: P)
+        -> Report
+        where F: Fn(&[u8; 16]) -> bool + Sync,
+              P: FnMut(&Report) {
+        // The key is the same for every candidate: hash it only once.
+        let mut prefix = Md5::new();
+        prefix.update(key.as_bytes());
+
+        let start      = Instant::now();
+        let next_chunk = AtomicU64::new(0);
+        let best       = AtomicU64::new(u64::MAX);
+        let hashes     = AtomicU64::new(0);
+        let report     = |nonce| Report {
+            nonce,
+            hashes:  hashes.load(Ordering::Relaxed),
+            elapsed: start.elapsed(),
+        };
+
+        thread::scope(|scope| {
+            let (tx, rx) = mpsc::channel::<()>();
+            for _ in 0..threads.max(1) {
+                // The channel is closed once every worker dropped its sender.
+                let tx = tx.clone();
+                let (prefix, pred) = (&prefix, &pred);
+                let (next_chunk, best, hashes) = (&next_chunk, &best, &hashes);
+                scope.spawn(move || {
+                    work(prefix, pred, limit, next_chunk, best, hashes);
+                    drop(tx);
+                });
+            }
+            drop(tx);
 
-    #[inline(always)]
-    fn function_g(x: u32, y : u32, z: u32) -> u32 {
-        (x & z) | (y & !z)
-    }
+            while let Err(mpsc::RecvTimeoutError::Timeout) =
+                rx.recv_timeout(PROGRESS_INTERVAL)
+            {
+                progress(&report(None));
+            }
+        });
 
-    #[inline(always)]
-    fn function_h(x: u32, y : u32, z: u32) -> u32 {
-        x ^ y ^ z
+        let nonce = best.load(Ordering::Relaxed);
+        report(if nonce == u64::MAX { None } else { Some(nonce) })
     }
 
-    #[inline(always)]
-    fn function_i(x: u32, y : u32, z: u32) -> u32 {
-        y ^ (x | !z)
-    }
+    // Process chunks of nonces until the smallest solution is known, or until
+    // the nonces below `limit` are exhausted.
+    fn work<F>(prefix: &Md5, pred: &F, limit: u64,
+               next_chunk: &AtomicU64, best: &AtomicU64, hashes: &AtomicU64)
+        where F: Fn(&[u8; 16]) -> bool {
+        let mut digits = [0; 20];
+
+        loop {
+            let chunk = next_chunk.fetch_add(1, Ordering::Relaxed);
+            let start = match chunk.checked_mul(CHUNK_SIZE) {
+                Some(start) if start < limit => start,
+                _                            => return,
+            };
+            let end = start.saturating_add(CHUNK_SIZE).min(limit);
+            let mut count = 0;
+
+            // Chunks are handed out in order: no need to look further once a
+            // solution has been found before this chunk.
+            let mut nonce = start;
+            while nonce < end && nonce < best.load(Ordering::Relaxed) {
+                // The last lanes may go past the end of the chunk.
+                let lanes = (end - nonce).min(LANES as u64) as usize;
+                let mut hashers = [*prefix; LANES];
+                for (lane, hasher) in hashers.iter_mut().enumerate() {
+                    let candidate = nonce.saturating_add(lane as u64);
+                    hasher.update(format_decimal(candidate, &mut digits));
+                }
+                count += lanes as u64;
+
+                let digests = md5::finalize_lanes(&hashers);
+                if let Some(lane) = digests[..lanes].iter().position(pred) {
+                    best.fetch_min(nonce + lane as u64, Ordering::Relaxed);
+                }
+                nonce += lanes as u64;
+            }
 
-    #[cfg_attr(feature = "cargo-clippy",
-               allow(too_many_arguments,many_single_char_names))]
-    #[inline(always)]
-    fn apply<F>(fun: F, a: u32, b: u32, c: u32, d: u32, xk: u32, s:u32, ti: u32)
-        -> u32
-        where F: Fn(u32, u32, u32) -> u32 {
-        let mut tmp = a.wrapping_add(fun(b, c, d));
-        tmp = tmp.wrapping_add(xk);
-        tmp = tmp.wrapping_add(ti);
-        b.wrapping_add(tmp.rotate_left(s))
+            hashes.fetch_add(count, Ordering::Relaxed);
+            if start >= best.load(Ordering::Relaxed) {
+                return;
+            }
+        }
     }
-}
 
-// }}}
-
-fn mine<F>(key : &str, pred: F) -> u64
-    where F: Fn(&[u8; 16]) -> bool {
-    for n in 0u64.. {
-        let data = format!("{}{}", key, n);
-        let hash = md5::digest(data.as_bytes());
-        if pred(&hash) {
-            return n;
+    /// Writes the decimal representation of `n` at the end of `buf`, returns
+    /// the digits.
+    pub fn format_decimal(mut n: u64, buf: &mut [u8; 20]) -> &[u8] {
+        let mut idx = buf.len();
+        loop {
+            idx -= 1;
+            buf[idx] = b'0' + (n % 10) as u8;
+            n /= 10;
+            if n == 0 {
+                return &buf[idx..];
+            }
         }
     }
-    assert!(false, "no solution found");
-    0
 }
 
-fn check_five_zeros(hash: &[u8; 16]) -> bool {
-    // As one byte == two hex character, we have to tests the 2.5 bytes (two
-    // whole bytes + the 4 high bits of the third byte) to see if the digest
-    // starts with 5 zeroes.
-    (hash[0] | hash[1] | (hash[2] >> 4)) == 0
-}
+// }}}
 
-fn check_six_zeros(hash: &[u8; 16]) -> bool {
-    // As one byte == two hex character, we have to tests the 2.5 bytes (two
-    // whole bytes + the 4 high bits of the third byte) to see if the digest
-    // starts with 5 zeroes.
-    (hash[0] | hash[1] | hash[2]) == 0
-}
+/// Nonces are searched below this bound.
+const MAX_NONCE: u64 = 1 << 32;
 
 fn main() {
     let file = File::open("input.txt").expect("cannot open input.txt");
@@ -252,55 +196,84 @@ fn main() {
     // Remove trailing new line.
     input.pop();
 
-    println!("The solution for five zeros is {}.",
-             mine(&input, check_five_zeros));
-    println!("The solution for six zeros is {}.",
-             mine(&input, check_six_zeros));
+    let threads = thread::available_parallelism().map(|n| n.get())
+                                                 .unwrap_or(1);
+    for &zeros in &[5, 6] {
+        let report = miner::mine(&input, threads, MAX_NONCE,
+                                 miner::leading_zeros(zeros), |report| {
+            eprint!("\rMining with {} zeros: {} hashes ({:.2} MH/s)",
+                    zeros, report.hashes, report.hash_rate() / 1e6);
+        });
+        eprintln!();
+        match report.nonce {
+            Some(nonce) => {
+                println!("The solution for {} zeros is {} ({} hashes, \
+                          {:.2} MH/s).", zeros, nonce,
+                         report.hashes, report.hash_rate() / 1e6);
+            },
+            None => println!("There is no solution for {} zeros below {}.",
+                             zeros, MAX_NONCE),
+        }
+    }
 }
 
 // {{{ Tests
 
 #[test]
-fn test_md5_rfc1321() {
-    // See §A.5 in RFC 1321.
-    let testcases: &[(&str, &[u8; 16])] = &[
-        ("",
-         &[0xd4, 0x1d, 0x8c, 0xd9, 0x8f, 0x00, 0xb2, 0x04,
-           0xe9, 0x80, 0x09, 0x98, 0xec, 0xf8, 0x42, 0x7e]
-        ),
-        ("a",
-         &[0x0c, 0xc1, 0x75, 0xb9, 0xc0, 0xf1, 0xb6, 0xa8,
-           0x31, 0xc3, 0x99, 0xe2, 0x69, 0x77, 0x26, 0x61]
-        ),
-        ("abc",
-         &[0x90, 0x01, 0x50, 0x98, 0x3c, 0xd2, 0x4f, 0xb0,
-           0xd6, 0x96, 0x3f, 0x7d, 0x28, 0xe1, 0x7f, 0x72]
-        ),
-        ("message digest",
-         &[0xf9, 0x6b, 0x69, 0x7d, 0x7c, 0xb7, 0x93, 0x8d,
-           0x52, 0x5a, 0x2f, 0x31, 0xaa, 0xf1, 0x61, 0xd0]
-        ),
-        ("abcdefghijklmnopqrstuvwxyz",
-         &[0xc3, 0xfc, 0xd3, 0xd7, 0x61, 0x92, 0xe4, 0x00,
-           0x7d, 0xfb, 0x49, 0x6c, 0xca, 0x67, 0xe1, 0x3b]
-        ),
-        ("ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789",
-         &[0xd1, 0x74, 0xab, 0x98, 0xd2, 0x77, 0xd9, 0xf5,
-           0xa5, 0x61, 0x1c, 0x2c, 0x9f, 0x41, 0x9d, 0x9f]
-        ),
-        ("12345678901234567890123456789012345678901234567890123456789012345678901234567890",
-         &[0x57, 0xed, 0xf4, 0xa2, 0x2b, 0xe3, 0xc9, 0x55,
-           0xac, 0x49, 0xda, 0x2e, 0x21, 0x07, 0xb6, 0x7a]
-        )
-    ];
-    for &(data, expected) in testcases.iter() {
-        assert_eq!(md5::digest(data.as_bytes()), *expected);
+fn examples_part1() {
+    for &threads in &[1, 4] {
+        let report = miner::mine("abcdef", threads, u64::MAX,
+                                 miner::leading_zeros(5), |_| ());
+        assert_eq!(report.nonce, Some(609043));
+        let report = miner::mine("pqrstuv", threads, u64::MAX,
+                                 miner::leading_zeros(5), |_| ());
+        assert_eq!(report.nonce, Some(1048970));
     }
 }
 
 #[test]
-fn examples_part1() {
-    assert_eq!(mine("abcdef", check_five_zeros), 609043);
+fn exhausted_nonces() {
+    // The search stops once every nonce below the limit was tried.
+    for &threads in &[1, 4] {
+        let report = miner::mine("abcdef", threads, 10_001, |_| false, |_| ());
+        assert_eq!(report.nonce, None);
+        assert_eq!(report.hashes, 10_001);
+        let report = miner::mine("abcdef", threads, 100_000,
+                                 miner::leading_zeros(33), |_| ());
+        assert_eq!(report.nonce, None);
+    }
+    // The limit is exclusive.
+    let report = miner::mine("abcdef", 2, 609043, miner::leading_zeros(5),
+                             |_| ());
+    assert_eq!(report.nonce, None);
+    let report = miner::mine("abcdef", 2, 609044, miner::leading_zeros(5),
+                             |_| ());
+    assert_eq!(report.nonce, Some(609043));
+}
+
+#[test]
+fn leading_zeros() {
+    let hash = [0x00, 0x00, 0x0f, 0xff, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+
+    assert!(miner::leading_zeros(0)(&hash));
+    assert!(miner::leading_zeros(5)(&hash));
+    assert!(!miner::leading_zeros(6)(&hash));
+
+    let zero = [0; 16];
+    assert!(miner::leading_zeros(31)(&zero));
+    assert!(miner::leading_zeros(32)(&zero));
+    assert!(!miner::leading_zeros(33)(&zero));
+    assert!(!miner::leading_zeros(usize::MAX)(&zero));
+}
+
+#[test]
+fn format_decimal() {
+    let mut buf = [0; 20];
+
+    assert_eq!(miner::format_decimal(0, &mut buf), b"0");
+    assert_eq!(miner::format_decimal(609043, &mut buf), b"609043");
+    assert_eq!(miner::format_decimal(u64::MAX, &mut buf),
+               b"18446744073709551615");
 }
 
 // }}}
@@ -12,61 +12,217 @@
 // }}}
 // {{{ Crates
 
-use std::collections::HashMap;
-use std::collections::hash_map::Entry;
+use std::env;
 use std::fs::File;
 use std::io::Read;
 
+use rules::Rule;
+
 // }}}
+// {{{ Rules
 
-fn is_nice_string(s: &str) -> bool {
-    let mut has_pair = false;
-    for pair in s.as_bytes().windows(2) {
-        // Check for ab, cd, pq, or xy.
-        if pair == b"ab" || pair == b"cd" || pair == b"pq" || pair == b"xy" {
-            return false;
+/// Composable rules telling nice strings from naughty ones.
+mod rules {
+    use std::collections::HashMap;
+    use std::collections::hash_map::Entry;
+    use std::fmt;
+    use std::ops;
+
+    /// A rule a string must follow to be nice.
+    #[derive(Clone, Debug)]
+    pub enum Rule {
+        /// Contains at least this many vowels.
+        MinVowels(usize),
+        /// Contains none of these substrings.
+        Forbidden(Vec<String>),
+        /// Contains a letter repeated with exactly this many letters between.
+        RepeatedWithGap(usize),
+        /// Contains a pair of letters appearing twice, without overlapping.
+        PairTwice,
+        /// Inverts a rule.
+        Not(Box<Rule>),
+        /// Every rule must pass.
+        All(Vec<Rule>),
+        /// At least one rule must pass.
+        Any(Vec<Rule>),
+    }
+
+    impl Rule {
+        /// Builds a rule forbidding some substrings.
+        pub fn forbidden(substrings: &[&str]) -> Rule {
+            Rule::Forbidden(substrings.iter().map(|s| s.to_string()).collect())
         }
-        // Search one letter that appears twice in a row.
-        if pair[0] == pair[1] {
-            has_pair = true;
+
+        /// Combines two rules that must both pass.
+        pub fn and(self, other: Rule) -> Rule {
+            match self {
+                Rule::All(mut rules) => { rules.push(other); Rule::All(rules) },
+                rule                 => Rule::All(vec![rule, other]),
+            }
+        }
+
+        /// Combines two rules of which at least one must pass.
+        pub fn or(self, other: Rule) -> Rule {
+            match self {
+                Rule::Any(mut rules) => { rules.push(other); Rule::Any(rules) },
+                rule                 => Rule::Any(vec![rule, other]),
+            }
+        }
+
+        /// Checks if the string follows the rule.
+        pub fn matches(&self, s: &str) -> bool {
+            let bytes = s.as_bytes();
+            match *self {
+                Rule::MinVowels(count) => {
+                    let vowels = bytes.iter().filter(|c| b"aeiou".contains(c));
+                    vowels.count() >= count
+                },
+                Rule::Forbidden(ref substrings) => {
+                    !substrings.iter().any(|sub| s.contains(sub.as_str()))
+                },
+                Rule::RepeatedWithGap(gap) => {
+                    bytes.windows(gap + 2).any(|w| w[0] == w[gap + 1])
+                },
+                Rule::PairTwice      => has_pair_twice(bytes),
+                Rule::Not(ref rule)  => !rule.matches(s),
+                Rule::All(ref rules) => rules.iter().all(|r| r.matches(s)),
+                Rule::Any(ref rules) => rules.iter().any(|r| r.matches(s)),
+            }
+        }
+
+        /// Checks the string against the rule, keeping the verdict of each
+        /// sub-rule.
+        pub fn check(&self, s: &str) -> Verdict {
+            let details = match *self {
+                Rule::Not(ref rule) => vec![rule.check(s)],
+                Rule::All(ref rules) | Rule::Any(ref rules) => {
+                    rules.iter().map(|r| r.check(s)).collect()
+                },
+                _ => Vec::new(),
+            };
+            Verdict {
+                rule:    self.to_string(),
+                passed:  self.matches(s),
+                negated: matches!(*self, Rule::Not(_)),
+                details,
+            }
         }
     }
-    // Check if the string contains at least three vowels.
-    let mut vowels = 0;
-    for c in s.as_bytes() {
-        vowels += match *c {
-            b'a' | b'e' | b'i' | b'o' | b'u' => 1,
-            _ => 0
-        };
-        if vowels == 3 {
-            return has_pair;
+
+    impl ops::Not for Rule {
+        type Output = Rule;
+
+        fn not(self) -> Rule {
+            Rule::Not(Box::new(self))
         }
     }
-    false
-}
 
-fn is_nice_string_v2(s: &str) -> bool {
-    let mut has_pair = false;
-    // Search a pair of any two letters that appears at least twice.
-    let mut pairs = HashMap::new();
-    for (i, pair) in s.as_bytes().windows(2).enumerate() {
-        match pairs.entry(pair) {
-            // First time we see this pair: insert the position.
-            Entry::Vacant(e)   => { e.insert(i); () }
-            // We know this pair, check for overlap!
-            Entry::Occupied(e) => if i - e.get() > 1 {
-                has_pair = true;
-                break;
+    impl fmt::Display for Rule {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match *self {
+                Rule::MinVowels(count) => {
+                    write!(f, "at least {} vowels", count)
+                },
+                Rule::Forbidden(ref subs) => {
+                    write!(f, "none of {}", subs.join(", "))
+                },
+                Rule::RepeatedWithGap(0) => {
+                    write!(f, "a letter twice in a row")
+                },
+                Rule::RepeatedWithGap(gap) => {
+                    write!(f, "a letter repeated with {} letter(s) in between",
+                           gap)
+                },
+                Rule::PairTwice => {
+                    write!(f, "a pair of letters appearing twice \
+                               without overlap")
+                },
+                Rule::Not(ref rule) => write!(f, "not ({})", rule),
+                Rule::All(_)        => write!(f, "all of"),
+                Rule::Any(_)        => write!(f, "any of"),
+            }
+        }
+    }
+
+    /// Searches a pair of any two letters that appears at least twice.
+    fn has_pair_twice(s: &[u8]) -> bool {
+        let mut pairs = HashMap::new();
+        for (i, pair) in s.windows(2).enumerate() {
+            match pairs.entry(pair) {
+                // First time we see this pair: insert the position.
+                Entry::Vacant(e)   => { e.insert(i); }
+                // We know this pair, check for overlap!
+                Entry::Occupied(e) => if i - e.get() > 1 {
+                    return true;
+                }
+            };
+        }
+        false
+    }
+
+    /// The outcome of a rule for a given string.
+    #[derive(Debug)]
+    pub struct Verdict {
+        pub rule:    String,        // Description of the rule.
+        pub passed:  bool,          // Did the string pass the rule?
+        pub details: Vec<Verdict>,  // Verdicts of the sub-rules.
+        negated:     bool,          // Is the rule a negation?
+    }
+
+    impl Verdict {
+        /// Returns the rules that made the string fail.
+        ///
+        /// Those are the failing simple rules, and the failing negations
+        /// (their sub-rule passed, so there is nothing more precise to say).
+        pub fn failures(&self) -> Vec<&str> {
+            if self.passed {
+                return Vec::new();
+            }
+            if self.details.is_empty() || self.negated {
+                return vec![&self.rule];
+            }
+            self.details.iter().flat_map(Verdict::failures).collect()
+        }
+
+        fn fmt_indented(&self, f: &mut fmt::Formatter, depth: usize)
+            -> fmt::Result {
+            writeln!(f, "{:indent$}{} {}", "",
+                     if self.passed { "pass" } else { "FAIL" }, self.rule,
+                     indent = 2 * depth)?;
+            for verdict in &self.details {
+                verdict.fmt_indented(f, depth + 1)?;
             }
-        };
+            Ok(())
+        }
     }
-    // Search one letter which repeats with exactly one letter between them.
-    for triple in s.as_bytes().windows(3) {
-        if triple[0] == triple[2] {
-            return has_pair;
+
+    impl fmt::Display for Verdict {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            self.fmt_indented(f, 0)
         }
     }
-    false
+}
+
+// }}}
+
+/// Rules of the first version of Santa's text file.
+fn nice_rules() -> Rule {
+    Rule::MinVowels(3)
+        .and(Rule::RepeatedWithGap(0))
+        .and(Rule::forbidden(&["ab", "cd", "pq", "xy"]))
+}
+
+/// Rules of the second version of Santa's text file.
+fn nice_rules_v2() -> Rule {
+    Rule::PairTwice.and(Rule::RepeatedWithGap(1))
+}
+
+fn is_nice_string(s: &str) -> bool {
+    nice_rules().matches(s)
+}
+
+fn is_nice_string_v2(s: &str) -> bool {
+    nice_rules_v2().matches(s)
 }
 
 fn main() {
@@ -75,10 +231,27 @@ fn main() {
 
     file.read_to_string(&mut input).unwrap();
 
+    // Explain the verdicts for a given string.
+    let args = env::args().collect::<Vec<_>>();
+    if args.len() == 3 && args[1] == "--explain" {
+        for rules in &[nice_rules(), nice_rules_v2()] {
+            let verdict = rules.check(&args[2]);
+            print!("{}", verdict);
+            if !verdict.passed {
+                println!("=> naughty because of: {}",
+                         verdict.failures().join("; "));
+            }
+        }
+        return;
+    }
+
     println!("There are {} nice strings.",
              input.lines().filter(|&s| is_nice_string(s)).count());
     println!("There are {} truly nice strings.",
              input.lines().filter(|&s| is_nice_string_v2(s)).count());
+    let either = nice_rules().or(nice_rules_v2());
+    println!("There are {} strings nice by at least one version.",
+             input.lines().filter(|&s| either.matches(s)).count());
 }
 
 // {{{ Tests
@@ -102,4 +275,60 @@ fn examples_part2() {
     assert_eq!(is_nice_string_v2("ieodomkazucvgmuy"), false);
 }
 
+#[test]
+fn combinators() {
+    let rule = Rule::RepeatedWithGap(2).or(!Rule::MinVowels(1));
+    assert!(rule.matches("abca"));
+    assert!(rule.matches("xyz"));
+    assert!(!rule.matches("abc"));
+
+    let rule = !Rule::PairTwice.and(Rule::forbidden(&["x"]));
+    assert!(!rule.matches("abab"));
+    assert!(rule.matches("abxab"));
+    assert!(rule.matches("aba"));
+    assert_eq!(rule.to_string(), "not (all of)");
+}
+
+#[test]
+fn verdicts() {
+    let verdict = nice_rules().check("jchzalrnumimnmhp");
+    assert!(!verdict.passed);
+    assert_eq!(verdict.failures(), vec!["a letter twice in a row"]);
+
+    let verdict = nice_rules().check("haegwjzuvuyypxyu");
+    assert_eq!(verdict.failures(), vec!["none of ab, cd, pq, xy"]);
+    assert_eq!(verdict.to_string(), "\
+FAIL all of
+  pass at least 3 vowels
+  pass a letter twice in a row
+  FAIL none of ab, cd, pq, xy
+");
+
+    let verdict = nice_rules_v2().check("ieodomkazucvgmuy");
+    assert_eq!(verdict.failures(),
+               vec!["a pair of letters appearing twice without overlap"]);
+    assert!(nice_rules_v2().check("xxyxx").failures().is_empty());
+}
+
+#[test]
+fn combinator_failures() {
+    // A failing negation is the culprit, not the sub-rules that passed.
+    let verdict = (!Rule::PairTwice.and(Rule::forbidden(&["x"]))).check("abab");
+    assert!(!verdict.passed);
+    assert_eq!(verdict.failures(), vec!["not (all of)"]);
+
+    let verdict = (!Rule::MinVowels(1)).check("abc");
+    assert_eq!(verdict.failures(), vec!["not (at least 1 vowels)"]);
+
+    // A passing alternative hides the failures of the other ones.
+    let verdict = Rule::RepeatedWithGap(2).or(Rule::MinVowels(1)).check("bcdb");
+    assert!(verdict.passed);
+    assert!(verdict.failures().is_empty());
+
+    // A failing alternative reports every option.
+    let verdict = Rule::RepeatedWithGap(0).or(!Rule::MinVowels(0)).check("ab");
+    assert_eq!(verdict.failures(), vec!["a letter twice in a row",
+                                        "not (at least 0 vowels)"]);
+}
+
 // }}}
@@ -12,8 +12,10 @@
 // }}}
 // {{{ Crates
 
+use std::env;
 use std::fs::File;
-use std::io::Read;
+use std::io::{self, BufWriter, Read};
+use std::path::Path;
 
 #[macro_use]
 extern crate nom;
@@ -23,8 +25,8 @@ extern crate nom;
 
 #[derive(Clone, Copy, Debug)]
 pub struct Point {
-    x : u16,
-    y : u16,
+    x : u32,
+    y : u32,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -33,10 +35,49 @@ pub struct Rect {
     br : Point, // Coordinate of the bottom-right corner.
 }
 
+impl Rect {
+    /// Tests if the rectangle contains no light, i.e. if its corners are
+    /// inverted along one of the axes.
+    fn is_empty(&self) -> bool {
+        self.tl.x > self.br.x || self.tl.y > self.br.y
+    }
+}
+
+// }}}
+// {{{ Lights
+
+/// Operations on a grid of lights.
+pub trait Lights {
+    /// Turns on the lights in the specified rectangle.
+    fn turn_on(&mut self, rect: &Rect);
+
+    /// Turns off the lights in the specified rectangle.
+    fn turn_off(&mut self, rect: &Rect);
+
+    /// Toggle the lights in the specified rectangle.
+    fn toggle(&mut self, rect: &Rect);
+
+    /// Increase the brightness in the specified rectangle.
+    fn inc_brightness(&mut self, rect: &Rect, x: u32);
+
+    /// Decrease the brightness in the specified rectangle.
+    fn dec_brightness(&mut self, rect: &Rect);
+
+    /// Returns the number of lights lit on the grid.
+    fn lights_lit(&self) -> u64;
+
+    /// Returns the total brightness.
+    fn brightness(&self) -> u64;
+}
+
 // }}}
 // {{{ Grid
 
+/// A dense grid, storing every light.
 mod grid {
+    use super::{Lights, Rect};
+    use image::Greymap;
+
     #[derive(Debug)]
     pub struct Grid {
         width  : usize,
@@ -54,56 +95,57 @@ mod grid {
             }
         }
 
-        /// Returns the number of lights lit on the grid.
-        pub fn lights_lit(&self) -> i32 {
-            self.lights.iter().fold(0,
-                |sum, &v| if v != 0 { sum + 1 } else { sum }
-            )
+        /// Renders the grid, one pixel per light, the brightest in white.
+        pub fn to_image(&self) -> Greymap {
+            Greymap::from_levels(self.width, self.height, &self.lights)
         }
 
-        /// Returns the total brightness.
-        pub fn brightness(&self) -> i32 {
-            self.lights.iter().fold(0, |sum, &v| sum + v as i32)
+        fn apply_op_on_rect<F>(&mut self, op: F, area: &Rect)
+            where F: Fn(u32) -> u32 {
+            if area.is_empty() {
+                return;
+            }
+            let i_0 = area.tl.y as usize;
+            let i_n = area.br.y as usize;
+            let j_0 = area.tl.x as usize;
+            let j_n = area.br.x as usize;
+
+            for i in i_0 .. i_n + 1 {
+                for j in j_0 .. j_n + 1 {
+                    let idx = indices_to_index(i, j, self.width);
+                    self.lights[idx] = op(self.lights[idx]);
+                }
+            }
         }
+    }
 
-        /// Turns on the lights in the specified rectangle.
-        pub fn turn_on(&mut self, rect: &super::Rect) {
+    impl Lights for Grid {
+        fn turn_on(&mut self, rect: &Rect) {
             self.apply_op_on_rect(|_| 1, rect);
         }
 
-        /// Turns off the lights in the specified rectangle.
-        pub fn turn_off(&mut self, rect: &super::Rect) {
+        fn turn_off(&mut self, rect: &Rect) {
             self.apply_op_on_rect(|_| 0, rect);
         }
 
-        /// Toggle the lights in the specified rectangle.
-        pub fn toggle(&mut self, rect: &super::Rect) {
+        fn toggle(&mut self, rect: &Rect) {
             self.apply_op_on_rect(|v| if v == 0 { 1 } else { 0 }, rect);
         }
 
-        /// Increase the brightness in the specified rectangle.
-        pub fn inc_brightness(&mut self, rect: &super::Rect, x: u32) {
+        fn inc_brightness(&mut self, rect: &Rect, x: u32) {
             self.apply_op_on_rect(|v| v + x, rect);
         }
 
-        /// Increase the brightness in the specified rectangle.
-        pub fn dec_brightness(&mut self, rect: &super::Rect) {
+        fn dec_brightness(&mut self, rect: &Rect) {
             self.apply_op_on_rect(|v| v.saturating_sub(1), rect);
         }
 
-        fn apply_op_on_rect<F>(&mut self, op: F, area: &super::Rect)
-            where F: Fn(u32) -> u32 {
-            let i_0 = area.tl.x as usize;
-            let i_n = area.br.x as usize;
-            let j_0 = area.tl.y as usize;
-            let j_n = area.br.y as usize;
+        fn lights_lit(&self) -> u64 {
+            self.lights.iter().filter(|&&v| v != 0).count() as u64
+        }
 
-            for i in i_0 .. i_n + 1 {
-                for j in j_0 .. j_n + 1 {
-                    let idx = indices_to_index(i, j, self.width);
-                    self.lights[idx] = op(self.lights[idx]);
-                }
-            }
+        fn brightness(&self) -> u64 {
+            self.lights.iter().map(|&v| u64::from(v)).sum()
         }
     }
     // {{{ Helpers
@@ -118,6 +160,311 @@ mod grid {
     // }}}
 }
 
+// }}}
+// {{{ Sparse grid
+
+/// A sparse grid, using coordinate compression.
+///
+/// The grid is split along the edges of the rectangles it will be updated
+/// with: every light within a cell always shares the same state, so the cost
+/// of an update depends on the number of rectangles instead of the size of the
+/// grid. Unexpected rectangles are supported by splitting the cells they cut.
+mod sparse {
+    use std::ops::Range;
+    use super::{Lights, Rect};
+
+    #[derive(Debug)]
+    pub struct SparseGrid {
+        // Boundaries of the cells: the i-th column spans [xs[i], xs[i+1]).
+        xs     : Vec<u32>,
+        ys     : Vec<u32>,
+        lights : Vec<u32>,
+    }
+
+    impl SparseGrid {
+        /// Creates a new grid, with all the lights turned off, that can be
+        /// updated with the given rectangles.
+        pub fn new(width: u32, height: u32, rects: &[Rect]) -> Self {
+            let xs = boundaries(width,  rects.iter().map(|r| (r.tl.x, r.br.x)));
+            let ys = boundaries(height, rects.iter().map(|r| (r.tl.y, r.br.y)));
+            let size = (xs.len() - 1) * (ys.len() - 1);
+
+            SparseGrid { xs, ys, lights: vec![0; size] }
+        }
+
+        /// Returns the number of lights in each cell, with its value.
+        fn cells<'a>(&'a self) -> impl Iterator<Item=(u64, u32)> + 'a {
+            let columns = self.ys.len() - 1;
+
+            self.lights.iter().enumerate().map(move |(idx, &v)| {
+                let (i, j) = (idx / columns, idx % columns);
+                let width  = u64::from(self.xs[i + 1] - self.xs[i]);
+                let height = u64::from(self.ys[j + 1] - self.ys[j]);
+                (width * height, v)
+            })
+        }
+
+        fn apply_op_on_rect<F>(&mut self, op: F, area: &Rect)
+            where F: Fn(u32) -> u32 {
+            if area.is_empty() {
+                return;
+            }
+            self.cut_x(area.tl.x);
+            self.cut_x(area.br.x + 1);
+            self.cut_y(area.tl.y);
+            self.cut_y(area.br.y + 1);
+
+            let columns = self.ys.len() - 1;
+            let rows    = span(&self.xs, area.tl.x, area.br.x);
+            let cols    = span(&self.ys, area.tl.y, area.br.y);
+
+            for i in rows {
+                let row = &mut self.lights[i * columns .. (i + 1) * columns];
+                for light in &mut row[cols.clone()] {
+                    *light = op(*light);
+                }
+            }
+        }
+
+        /// Adds a boundary at `x`, splitting the row of cells it cuts.
+        fn cut_x(&mut self, x: u32) {
+            let columns = self.ys.len() - 1;
+            if let Some(i) = cut(&mut self.xs, x) {
+                // The new row starts as a copy of the one it was cut from.
+                let row = self.lights[i * columns .. (i + 1) * columns]
+                              .to_vec();
+                let at  = (i + 1) * columns;
+                self.lights.splice(at..at, row);
+            }
+        }
+
+        /// Adds a boundary at `y`, splitting the column of cells it cuts.
+        fn cut_y(&mut self, y: u32) {
+            let columns = self.ys.len() - 1;
+            if let Some(j) = cut(&mut self.ys, y) {
+                // The new column starts as a copy of the one it was cut from.
+                let mut lights = Vec::with_capacity(self.lights.len()
+                                                    + self.xs.len() - 1);
+                for row in self.lights.chunks(columns) {
+                    lights.extend_from_slice(&row[..j + 1]);
+                    lights.extend_from_slice(&row[j..]);
+                }
+                self.lights = lights;
+            }
+        }
+    }
+
+    impl Lights for SparseGrid {
+        fn turn_on(&mut self, rect: &Rect) {
+            self.apply_op_on_rect(|_| 1, rect);
+        }
+
+        fn turn_off(&mut self, rect: &Rect) {
+            self.apply_op_on_rect(|_| 0, rect);
+        }
+
+        fn toggle(&mut self, rect: &Rect) {
+            self.apply_op_on_rect(|v| if v == 0 { 1 } else { 0 }, rect);
+        }
+
+        fn inc_brightness(&mut self, rect: &Rect, x: u32) {
+            self.apply_op_on_rect(|v| v + x, rect);
+        }
+
+        fn dec_brightness(&mut self, rect: &Rect) {
+            self.apply_op_on_rect(|v| v.saturating_sub(1), rect);
+        }
+
+        fn lights_lit(&self) -> u64 {
+            self.cells().filter(|&(_, v)| v != 0).map(|(count, _)| count).sum()
+        }
+
+        fn brightness(&self) -> u64 {
+            self.cells().map(|(count, v)| count * u64::from(v)).sum()
+        }
+    }
+
+    // {{{ Helpers
+
+    /// Returns the sorted boundaries of the cells along an axis.
+    fn boundaries<I>(size: u32, ranges: I) -> Vec<u32>
+        where I: Iterator<Item=(u32, u32)> {
+        let mut bounds = vec![0, size];
+        for (lo, hi) in ranges {
+            assert!(hi < size, "rectangle out of the grid");
+            bounds.push(lo);
+            bounds.push(hi + 1);
+        }
+        bounds.sort();
+        bounds.dedup();
+        bounds
+    }
+
+    /// Inserts the boundary `at`, if missing.
+    ///
+    /// Returns the index of the cell that was split in two.
+    fn cut(bounds: &mut Vec<u32>, at: u32) -> Option<usize> {
+        assert!(at <= *bounds.last().unwrap(), "rectangle out of the grid");
+        match bounds.binary_search(&at) {
+            Ok(_)    => None,
+            Err(idx) => {
+                bounds.insert(idx, at);
+                Some(idx - 1)
+            },
+        }
+    }
+
+    /// Returns the range of cells covering the lights from `lo` to `hi`.
+    ///
+    /// Both `lo` and `hi + 1` must be boundaries.
+    fn span(bounds: &[u32], lo: u32, hi: u32) -> Range<usize> {
+        let start = bounds.binary_search(&lo).expect("missing boundary");
+        let end   = bounds.binary_search(&(hi + 1)).expect("missing boundary");
+        start..end
+    }
+
+    // }}}
+}
+
+// }}}
+// {{{ Image
+
+/// Greyscale images, written as binary PGM or as PNG.
+mod image {
+    use std::io::{self, Write};
+
+    /// Supported image file formats.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum Format {
+        Pgm,
+        Png,
+    }
+
+    impl Format {
+        /// Returns the usual file extension for this format.
+        pub fn extension(self) -> &'static str {
+            match self {
+                Format::Pgm => "pgm",
+                Format::Png => "png",
+            }
+        }
+    }
+
+    /// An 8-bit greyscale image, stored row by row.
+    #[derive(Debug)]
+    pub struct Greymap {
+        width  : usize,
+        height : usize,
+        pixels : Vec<u8>,
+    }
+
+    impl Greymap {
+        /// Creates an image from arbitrary levels, scaled so that the
+        /// highest one is white (an all-zero input gives a black image).
+        pub fn from_levels(width: usize, height: usize, levels: &[u32])
+            -> Self {
+            assert_eq!(levels.len(), width*height);
+            let max = u64::from(levels.iter().cloned().max().unwrap_or(0));
+            let pixels = levels.iter().map(|&v| {
+                (u64::from(v) * 255).checked_div(max).unwrap_or(0) as u8
+            }).collect();
+
+            Greymap { width, height, pixels }
+        }
+
+        /// Writes the image in the specified format.
+        pub fn write<W: Write>(&self, out: &mut W, format: Format)
+            -> io::Result<()> {
+            match format {
+                Format::Pgm => self.write_pgm(out),
+                Format::Png => self.write_png(out),
+            }
+        }
+
+        fn write_pgm<W: Write>(&self, out: &mut W) -> io::Result<()> {
+            write!(out, "P5\n{} {}\n255\n", self.width, self.height)?;
+            out.write_all(&self.pixels)
+        }
+
+        fn write_png<W: Write>(&self, out: &mut W) -> io::Result<()> {
+            let mut header = Vec::with_capacity(13);
+            header.extend_from_slice(&(self.width  as u32).to_be_bytes());
+            header.extend_from_slice(&(self.height as u32).to_be_bytes());
+            // 8-bit greyscale, deflate, no filter, no interlacing.
+            header.extend_from_slice(&[8, 0, 0, 0, 0]);
+
+            // Each scanline is prefixed by its filter type (none).
+            let mut raw = Vec::with_capacity((self.width+1) * self.height);
+            for row in self.pixels.chunks(self.width.max(1)) {
+                raw.push(0);
+                raw.extend_from_slice(row);
+            }
+
+            out.write_all(b"\x89PNG\r\n\x1a\n")?;
+            write_chunk(out, b"IHDR", &header)?;
+            write_chunk(out, b"IDAT", &zlib_stored(&raw))?;
+            write_chunk(out, b"IEND", &[])
+        }
+    }
+    // {{{ Helpers
+
+    /// Writes a PNG chunk: length, type, data and CRC.
+    fn write_chunk<W: Write>(out: &mut W, kind: &[u8; 4], data: &[u8])
+        -> io::Result<()> {
+        out.write_all(&(data.len() as u32).to_be_bytes())?;
+        out.write_all(kind)?;
+        out.write_all(data)?;
+        let crc = crc32(kind.iter().chain(data.iter()).cloned());
+        out.write_all(&crc.to_be_bytes())
+    }
+
+    /// Wraps the data into a zlib stream made of uncompressed blocks.
+    ///
+    /// No compression at all, but this is all PNG readers require.
+    fn zlib_stored(data: &[u8]) -> Vec<u8> {
+        const MAX_BLOCK: usize = 0xFFFF;
+        let mut out = Vec::with_capacity(data.len() + data.len()/MAX_BLOCK*5
+                                         + 11);
+
+        // Deflate, 32K window, no dictionary, fastest level.
+        out.extend_from_slice(&[0x78, 0x01]);
+        let mut blocks = data.chunks(MAX_BLOCK).peekable();
+        if blocks.peek().is_none() {
+            out.extend_from_slice(&[1, 0, 0, 0xFF, 0xFF]);
+        }
+        while let Some(block) = blocks.next() {
+            let len = block.len() as u16;
+            out.push(if blocks.peek().is_none() { 1 } else { 0 });
+            out.extend_from_slice(&len.to_le_bytes());
+            out.extend_from_slice(&(!len).to_le_bytes());
+            out.extend_from_slice(block);
+        }
+        out.extend_from_slice(&adler32(data).to_be_bytes());
+        out
+    }
+
+    /// Computes the CRC-32 (ISO 3309) used by PNG.
+    pub fn crc32<I: Iterator<Item=u8>>(bytes: I) -> u32 {
+        !bytes.fold(!0, |crc, byte| {
+            (0..8).fold(crc ^ u32::from(byte), |crc, _| {
+                if crc & 1 == 1 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 }
+            })
+        })
+    }
+
+    /// Computes the Adler-32 checksum used by zlib.
+    pub fn adler32(bytes: &[u8]) -> u32 {
+        const MOD: u32 = 65521;
+        let (a, b) = bytes.iter().fold((1, 0), |(a, b), &byte| {
+            let a = (a + u32::from(byte)) % MOD;
+            (a, (b + a) % MOD)
+        });
+        (b << 16) | a
+    }
+
+    // }}}
+}
+
 // }}}
 // {{{ Instruction
 
@@ -132,6 +479,17 @@ mod instruction {
         Toggle(super::Rect)
     }
 
+    impl Instruction {
+        /// Returns the area affected by the instruction.
+        pub fn rect(&self) -> super::Rect {
+            match *self {
+                Instruction::TurnOn(rect)  |
+                Instruction::TurnOff(rect) |
+                Instruction::Toggle(rect)  => rect,
+            }
+        }
+    }
+
     impl str::FromStr for Instruction {
         type Err = nom::ErrorKind;
 
@@ -150,8 +508,8 @@ mod instruction {
            tag!(",")                                >>
            y: map_res!(nom::digit, str::from_utf8)  >>
            (super::Point {
-               x: x.parse::<u16>().unwrap(),
-               y: y.parse::<u16>().unwrap()
+               x: x.parse::<u32>().unwrap(),
+               y: y.parse::<u32>().unwrap()
            })
        )
     );
@@ -208,25 +566,45 @@ mod instruction {
 // }}}
 
 use grid::Grid;
+use image::Format;
 use instruction::Instruction;
+use sparse::SparseGrid;
 
-pub fn execute(grid: &mut Grid, instructions: &[Instruction]) {
-    for instruction in instructions.iter() {
+pub fn execute<G: Lights>(grid: &mut G, instructions: &[Instruction]) {
+    execute_with(grid, instructions, |_, _| ());
+}
+
+/// Like `execute`, but calls `on_step` with the step number and the grid
+/// after each instruction.
+pub fn execute_with<G, F>(grid: &mut G, instructions: &[Instruction],
+                          mut on_step: F)
+    where G: Lights, F: FnMut(usize, &G) {
+    for (step, instruction) in instructions.iter().enumerate() {
         match *instruction {
             Instruction::TurnOn(area)  => grid.turn_on(&area),
             Instruction::TurnOff(area) => grid.turn_off(&area),
             Instruction::Toggle(area)  => grid.toggle(&area),
         }
+        on_step(step + 1, grid);
     }
 }
 
-pub fn execute_v2(grid: &mut Grid, instructions: &[Instruction]) {
-    for instruction in instructions.iter() {
+pub fn execute_v2<G: Lights>(grid: &mut G, instructions: &[Instruction]) {
+    execute_v2_with(grid, instructions, |_, _| ());
+}
+
+/// Like `execute_v2`, but calls `on_step` with the step number and the grid
+/// after each instruction.
+pub fn execute_v2_with<G, F>(grid: &mut G, instructions: &[Instruction],
+                             mut on_step: F)
+    where G: Lights, F: FnMut(usize, &G) {
+    for (step, instruction) in instructions.iter().enumerate() {
         match *instruction {
             Instruction::TurnOn(area)  => grid.inc_brightness(&area, 1),
             Instruction::TurnOff(area) => grid.dec_brightness(&area),
             Instruction::Toggle(area)  => grid.inc_brightness(&area, 2),
         }
+        on_step(step + 1, grid);
     }
 }
 
@@ -238,17 +616,66 @@ fn main() {
     let instructions = input.lines().map(|l| l.parse::<Instruction>().unwrap())
                                     .collect::<Vec<_>>();
 
-    let mut grid = Grid::new(1000, 1000);
-    execute(&mut grid, &instructions);
+    // The dense engine is the reference, the sparse one scales to huge grids.
+    if env::args().any(|arg| arg == "--sparse") {
+        let rects = instructions.iter().map(Instruction::rect)
+                                       .collect::<Vec<_>>();
+        run(|| SparseGrid::new(1000, 1000, &rects), &instructions);
+    } else {
+        run(|| Grid::new(1000, 1000), &instructions);
+    }
+
+    // `--render DIR` dumps the final grids (and each step with `--frames`).
+    let args = env::args().collect::<Vec<_>>();
+    if let Some(pos) = args.iter().position(|arg| arg == "--render") {
+        let dir    = Path::new(args.get(pos + 1).expect("missing directory"));
+        let format = if args.iter().any(|arg| arg == "--pgm") { Format::Pgm }
+                     else { Format::Png };
+        let frames = args.iter().any(|arg| arg == "--frames");
+        render(dir, format, frames, &instructions).unwrap();
+    }
+}
+
+fn run<G, F>(new_grid: F, instructions: &[Instruction])
+    where G: Lights, F: Fn() -> G {
+    let mut grid = new_grid();
+    execute(&mut grid, instructions);
     println!("After following the instructions, there are {} lights lit.",
              grid.lights_lit());
 
-    let mut grid = Grid::new(1000, 1000);
-    execute_v2(&mut grid, &instructions);
+    let mut grid = new_grid();
+    execute_v2(&mut grid, instructions);
     println!("After following the instructions, the total brightness is {}.",
              grid.brightness());
 }
 
+/// Renders both parts with the dense grid into `dir`.
+fn render(dir: &Path, format: Format, frames: bool,
+          instructions: &[Instruction]) -> io::Result<()> {
+    let save = |name: String, grid: &Grid| -> io::Result<()> {
+        let path = dir.join(format!("{}.{}", name, format.extension()));
+        let mut out = BufWriter::new(File::create(path)?);
+        grid.to_image().write(&mut out, format)
+    };
+    let mut result = Ok(());
+    let mut snapshot = |part: &str, step: usize, grid: &Grid| {
+        if frames && result.is_ok() {
+            result = save(format!("{}-{:03}", part, step), grid);
+        }
+    };
+
+    let mut grid = Grid::new(1000, 1000);
+    execute_with(&mut grid, instructions, |step, g| snapshot("part1", step, g));
+    save("part1".to_owned(), &grid)?;
+
+    let mut grid = Grid::new(1000, 1000);
+    execute_v2_with(&mut grid, instructions,
+                    |step, g| snapshot("part2", step, g));
+    save("part2".to_owned(), &grid)?;
+
+    result
+}
+
 // {{{ Tests
 
 #[test]
@@ -285,4 +712,165 @@ fn examples_part2() {
     assert_eq!(grid.brightness(), 2_000_001);
 }
 
+
+/// Generates pseudo-random instructions on a `size`×`size` grid.
+#[cfg(test)]
+fn random_instructions(size: u32, count: usize, mut seed: u64)
+    -> Vec<Instruction> {
+    let mut next = |bound: u32| {
+        // Numerical Recipes' LCG, good enough for tests.
+        seed = seed.wrapping_mul(6_364_136_223_846_793_005)
+                   .wrapping_add(1_442_695_040_888_963_407);
+        ((seed >> 33) % u64::from(bound)) as u32
+    };
+
+    (0..count).map(|_| {
+        let (x0, x1) = (next(size), next(size));
+        let (y0, y1) = (next(size), next(size));
+        let rect = Rect {
+            tl: Point { x: x0.min(x1), y: y0.min(y1) },
+            br: Point { x: x0.max(x1), y: y0.max(y1) },
+        };
+        match next(3) {
+            0 => Instruction::TurnOn(rect),
+            1 => Instruction::TurnOff(rect),
+            _ => Instruction::Toggle(rect),
+        }
+    }).collect()
+}
+
+#[test]
+fn sparse_vs_dense() {
+    for seed in 0..20 {
+        let instructions = random_instructions(100, 50, seed);
+        let rects = instructions.iter().map(Instruction::rect)
+                                       .collect::<Vec<_>>();
+
+        let mut dense  = Grid::new(100, 100);
+        let mut sparse = SparseGrid::new(100, 100, &rects);
+        execute(&mut dense, &instructions);
+        execute(&mut sparse, &instructions);
+        assert_eq!(sparse.lights_lit(), dense.lights_lit());
+
+        let mut dense  = Grid::new(100, 100);
+        let mut sparse = SparseGrid::new(100, 100, &rects);
+        execute_v2(&mut dense, &instructions);
+        execute_v2(&mut sparse, &instructions);
+        assert_eq!(sparse.brightness(), dense.brightness());
+
+        // Rectangles unknown to the sparse grid split its cells.
+        let mut dense  = Grid::new(100, 100);
+        let mut sparse = SparseGrid::new(100, 100, &rects[..10]);
+        execute_v2(&mut dense, &instructions);
+        execute_v2(&mut sparse, &instructions);
+        assert_eq!(sparse.brightness(), dense.brightness());
+    }
+}
+
+#[test]
+fn inverted_rects() {
+    let rects = [
+        Rect { tl: Point { x: 2, y: 5 }, br: Point { x: 6, y: 3 } },
+        Rect { tl: Point { x: 6, y: 3 }, br: Point { x: 2, y: 5 } },
+        Rect { tl: Point { x: 6, y: 5 }, br: Point { x: 2, y: 3 } },
+    ];
+    let instructions = rects.iter().map(|&rect| Instruction::TurnOn(rect))
+                                   .collect::<Vec<_>>();
+
+    // Inverted rectangles are empty, whether the grid expects them or not.
+    let mut dense    = Grid::new(10, 10);
+    let mut sparse   = SparseGrid::new(10, 10, &rects);
+    let mut surprise = SparseGrid::new(10, 10, &[]);
+    execute(&mut dense, &instructions);
+    execute(&mut sparse, &instructions);
+    execute(&mut surprise, &instructions);
+    assert_eq!(dense.lights_lit(), 0);
+    assert_eq!(sparse.lights_lit(), 0);
+    assert_eq!(surprise.lights_lit(), 0);
+}
+
+#[test]
+fn sparse_huge_grid() {
+    let size  = 1_000_000;
+    let whole = Rect {
+        tl: Point { x: 0,        y: 0        },
+        br: Point { x: size - 1, y: size - 1 },
+    };
+    let mut instructions = random_instructions(size, 200, 42);
+    instructions.push(Instruction::TurnOn(whole));
+    instructions.push(Instruction::Toggle(Rect {
+        tl: Point { x: 0, y: 0 },
+        br: Point { x: size - 1, y: 0 },
+    }));
+    let rects = instructions.iter().map(Instruction::rect)
+                                   .collect::<Vec<_>>();
+
+    let mut grid = SparseGrid::new(size, size, &rects);
+    execute(&mut grid, &instructions);
+    assert_eq!(grid.lights_lit(), 999_999_000_000);
+
+    let mut grid = SparseGrid::new(size, size, &rects);
+    execute_v2(&mut grid, &instructions[instructions.len() - 2..]);
+    assert_eq!(grid.brightness(), 1_000_002_000_000);
+}
+
+#[test]
+fn render_pgm() {
+    let area = Rect { tl: Point { x: 1, y: 0 }, br: Point { x: 2, y: 1 } };
+    let instructions = [
+        Instruction::TurnOn(Rect { tl: Point { x: 0, y: 0 },
+                                   br: Point { x: 0, y: 0 } }),
+        Instruction::Toggle(area),
+    ];
+    let mut steps = Vec::new();
+    let mut grid  = Grid::new(4, 2);
+    execute_v2_with(&mut grid, &instructions, |step, g| {
+        steps.push((step, g.brightness()));
+    });
+    assert_eq!(steps, vec![(1, 1), (2, 9)]);
+
+    // Toggling is twice as bright as turning on in the second part...
+    let mut pgm = Vec::new();
+    grid.to_image().write(&mut pgm, Format::Pgm).unwrap();
+    assert_eq!(pgm, b"P5\n4 2\n255\n\x7f\xff\xff\x00\x00\xff\xff\x00");
+
+    // ... but the same in the first one.
+    let mut grid = Grid::new(4, 2);
+    execute(&mut grid, &instructions);
+    let mut pgm = Vec::new();
+    grid.to_image().write(&mut pgm, Format::Pgm).unwrap();
+    assert_eq!(pgm, b"P5\n4 2\n255\n\xff\xff\xff\x00\x00\xff\xff\x00");
+
+    // An all-dark grid stays black.
+    let mut pgm = Vec::new();
+    Grid::new(2, 1).to_image().write(&mut pgm, Format::Pgm).unwrap();
+    assert_eq!(pgm, b"P5\n2 1\n255\n\x00\x00");
+}
+
+#[test]
+fn render_png() {
+    assert_eq!(image::crc32(b"IEND".iter().cloned()), 0xAE42_6082);
+    assert_eq!(image::adler32(b"Wikipedia"), 0x11E6_0398);
+
+    let mut grid = Grid::new(3, 2);
+    grid.turn_on(&Rect { tl: Point { x: 1, y: 1 }, br: Point { x: 2, y: 1 } });
+    let mut png = Vec::new();
+    grid.to_image().write(&mut png, Format::Png).unwrap();
+
+    assert_eq!(&png[..8], b"\x89PNG\r\n\x1a\n");
+    assert_eq!(&png[8..33], &[
+        0, 0, 0, 13, b'I', b'H', b'D', b'R',
+        0, 0, 0, 3, 0, 0, 0, 2, 8, 0, 0, 0, 0,
+        0xB8, 0x1F, 0x39, 0xC6,
+    ][..]);
+    // Scanlines are stored as-is, each behind a "no filter" byte.
+    assert_eq!(&png[33..41], b"\0\0\0\x13IDAT");
+    assert_eq!(&png[41..60], &[
+        0x78, 0x01,
+        1, 8, 0, 0xF7, 0xFF,
+        0, 0, 0, 0, 0, 0, 255, 255,
+        0x03, 0x05, 0x01, 0xFF,
+    ][..]);
+    assert_eq!(&png[png.len()-12..], b"\0\0\0\0IEND\xaeB`\x82");
+}
 // }}}
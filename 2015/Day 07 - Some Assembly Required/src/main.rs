@@ -12,6 +12,7 @@
 // }}}
 // {{{ Crates
 
+use std::env;
 use std::fs::File;
 use std::io::Read;
 
@@ -153,8 +154,9 @@ mod instruction {
 
 /// A module to build and emulate a circuit.
 mod circuit {
-    use std::collections::HashMap;
-    use std::collections::VecDeque;
+    use std::collections::{HashMap, HashSet, VecDeque};
+    use std::error;
+    use std::fmt::{self, Write};
     use instruction::{Instruction, Input};
 
     // {{{ Operand
@@ -194,6 +196,34 @@ mod circuit {
         RShift { left:  Operand, right: u8,     },
     }
 
+    impl Operation {
+        /// Returns the name of the gate (with its offset, for shifts).
+        pub fn kind(&self) -> String {
+            match *self {
+                Operation::Assign { .. }        => "ASSIGN".to_owned(),
+                Operation::Not    { .. }        => "NOT".to_owned(),
+                Operation::And    { .. }        => "AND".to_owned(),
+                Operation::Or     { .. }        => "OR".to_owned(),
+                Operation::LShift { right, .. } => format!("LSHIFT {}", right),
+                Operation::RShift { right, .. } => format!("RSHIFT {}", right),
+            }
+        }
+
+        /// Returns the operands of the gate.
+        pub fn operands(&self) -> Vec<Operand> {
+            match *self {
+                Operation::Assign { ref wire } =>
+                    vec![Operand::Wire(wire.clone())],
+                Operation::Not    { ref op }              => vec![op.clone()],
+                Operation::And    { ref left, ref right } |
+                Operation::Or     { ref left, ref right } =>
+                    vec![left.clone(), right.clone()],
+                Operation::LShift { ref left, .. } |
+                Operation::RShift { ref left, .. }        => vec![left.clone()],
+            }
+        }
+    }
+
     // }}}
     // {{{ Signal
 
@@ -250,6 +280,45 @@ mod circuit {
         }
     }
 
+    // }}}
+    // {{{ Error
+
+    /// An error detected while building or emulating a circuit.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub enum Error {
+        /// The wire is driven by more than one source.
+        DuplicateWire(String),
+        /// The wire is used (by `used_by`) but never defined.
+        UndefinedWire { wire: String, used_by: String },
+        /// The wire doesn't exist in the circuit.
+        UnknownWire(String),
+        /// The wire signal isn't overridden.
+        NotOverridden(String),
+        /// The wires form a loop, listed in the direction of the signal.
+        Cycle(Vec<String>),
+    }
+
+    impl fmt::Display for Error {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match *self {
+                Error::DuplicateWire(ref wire) =>
+                    write!(f, "wire {} already exists", wire),
+                Error::UndefinedWire { ref wire, ref used_by } =>
+                    write!(f, "wire {} (used by {}) is undefined",
+                           wire, used_by),
+                Error::UnknownWire(ref wire) =>
+                    write!(f, "wire {} doesn't exist", wire),
+                Error::NotOverridden(ref wire) =>
+                    write!(f, "wire {} isn't overridden", wire),
+                Error::Cycle(ref wires) =>
+                    write!(f, "cycle detected: {} -> {}",
+                           wires.join(" -> "), wires[0]),
+            }
+        }
+    }
+
+    impl error::Error for Error {}
+
     // }}}
     // {{{ Circuit
 
@@ -261,110 +330,252 @@ mod circuit {
     pub struct Circuit {
         wires: HashMap<String, Signal>,
         // Graph represented by an adjacency list.
-        graph: HashMap<String, Vec<String>>
+        graph: HashMap<String, Vec<String>>,
+        // Wires whose signal is forced, regardless of their definition.
+        overrides: HashMap<String, u16>,
+        // Wires sorted in topological order (empty until emulated).
+        order: Vec<String>,
+        // Computed signals.
+        values: HashMap<String, u16>,
     }
 
     impl Circuit {
         /// Initialize a circuit.
         pub fn new() -> Self {
             Circuit {
-                wires: HashMap::new(),
-                graph: HashMap::new()
+                wires:     HashMap::new(),
+                graph:     HashMap::new(),
+                overrides: HashMap::new(),
+                order:     Vec::new(),
+                values:    HashMap::new(),
             }
         }
 
         /// Build a circuit from a description.
-        pub fn build(&mut self, description : &str) {
+        ///
+        /// Discard the previous circuit, including the overridden signals.
+        pub fn build(&mut self, description : &str) -> Result<(), Error> {
             let instructions = description.lines()
                                           .map(Instruction::from)
                                           .collect::<Vec<_>>();
             self.wires.clear();
             self.graph.clear();
+            self.overrides.clear();
+            self.order.clear();
+            self.values.clear();
             for instruction in instructions {
                 match instruction {
                     Instruction::Assign { value, out } |
                     Instruction::Not    { value, out } => {
-                        self.add_wire(out, Signal::from(instruction));
+                        self.add_wire(out, Signal::from(instruction))?;
                         self.update_relations(value, out);
                     },
                     Instruction::And { left, right, out } |
                     Instruction::Or  { left, right, out } => {
-                        self.add_wire(out, Signal::from(instruction));
+                        self.add_wire(out, Signal::from(instruction))?;
                         self.update_relations(right, out);
                         self.update_relations(left, out);
                     },
                     Instruction::LShift { left, out, .. } |
                     Instruction::RShift { left, out, .. } => {
-                        self.add_wire(out, Signal::from(instruction));
+                        self.add_wire(out, Signal::from(instruction))?;
                         self.update_relations(left, out);
                     },
                 }
             }
+            Ok(())
         }
 
         /// Emulate the circuit until all the values are propagated.
-        pub fn emulate(&mut self) {
-            let empty = vec![];
-
-            // Initialize the queue with the wires which have a known value.
-            let mut queue = self.wires.iter().filter_map(|(wire, value)|
-                match *value {
-                    Signal::Value(_) => Some(wire.to_owned()),
-                    Signal::Op(_)    => None,
-                }
-            ).collect::<VecDeque<String>>();
-
-            // Propagate the values.
-            while !queue.is_empty() {
-                let src = queue.pop_front().unwrap();
-
-                for tgt in self.graph.get(&src).unwrap_or(&empty) {
-                    let signal = self.wires.remove(tgt).unwrap();
-                    let mut new_signal = signal.clone();
-
-                    // The wire's signal is unknown: try to compute it!
-                    if let Signal::Op(op) = signal {
-                        if let Some(value) = self.evaluate_operation(op) {
-                            // We have a value: we can update the wire's signal
-                            // and process its successor.
-                            new_signal = Signal::Value(value);
-                            queue.push_back(tgt.to_owned());
-                        }
-                    }
-
-                    // XXX Can't call `add_wire` because of the borrow of `for`.
-                    self.wires.insert(tgt.to_owned(), new_signal);
-                }
+        ///
+        /// Fails if a wire is used without being defined, or if the circuit
+        /// contains a loop.
+        pub fn emulate(&mut self) -> Result<(), Error> {
+            self.check_definitions()?;
+            self.order = self.sort_wires()?;
+            self.values.clear();
+
+            for wire in self.order.clone() {
+                let value = self.evaluate_wire(&wire);
+                self.values.insert(wire, value);
             }
+            Ok(())
         }
 
         /// Returns the signal on the specified wire.
         ///
+        /// Before the emulation, only overridden wires and wires assigned a
+        /// literal value have a known signal.
+        ///
         /// Returns None if the wire doesn't exists or if the wire signal wasn't
         /// computed.
         pub fn get_signal(&self, wire: &str) -> Option<u16> {
-            self.wires.get(wire).and_then(|signal|
-                match *signal {
-                    Signal::Value(v) => Some(v),
-                    _                => None
+            if let Some(&value) = self.values.get(wire) {
+                return Some(value);
+            }
+            if let Some(&value) = self.overrides.get(wire) {
+                return Some(value);
+            }
+            match self.wires.get(wire) {
+                Some(&Signal::Value(value)) => Some(value),
+                _                           => None,
+            }
+        }
+
+        /// Set the signal on the specified wire, overriding its definition.
+        ///
+        /// If the circuit was already emulated, only the wires that depend on
+        /// the overridden one are re-evaluated.
+        pub fn set_signal(&mut self, wire: &str, value: u16)
+            -> Result<(), Error>
+        {
+            if !self.wires.contains_key(wire) {
+                return Err(Error::UnknownWire(wire.to_owned()));
+            }
+            // Ignoring the inputs of a wire can't break the current order.
+            self.overrides.insert(wire.to_owned(), value);
+            self.propagate(wire);
+            Ok(())
+        }
+
+        /// Restore the definition of a wire whose signal was overridden.
+        ///
+        /// If the circuit was already emulated, only the wires that depend on
+        /// the restored one are re-evaluated.
+        ///
+        /// Fails, leaving the override in place, if restoring the wire
+        /// closes a loop.
+        pub fn clear_signal(&mut self, wire: &str) -> Result<(), Error> {
+            if !self.wires.contains_key(wire) {
+                return Err(Error::UnknownWire(wire.to_owned()));
+            }
+            let value = self.overrides.remove(wire).ok_or_else(|| {
+                Error::NotOverridden(wire.to_owned())
+            })?;
+            // The inputs of the wire are back, the order must be recomputed.
+            if !self.order.is_empty() {
+                match self.sort_wires() {
+                    Ok(order) => self.order = order,
+                    Err(err)  => {
+                        self.overrides.insert(wire.to_owned(), value);
+                        return Err(err);
+                    },
                 }
-            )
+            }
+            self.propagate(wire);
+            Ok(())
         }
 
-        /// Set the signal on the specified wire.
-        pub fn set_signal(&mut self, wire: &str, value: u16) {
-            if let Some(signal) = self.wires.get_mut(wire) {
-                *signal = Signal::Value(value)
+        // {{{ Export
+
+        /// Render the circuit as a Graphviz DOT graph.
+        ///
+        /// Wires are nodes labelled by their gate, edges go from the operands
+        /// to the gates. Literal operands are rendered as plain text nodes.
+        pub fn to_dot(&self) -> String {
+            let mut dot = String::from("digraph circuit {\n");
+
+            for wire in self.sorted_wires() {
+                let label = match self.wires[wire] {
+                    _ if self.overrides.contains_key(wire) =>
+                        format!("{} (override)", self.overrides[wire]),
+                    Signal::Value(value) => value.to_string(),
+                    Signal::Op(ref op)   => op.kind(),
+                };
+                writeln!(dot, "    \"{}\" [label=\"{}\\n{}\"];",
+                         wire, wire, label).unwrap();
+
+                let operands = match self.wires[wire] {
+                    Signal::Op(ref op) if !self.overrides.contains_key(wire) =>
+                        op.operands(),
+                    _ => vec![],
+                };
+                for (i, operand) in operands.into_iter().enumerate() {
+                    match operand {
+                        Operand::Wire(src) =>
+                            writeln!(dot, "    \"{}\" -> \"{}\";",
+                                     src, wire).unwrap(),
+                        Operand::Literal(value) => {
+                            writeln!(dot, "    \"{}#{}\" [label=\"{}\", \
+                                           shape=plaintext];",
+                                     wire, i, value).unwrap();
+                            writeln!(dot, "    \"{}#{}\" -> \"{}\";",
+                                     wire, i, wire).unwrap();
+                        },
+                    }
+                }
             }
+
+            dot.push_str("}\n");
+            dot
+        }
+
+        /// Render the circuit as a synthesizable Verilog module.
+        ///
+        /// Every wire is exposed as a 16-bit output, prefixed by `w_` to avoid
+        /// clashes with Verilog keywords (`if`, `do`, `or`, …).
+        pub fn to_verilog(&self, module: &str) -> String {
+            let wires = self.sorted_wires();
+            let ports = wires.iter()
+                             .map(|wire| format!("    output wire [15:0] w_{}",
+                                                 wire))
+                             .collect::<Vec<_>>();
+            let mut verilog = format!("module {}(\n{}\n);\n",
+                                      module, ports.join(",\n"));
+
+            for wire in wires {
+                let expr = match self.wires[wire] {
+                    _ if self.overrides.contains_key(wire) =>
+                        verilog_operand(&Operand::Literal(self.overrides[wire])),
+                    Signal::Value(value) =>
+                        verilog_operand(&Operand::Literal(value)),
+                    Signal::Op(ref op) => {
+                        let operands = op.operands();
+                        let args = operands.iter()
+                                           .map(verilog_operand)
+                                           .collect::<Vec<_>>();
+                        match *op {
+                            Operation::Assign { .. } => args[0].clone(),
+                            Operation::Not    { .. } =>
+                                format!("~{}", args[0]),
+                            Operation::And    { .. } =>
+                                format!("{} & {}", args[0], args[1]),
+                            Operation::Or     { .. } =>
+                                format!("{} | {}", args[0], args[1]),
+                            Operation::LShift { right, .. } =>
+                                format!("{} << {}", args[0], right),
+                            Operation::RShift { right, .. } =>
+                                format!("{} >> {}", args[0], right),
+                        }
+                    },
+                };
+                writeln!(verilog, "    assign w_{} = {};", wire, expr).unwrap();
+            }
+
+            verilog.push_str("endmodule\n");
+            verilog
         }
 
+        // }}}
         // {{{ Internals
 
+        // Returns the wires' names, in alphabetical order.
+        fn sorted_wires(&self) -> Vec<&str> {
+            let mut wires = self.wires.keys()
+                                      .map(String::as_str)
+                                      .collect::<Vec<_>>();
+            wires.sort();
+            wires
+        }
+
         // Add a wire into the circuit.
-        fn add_wire(&mut self, wire: &str, signal: Signal) {
+        fn add_wire(&mut self, wire: &str, signal: Signal)
+            -> Result<(), Error>
+        {
             if self.wires.insert(wire.to_owned(), signal).is_some() {
-                panic!("wire {} already exists", wire);
+                return Err(Error::DuplicateWire(wire.to_owned()));
             }
+            Ok(())
         }
 
         // Update the adjacency list that represent the relation between the wires.
@@ -376,6 +587,154 @@ mod circuit {
             }
         }
 
+        // Returns the wires the given wire reads its signal from.
+        fn dependencies(&self, wire: &str) -> Vec<&str> {
+            if self.overrides.contains_key(wire) {
+                return vec![];
+            }
+            let operands = match self.wires[wire] {
+                Signal::Op(ref op) => match *op {
+                    Operation::Assign { ref wire } =>
+                        return vec![wire.as_str()],
+                    Operation::Not    { ref op }            => vec![op],
+                    Operation::And    { ref left, ref right } |
+                    Operation::Or     { ref left, ref right } =>
+                        vec![left, right],
+                    Operation::LShift { ref left, .. } |
+                    Operation::RShift { ref left, .. }      => vec![left],
+                },
+                Signal::Value(_) => vec![],
+            };
+            operands.into_iter().filter_map(|operand| match *operand {
+                Operand::Wire(ref wire) => Some(wire.as_str()),
+                Operand::Literal(_)     => None,
+            }).collect()
+        }
+
+        // Check that every wire used as input is defined.
+        fn check_definitions(&self) -> Result<(), Error> {
+            let mut wires = self.wires.keys().collect::<Vec<_>>();
+            wires.sort();
+
+            for wire in wires {
+                for dependency in self.dependencies(wire) {
+                    if !self.wires.contains_key(dependency) {
+                        return Err(Error::UndefinedWire {
+                            wire:    dependency.to_owned(),
+                            used_by: wire.to_owned(),
+                        });
+                    }
+                }
+            }
+            Ok(())
+        }
+
+        // Sort the wires in topological order (Kahn's algorithm).
+        fn sort_wires(&self) -> Result<Vec<String>, Error> {
+            let empty = vec![];
+            let mut degrees = self.wires.keys().map(|wire|
+                (wire.as_str(), self.dependencies(wire).len())
+            ).collect::<HashMap<_, _>>();
+            let mut queue = degrees.iter()
+                                   .filter(|&(_, &degree)| degree == 0)
+                                   .map(|(&wire, _)| wire)
+                                   .collect::<VecDeque<_>>();
+            let mut order = Vec::with_capacity(self.wires.len());
+
+            while let Some(src) = queue.pop_front() {
+                order.push(src.to_owned());
+                for tgt in self.graph.get(src).unwrap_or(&empty) {
+                    // Overridden wires don't read their inputs.
+                    if self.overrides.contains_key(tgt) {
+                        continue;
+                    }
+                    let degree = degrees.get_mut(tgt.as_str())
+                                        .expect("defined wire");
+                    *degree -= 1;
+                    if *degree == 0 {
+                        queue.push_back(tgt);
+                    }
+                }
+            }
+
+            if order.len() == self.wires.len() {
+                Ok(order)
+            } else {
+                Err(Error::Cycle(self.find_cycle(&degrees)))
+            }
+        }
+
+        // Extract a loop from the wires that couldn't be sorted.
+        fn find_cycle(&self, degrees: &HashMap<&str, usize>) -> Vec<String> {
+            // Every unsorted wire depends on at least one other unsorted wire:
+            // walking up the dependencies eventually comes back on its steps.
+            let start = degrees.iter()
+                               .filter(|&(_, &degree)| degree != 0)
+                               .map(|(&wire, _)| wire)
+                               .min()
+                               .expect("unsorted wire");
+            let mut path = vec![start];
+            loop {
+                let last = path[path.len() - 1];
+                let next = self.dependencies(last).into_iter()
+                    .filter(|wire| degrees[wire] != 0)
+                    .min()
+                    .expect("unsorted dependency");
+                if let Some(idx) = path.iter().position(|&wire| wire == next) {
+                    // Dependencies go against the signal: reverse the loop.
+                    let mut cycle = path.split_off(idx);
+                    cycle.reverse();
+                    let first = (0..cycle.len()).min_by_key(|&i| cycle[i])
+                                                .expect("non-empty cycle");
+                    cycle.rotate_left(first);
+                    return cycle.into_iter().map(str::to_owned).collect();
+                }
+                path.push(next);
+            }
+        }
+
+        // Re-evaluate the wires that depend on the given one (included).
+        fn propagate(&mut self, wire: &str) {
+            // Not emulated yet: nothing to update.
+            if self.order.is_empty() {
+                return;
+            }
+
+            let empty = vec![];
+            let mut cone = HashSet::new();
+            let mut stack = vec![wire];
+            while let Some(src) = stack.pop() {
+                if cone.insert(src) {
+                    stack.extend(self.graph.get(src)
+                                           .unwrap_or(&empty)
+                                           .iter()
+                                           .map(String::as_str));
+                }
+            }
+
+            let wires = self.order.iter()
+                                  .filter(|wire| cone.contains(wire.as_str()))
+                                  .cloned()
+                                  .collect::<Vec<_>>();
+            for wire in wires {
+                let value = self.evaluate_wire(&wire);
+                self.values.insert(wire, value);
+            }
+        }
+
+        // Compute the signal of a wire, whose inputs are already known.
+        fn evaluate_wire(&self, wire: &str) -> u16 {
+            if let Some(&value) = self.overrides.get(wire) {
+                return value;
+            }
+            match self.wires[wire] {
+                Signal::Value(value) => value,
+                Signal::Op(ref op)   =>
+                    self.evaluate_operation(op.clone())
+                        .expect("inputs are evaluated first"),
+            }
+        }
+
         fn get_operand_value(&self, operand: Operand) -> Option<u16> {
             match operand {
                 Operand::Wire(wire)     => self.get_signal(&wire),
@@ -412,6 +771,14 @@ mod circuit {
         // }}}
     }
 
+    // Render an operand as a Verilog expression.
+    fn verilog_operand(operand: &Operand) -> String {
+        match *operand {
+            Operand::Wire(ref wire) => format!("w_{}", wire),
+            Operand::Literal(value) => format!("16'd{}", value),
+        }
+    }
+
     // }}}
 }
 
@@ -425,19 +792,35 @@ fn main() {
     let mut description = String::new();
 
     file.read_to_string(&mut description).unwrap();
-    circuit.build(&description);
-    circuit.emulate();
+    circuit.build(&description).unwrap();
+
+    // Export the circuit instead of emulating it.
+    match env::args().nth(1).as_deref() {
+        Some("--dot") => {
+            print!("{}", circuit.to_dot());
+            return;
+        },
+        Some("--verilog") => {
+            print!("{}", circuit.to_verilog("circuit"));
+            return;
+        },
+        _ => (),
+    }
+
+    circuit.emulate().unwrap();
 
     let signal_a = circuit.get_signal("a").unwrap();
     println!("After running the circuit, the signal on wire `a` is {}.",
              signal_a);
 
-    circuit.build(&description);
     println!("Overriding `b` with {}", signal_a);
-    circuit.set_signal("b", signal_a);
-    circuit.emulate();
+    circuit.set_signal("b", signal_a).unwrap();
     println!("After re-running the circuit, the signal on wire `a` is {}.",
              circuit.get_signal("a").unwrap());
+
+    circuit.clear_signal("b").unwrap();
+    println!("Once `b` is restored, the signal on wire `a` is back to {}.",
+             circuit.get_signal("a").unwrap());
 }
 
 // {{{ Tests
@@ -455,8 +838,8 @@ NOT x -> h
 NOT y -> i";
     let mut circuit = Circuit::new();
 
-    circuit.build(description);
-    circuit.emulate();
+    circuit.build(description).unwrap();
+    circuit.emulate().unwrap();
 
     assert_eq!(circuit.get_signal("d"), Some(72));
     assert_eq!(circuit.get_signal("e"), Some(507));
@@ -468,4 +851,134 @@ NOT y -> i";
     assert_eq!(circuit.get_signal("y"), Some(456));
 }
 
+
+#[test]
+fn override_signal() {
+    let description = "\
+123 -> x
+x AND y -> d
+456 -> y
+NOT y -> i
+d OR i -> e";
+    let mut circuit = Circuit::new();
+
+    circuit.build(description).unwrap();
+    circuit.emulate().unwrap();
+    assert_eq!(circuit.get_signal("e"), Some(65151));
+
+    circuit.set_signal("y", 0).unwrap();
+    assert_eq!(circuit.get_signal("d"), Some(0));
+    assert_eq!(circuit.get_signal("i"), Some(65535));
+    assert_eq!(circuit.get_signal("x"), Some(123));
+
+    circuit.set_signal("i", 1).unwrap();
+    assert_eq!(circuit.get_signal("e"), Some(1));
+
+    circuit.clear_signal("y").unwrap();
+    circuit.clear_signal("i").unwrap();
+    assert_eq!(circuit.get_signal("e"), Some(65151));
+
+    assert_eq!(circuit.set_signal("z", 1),
+               Err(circuit::Error::UnknownWire("z".to_owned())));
+    assert_eq!(circuit.clear_signal("z"),
+               Err(circuit::Error::UnknownWire("z".to_owned())));
+    assert_eq!(circuit.clear_signal("x"),
+               Err(circuit::Error::NotOverridden("x".to_owned())));
+}
+
+#[test]
+fn signals_before_emulation() {
+    let description = "\
+123 -> x
+456 -> y
+x AND y -> d";
+    let mut circuit = Circuit::new();
+
+    circuit.build(description).unwrap();
+    // Literal assignments and overrides are known without emulating.
+    assert_eq!(circuit.get_signal("x"), Some(123));
+    assert_eq!(circuit.get_signal("d"), None);
+    circuit.set_signal("d", 7).unwrap();
+    assert_eq!(circuit.get_signal("d"), Some(7));
+    circuit.set_signal("y", 0).unwrap();
+    assert_eq!(circuit.get_signal("y"), Some(0));
+    assert_eq!(circuit.get_signal("z"), None);
+}
+
+#[test]
+fn invalid_circuits() {
+    let mut circuit = Circuit::new();
+
+    assert_eq!(circuit.build("1 -> x\n2 -> x"),
+               Err(circuit::Error::DuplicateWire("x".to_owned())));
+
+    circuit.build("1 -> x\nx AND y -> z").unwrap();
+    assert_eq!(circuit.emulate(), Err(circuit::Error::UndefinedWire {
+        wire:    "y".to_owned(),
+        used_by: "z".to_owned(),
+    }));
+
+    circuit.build("1 -> x\nx OR c -> a\nNOT a -> b\nb -> c\nc -> d").unwrap();
+    let error = circuit.emulate().unwrap_err();
+    assert_eq!(error, circuit::Error::Cycle(vec!["a".to_owned(),
+                                        "b".to_owned(),
+                                        "c".to_owned()]));
+    assert_eq!(error.to_string(), "cycle detected: a -> b -> c -> a");
+
+    // Overriding a wire breaks the loop.
+    circuit.set_signal("b", 7).unwrap();
+    circuit.emulate().unwrap();
+    assert_eq!(circuit.get_signal("d"), Some(7));
+    assert_eq!(circuit.clear_signal("b"), Err(error));
+    // The failed restoration left the circuit untouched.
+    assert_eq!(circuit.get_signal("d"), Some(7));
+    circuit.set_signal("b", 8).unwrap();
+    assert_eq!(circuit.get_signal("d"), Some(8));
+}
+
+#[test]
+fn export() {
+    let description = "\
+123 -> x
+x AND y -> d
+NOT x -> in
+in LSHIFT 2 -> y
+1 OR d -> e";
+    let mut circuit = Circuit::new();
+
+    circuit.build(description).unwrap();
+    circuit.set_signal("x", 7).unwrap();
+
+    assert_eq!(circuit.to_dot(), "\
+digraph circuit {
+    \"d\" [label=\"d\\nAND\"];
+    \"x\" -> \"d\";
+    \"y\" -> \"d\";
+    \"e\" [label=\"e\\nOR\"];
+    \"e#0\" [label=\"1\", shape=plaintext];
+    \"e#0\" -> \"e\";
+    \"d\" -> \"e\";
+    \"in\" [label=\"in\\nNOT\"];
+    \"x\" -> \"in\";
+    \"x\" [label=\"x\\n7 (override)\"];
+    \"y\" [label=\"y\\nLSHIFT 2\"];
+    \"in\" -> \"y\";
+}
+");
+    assert_eq!(circuit.to_verilog("example"), "\
+module example(
+    output wire [15:0] w_d,
+    output wire [15:0] w_e,
+    output wire [15:0] w_in,
+    output wire [15:0] w_x,
+    output wire [15:0] w_y
+);
+    assign w_d = w_x & w_y;
+    assign w_e = 16'd1 | w_d;
+    assign w_in = ~w_x;
+    assign w_x = 16'd7;
+    assign w_y = w_in << 2;
+endmodule
+");
+}
 // }}}
@@ -14,9 +14,136 @@
 
 use std::fs::File;
 use std::io::Read;
+use std::process;
 
-extern crate regex;
-use regex::Regex;
+#[cfg(test)]
+extern crate quickcheck;
+
+use literal::Error;
+
+// }}}
+// {{{ Literal
+
+/// Codec for the string literals of Santa's list.
+///
+/// A literal is surrounded by double quotes, and may contain the escape
+/// sequences `\\`, `\"` and `\xHH`.
+pub mod literal {
+    use std::error;
+    use std::fmt;
+
+    /// Reasons why a string literal is invalid.
+    ///
+    /// Offsets are in bytes, from the start of the literal.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum Error {
+        /// A double quote is expected at this offset.
+        MissingQuote(usize),
+        /// A double quote ends the literal too early.
+        UnescapedQuote(usize),
+        /// The escape sequence starting at this offset is unknown.
+        InvalidEscape(usize),
+        /// The `\x` at this offset isn't followed by two hex digits.
+        InvalidHex(usize),
+    }
+
+    impl fmt::Display for Error {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match *self {
+                Error::MissingQuote(offset)
+                    => write!(f, "missing double quote at offset {}", offset),
+                Error::UnescapedQuote(offset)
+                    => write!(f, "unescaped double quote at offset {}",
+                              offset),
+                Error::InvalidEscape(offset)
+                    => write!(f, "invalid escape sequence at offset {}",
+                              offset),
+                Error::InvalidHex(offset)
+                    => write!(f, "invalid hexadecimal escape at offset {}",
+                              offset),
+            }
+        }
+    }
+
+    impl error::Error for Error {}
+
+    /// Returns the bytes represented by a string literal.
+    pub fn decode(literal: &str) -> Result<Vec<u8>, Error> {
+        let bytes = literal.as_bytes();
+        let mut data = Vec::with_capacity(bytes.len());
+
+        if bytes.first() != Some(&b'"') {
+            return Err(Error::MissingQuote(0));
+        }
+        let mut i = 1;
+        loop {
+            match bytes.get(i) {
+                None       => return Err(Error::MissingQuote(i)),
+                Some(&b'"') => {
+                    if i != bytes.len() - 1 {
+                        return Err(Error::UnescapedQuote(i));
+                    }
+                    return Ok(data);
+                },
+                Some(&b'\\') => {
+                    let (byte, len) = unescape(&bytes[i..])
+                        .ok_or(Error::InvalidEscape(i))?
+                        .map_err(|_| Error::InvalidHex(i))?;
+                    data.push(byte);
+                    i += len;
+                },
+                Some(&byte) => {
+                    data.push(byte);
+                    i += 1;
+                },
+            }
+        }
+    }
+
+    /// Decodes the escape sequence at the start of `s`.
+    ///
+    /// Returns `None` for unknown sequences, and an error for invalid
+    /// hexadecimal ones. Otherwise, returns the byte and the sequence length.
+    fn unescape(s: &[u8]) -> Option<Result<(u8, usize), ()>> {
+        match s.get(1) {
+            Some(&b'\\') => Some(Ok((b'\\', 2))),
+            Some(&b'"')  => Some(Ok((b'"', 2))),
+            Some(&b'x')  => {
+                let digits = s.get(2..4).map(|hex| {
+                    hex.iter().map(|&c| char::from(c).to_digit(16))
+                              .collect::<Option<Vec<_>>>()
+                });
+                Some(match digits {
+                    Some(Some(digits)) => {
+                        Ok(((digits[0] * 16 + digits[1]) as u8, 4))
+                    },
+                    _ => Err(()),
+                })
+            },
+            _ => None,
+        }
+    }
+
+    /// Returns the string literal representing these bytes.
+    ///
+    /// Printable ASCII characters are kept as is (except `"` and `\`, which
+    /// are escaped), everything else uses the hexadecimal notation.
+    pub fn encode(data: &[u8]) -> String {
+        let mut literal = String::with_capacity(data.len() + 2);
+
+        literal.push('"');
+        for &byte in data {
+            match byte {
+                b'"'         => literal.push_str("\\\""),
+                b'\\'        => literal.push_str("\\\\"),
+                b' ' ..= b'~' => literal.push(char::from(byte)),
+                _            => literal.push_str(&format!("\\x{:02x}", byte)),
+            }
+        }
+        literal.push('"');
+        literal
+    }
+}
 
 // }}}
 
@@ -26,33 +153,13 @@ fn code_length(s: &str) -> usize {
 }
 
 // Return the number of characters in the in-memory string.
-fn memory_length(s: &str) -> usize {
-    // -2 because we ignore the surrounding double quotes.
-    let len = code_length(s) - 2;
-
-    let esc = Regex::new(r#"\\\\|\\""#).unwrap();
-    // -1 per \\ and \" because they occupies two characters but count as one.
-    let simple_esc = esc.find_iter(s).count();
-
-    // -3 per \xHH because they uses four characters but count as one.
-    let hex = Regex::new(r"(?P<prefix>\\+)x[[:xdigit:]]{2}").unwrap();
-    let hex_esc = hex.captures_iter(s).fold(0, |acc, caps|
-        // Only count \xFF, not \\xFF.
-        if caps["prefix"].len() % 2 == 1 {
-            acc + 3
-        } else {
-            acc
-        }
-    );
-    len - simple_esc - hex_esc
+fn memory_length(s: &str) -> Result<usize, Error> {
+    literal::decode(s).map(|data| data.len())
 }
 
 // Return the number of characters in the encoded string.
 fn encoded_length(s: &str) -> usize {
-    let len = code_length(s) + 2;
-
-    let esc = Regex::new(r#"\\|""#).unwrap();
-    len + esc.find_iter(s).count()
+    literal::encode(s.as_bytes()).len()
 }
 
 fn main() {
@@ -61,9 +168,18 @@ fn main() {
 
     file.read_to_string(&mut input).unwrap();
 
-    let code_sum: usize = input.lines().map(|l| code_length(l)).sum();
-    let mem_sum:  usize = input.lines().map(|l| memory_length(l)).sum();
-    let enc_sum:  usize = input.lines().map(|l| encoded_length(l)).sum();
+    let code_sum: usize = input.lines().map(code_length).sum();
+    let mut mem_sum     = 0;
+    for (i, line) in input.lines().enumerate() {
+        match memory_length(line) {
+            Ok(len)  => mem_sum += len,
+            Err(err) => {
+                eprintln!("line {}: {}", i + 1, err);
+                process::exit(1);
+            },
+        }
+    }
+    let enc_sum:  usize = input.lines().map(encoded_length).sum();
 
     println!("The difference between code and in-memory is {}",
              code_sum - mem_sum);
@@ -78,7 +194,8 @@ fn examples_part1() {
     let input = vec![r#""""#, r#""abc""#, r#""aaa\"aaa""#, r#""\x27""#];
 
     let code_sum: usize = input.iter().map(|s| code_length(s)).sum();
-    let mem_sum:  usize = input.iter().map(|s| memory_length(s)).sum();
+    let mem_sum:  usize = input.iter().map(|s| memory_length(s))
+                               .sum::<Result<_, _>>().unwrap();
 
     assert_eq!(code_sum - mem_sum, 12);
 }
@@ -93,4 +210,89 @@ fn examples_part2() {
     assert_eq!(enc_sum - code_sum, 19);
 }
 
+#[test]
+fn decode() {
+    assert_eq!(literal::decode(r#""a\\b\"c\x41\x7e""#),
+               Ok(b"a\\b\"cA~".to_vec()));
+    assert_eq!(literal::decode(r#""\xff\x00""#), Ok(vec![0xff, 0x00]));
+
+    assert_eq!(literal::decode(r#"abc""#),    Err(Error::MissingQuote(0)));
+    assert_eq!(literal::decode(r#""abc"#),    Err(Error::MissingQuote(4)));
+    assert_eq!(literal::decode(r#"""#),       Err(Error::MissingQuote(1)));
+    assert_eq!(literal::decode(r#""\""#),     Err(Error::MissingQuote(3)));
+    assert_eq!(literal::decode(r#""a"b""#),   Err(Error::UnescapedQuote(2)));
+    assert_eq!(literal::decode(r#""ab\n""#),  Err(Error::InvalidEscape(3)));
+    assert_eq!(literal::decode(r#""ab\x4g""#), Err(Error::InvalidHex(3)));
+    assert_eq!(literal::decode(r#""ab\x4""#), Err(Error::InvalidHex(3)));
+    assert_eq!(Error::InvalidHex(3).to_string(),
+               "invalid hexadecimal escape at offset 3");
+}
+
+#[test]
+fn encode() {
+    assert_eq!(literal::encode(b""), r#""""#);
+    assert_eq!(literal::encode(b"a\\b\"c\n\x7f"), r#""a\\b\"c\x0a\x7f""#);
+    // Non-canonical escapes don't survive a round trip.
+    assert_eq!(literal::encode(&literal::decode(r#""\x27""#).unwrap()),
+               r#""'""#);
+}
+
+#[cfg(test)]
+fn encode_then_decode(data: Vec<u8>) -> bool {
+    literal::decode(&literal::encode(&data)) == Ok(data)
+}
+
+/// Canonical literals (the ones produced by the encoder) round-trip.
+///
+/// Other literals don't: the same byte may be written in several ways (e.g.
+/// `"\x27"` and `"'"`), and the encoder only knows one of them.
+#[cfg(test)]
+fn canonical_round_trip(data: Vec<u8>) -> bool {
+    let literal = literal::encode(&data);
+    literal::decode(&literal).map(|data| literal::encode(&data))
+        == Ok(literal)
+}
+
+#[cfg(test)]
+fn length_relations(s: String) -> bool {
+    let literal = literal::encode(s.as_bytes());
+    let escapes = s.bytes().filter(|&c| c == b'"' || c == b'\\').count();
+    memory_length(&literal) == Ok(s.len())
+        && code_length(&literal) >= s.len() + 2
+        // Part two: quotes and backslashes are the only thing to escape.
+        && (s.is_ascii() && !s.contains(|c: char| c.is_ascii_control())) ==
+           (encoded_length(&s) == code_length(&s) + escapes + 2)
+}
+
+#[cfg(test)]
+fn decode_never_panics(parts: Vec<(String, u8)>) -> bool {
+    // Interleave random text with fragments of escape sequences, to make
+    // sure (valid or broken) escapes are exercised.
+    const FRAGMENTS: [&str; 8] = [
+        "\\", "\\\\", "\\\"", "\\x", "\"", "x", "7", "g",
+    ];
+    let mut s = String::from("\"");
+    for (text, fragment) in &parts {
+        s.push_str(text);
+        s.push_str(FRAGMENTS[usize::from(*fragment) % FRAGMENTS.len()]);
+    }
+    s.push('"');
+    match literal::decode(&s) {
+        Ok(data) => literal::decode(&literal::encode(&data)) == Ok(data),
+        Err(_)   => true,
+    }
+}
+
+#[test]
+fn properties() {
+    let round_trip: fn(Vec<u8>) -> bool = encode_then_decode;
+    let canonical:  fn(Vec<u8>) -> bool = canonical_round_trip;
+    let lengths:    fn(String) -> bool  = length_relations;
+    let robust:     fn(Vec<(String, u8)>) -> bool = decode_never_panics;
+    quickcheck::quickcheck(round_trip);
+    quickcheck::quickcheck(canonical);
+    quickcheck::quickcheck(lengths);
+    quickcheck::quickcheck(robust);
+}
+
 // }}}
@@ -12,7 +12,9 @@
 // }}}
 // {{{ Crates
 
-use std::collections::HashSet;
+use std::cmp;
+use std::env;
+use std::fmt::Write;
 use std::fs::File;
 use std::io::BufRead;
 use std::io::BufReader;
@@ -38,84 +40,251 @@ struct Move {
 }
 
 impl FromStr for Move {
-    type Err = ();
+    type Err = String;
 
     /// Builds a `Move` from a string.
     ///
     /// The input string should match the following regexp: "^(L|R)\d+$".
-    fn from_str(s: &str) -> Result<Move, ()> {
+    fn from_str(s: &str) -> Result<Move, String> {
         let mut chars = s.chars();
 
-        let turn = match chars.nth(0).unwrap() {
-            'L' => Turn::Left,
-            'R' => Turn::Right,
-            c   => panic!("unexpected turn direction: {}", c),
+        let turn = match chars.next() {
+            Some('L') => Turn::Left,
+            Some('R') => Turn::Right,
+            Some(c)   => return Err(format!("unexpected turn direction: {}", c)),
+            None      => return Err("empty instruction".to_owned()),
         };
-        let steps: i32 = chars.as_str().parse().unwrap();
+        // Steps are signed like the coordinates, but can't be negative.
+        let steps = chars.as_str().parse::<i32>().ok()
+                         .filter(|&steps| steps >= 0)
+                         .ok_or_else(|| {
+                             format!("invalid step count: {:?}", chars.as_str())
+                         })?;
 
-        Ok(Move { turn: turn, steps: steps })
+        Ok(Move { turn, steps })
     }
 }
 
 // }}}
 // {{{ Point
 
-/// Represents an agent working for Santa.
+/// Represents a point's orientation.
+#[derive(Clone, Copy, Debug)]
+enum Direction {
+    North,
+    East,
+    South,
+    West,
+}
+
+/// Represents a location on the map.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+struct Point {
+    x:   i32,
+    y:   i32,
+}
+
+impl Point {
+    /// Returns the point `distance` blocks away in the given direction.
+    fn walk(self, direction: Direction, distance: i32) -> Point {
+        match direction {
+            Direction::North => Point { x: self.x, y: self.y + distance },
+            Direction::East  => Point { x: self.x + distance, y: self.y },
+            Direction::South => Point { x: self.x, y: self.y - distance },
+            Direction::West  => Point { x: self.x - distance, y: self.y },
+        }
+    }
+}
+
+// }}}
+// {{{ Segment
+
+/// Represents a straight walk, from one point to another.
+///
+/// Segments are always axis-aligned and both ends belong to the segment.
+#[derive(Clone, Copy, Debug)]
+struct Segment {
+    start:  Point,
+    end:    Point,
+}
+
+impl Segment {
+    /// Returns the number of blocks walked along the segment.
+    fn len(&self) -> i32 {
+        manhattan_dist(self.start, self.end)
+    }
+
+    /// Returns the unit step walked along the segment.
+    fn step(&self) -> (i32, i32) {
+        ((self.end.x - self.start.x).signum(),
+         (self.end.y - self.start.y).signum())
+    }
+
+    /// Returns the points of the segment, from its start to its end.
+    fn points(&self) -> impl Iterator<Item=Point> {
+        let (start, (dx, dy)) = (self.start, self.step());
+        (0..self.len() + 1).map(move |i| {
+            Point { x: start.x + i * dx, y: start.y + i * dy }
+        })
+    }
+
+    /// Returns the segment without its first block.
+    ///
+    /// The segment must be at least one block long.
+    fn tail(&self) -> Segment {
+        let (dx, dy) = self.step();
+        let start = Point { x: self.start.x + dx, y: self.start.y + dy };
+        Segment { start, end: self.end }
+    }
+
+    /// Returns the part of the segment shared with `other`, if any.
+    ///
+    /// The overlap is walked in the same direction as `self`: its start is
+    /// the shared point nearest to the start of `self`.
+    fn intersection(&self, other: &Segment) -> Option<Segment> {
+        // Both segments being axis-aligned, their intersection is the
+        // intersection of their bounding boxes (if any).
+        let (xmin, xmax) = overlap(self.start.x, self.end.x,
+                                   other.start.x, other.end.x);
+        let (ymin, ymax) = overlap(self.start.y, self.end.y,
+                                   other.start.y, other.end.y);
+        if xmin > xmax || ymin > ymax {
+            return None;
+        }
+        let lo = Point { x: xmin, y: ymin };
+        let hi = Point { x: xmax, y: ymax };
+        let forward = manhattan_dist(self.start, lo)
+                   <= manhattan_dist(self.start, hi);
+        Some(if forward {
+            Segment { start: lo, end: hi }
+        } else {
+            Segment { start: hi, end: lo }
+        })
+    }
+}
+
+/// Returns the overlap of the ranges [a1, a2] and [b1, b2].
+///
+/// The overlap is empty if the lower bound is greater than the upper one.
+fn overlap(a1: i32, a2: i32, b1: i32, b2: i32) -> (i32, i32) {
+    (cmp::max(cmp::min(a1, a2), cmp::min(b1, b2)),
+     cmp::min(cmp::max(a1, a2), cmp::max(b1, b2)))
+}
+
+// }}}
+// {{{ Navigator
+
+/// Represents an agent working for Santa, keeping track of its route.
 #[derive(Debug)]
-struct Agent {
+struct Navigator {
     position:  Point,
     direction: Direction,
-    has_read_all: bool,
+    route:     Vec<Segment>,
 }
 
-impl Agent {
-    /// Drops the agent on the landing site.
-    fn new(has_read_all: bool) -> Agent {
-        Agent {
-            position: Point { x: 0, y: 0 },
+impl Navigator {
+    /// Drops the navigator on the landing site.
+    fn new() -> Navigator {
+        Navigator {
+            position:  Point { x: 0, y: 0 },
             direction: Direction::North,
-            has_read_all: has_read_all,
+            route:     Vec::new(),
         }
     }
 
     /// Moves according to the instructions.
     fn follow_instructions(&mut self, instructions: &[Move]) {
-        let mut blocks = HashSet::new();
-
         for instruction in instructions {
-            self.direction = turn(self.direction, instruction.turn);
-            for _ in 0 .. instruction.steps {
-                // If we have read **all** the instructions, we know we should
-                // stop as soon as we walk on the same block twice.
-                if self.has_read_all && !blocks.insert(self.position) {
-                    break;
-                }
-                match self.direction {
-                    Direction::North => self.position.y += 1,
-                    Direction::East  => self.position.x += 1,
-                    Direction::South => self.position.y -= 1,
-                    Direction::West  => self.position.x -= 1,
-                }
-
-            }
+            self.walk(instruction);
         }
     }
-}
 
-/// Represents a point's orientation.
-#[derive(Clone, Copy, Debug)]
-enum Direction {
-    North,
-    East,
-    South,
-    West,
-}
+    /// Turns, then walks straight ahead.
+    fn walk(&mut self, instruction: &Move) {
+        self.direction = turn(self.direction, instruction.turn);
+        if instruction.steps == 0 {
+            return;
+        }
+        let start = self.position;
+        self.position = start.walk(self.direction, instruction.steps);
+        self.route.push(Segment { start, end: self.position });
+    }
 
-/// Represents a location on the map.
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
-struct Point {
-    x:   i32,
-    y:   i32,
+    /// Returns the segments walked so far.
+    fn route(&self) -> &[Segment] {
+        &self.route
+    }
+
+    /// Returns the first location visited twice, if any.
+    fn first_crossing(&self) -> Option<Point> {
+        (1..self.route.len()).filter_map(|i| {
+            let start = self.route[i].start;
+            self.revisits(i).iter().map(|overlap| overlap.start)
+                            .min_by_key(|&p| manhattan_dist(start, p))
+        }).next()
+    }
+
+    /// Returns every location visited again, in the order of the walk.
+    ///
+    /// A location is reported each time it is visited again.
+    fn revisited(&self) -> Vec<Point> {
+        (1..self.route.len()).flat_map(|i| {
+            let start = self.route[i].start;
+            let mut points = self.revisits(i).iter()
+                                 .flat_map(Segment::points)
+                                 .collect::<Vec<_>>();
+            points.sort_by_key(|&p| manhattan_dist(start, p));
+            points.dedup();
+            points
+        }).collect()
+    }
+
+    /// Returns the parts of the i-th segment already walked before.
+    fn revisits(&self, i: usize) -> Vec<Segment> {
+        // The start was visited by the previous segment, not again.
+        let segment = self.route[i].tail();
+        self.route[..i].iter()
+            .filter_map(|previous| segment.intersection(previous))
+            .collect()
+    }
+
+    /// Renders the route as an SVG image.
+    ///
+    /// The landing site is green, the last position is red and the first
+    /// location visited twice (if any) is blue. North is up.
+    fn to_svg(&self) -> String {
+        let origin = Point { x: 0, y: 0 };
+        let points = self.route.iter().map(|segment| segment.end);
+        let (xmin, xmax, ymin, ymax) = points.clone().fold(
+            (0, 0, 0, 0),
+            |(xmin, xmax, ymin, ymax), p| {
+                (cmp::min(xmin, p.x), cmp::max(xmax, p.x),
+                 cmp::min(ymin, p.y), cmp::max(ymax, p.y))
+            });
+        let mut svg = String::new();
+
+        // Flip the y axis, and leave some room around the route.
+        writeln!(svg, "<svg xmlns=\"http://www.w3.org/2000/svg\" \
+                       viewBox=\"{} {} {} {}\">",
+                 xmin - 1, -ymax - 1, xmax - xmin + 2, ymax - ymin + 2)
+            .unwrap();
+        let coords = Some(origin).into_iter().chain(points)
+            .map(|p| format!("{},{}", p.x, -p.y))
+            .collect::<Vec<_>>();
+        writeln!(svg, "  <polyline points=\"{}\" fill=\"none\" \
+                       stroke=\"black\" stroke-width=\"0.5\"/>",
+                 coords.join(" ")).unwrap();
+        let mut marks = vec![(origin, "green"), (self.position, "red")];
+        if let Some(crossing) = self.first_crossing() {
+            marks.push((crossing, "blue"));
+        }
+        for (p, color) in marks {
+            writeln!(svg, "  <circle cx=\"{}\" cy=\"{}\" r=\"1\" \
+                           fill=\"{}\"/>", p.x, -p.y, color).unwrap();
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
 }
 
 // }}}
@@ -147,18 +316,29 @@ fn manhattan_dist(p: Point, q: Point) -> i32 {
     (p.x - q.x).abs() + (p.y - q.y).abs()
 }
 
+/// Parses the document's instructions.
+fn parse_instructions(instructions: &str) -> Result<Vec<Move>, String> {
+    instructions.split(',').map(|s| s.trim().parse()).collect()
+}
+
 /// Computes the distance between our landing site and the Easter Bunny HQ.
-fn follow_instructions(instructions: &str, has_read_all: bool) -> i32{
-    // Parse the document's instructions.
-    let moves: Vec<Move> = instructions.split(',')
-                                       .map(|s| s.trim().parse().unwrap())
-                                       .collect();
+///
+/// If we have read **all** the instructions, we know we should stop as soon
+/// as we walk on the same block twice.
+fn follow_instructions(instructions: &str, has_read_all: bool)
+    -> Result<i32, String> {
+    let moves = parse_instructions(instructions)?;
     // Follow the instructions.
     let start = Point { x: 0, y: 0 };
-    let mut me = Agent::new(has_read_all);
+    let mut me = Navigator::new();
     me.follow_instructions(&moves);
     // Compute the distance.
-    manhattan_dist(start, me.position)
+    let hq = if has_read_all {
+        me.first_crossing().unwrap_or(me.position)
+    } else {
+        me.position
+    };
+    Ok(manhattan_dist(start, hq))
 }
 
 // }}}
@@ -171,24 +351,120 @@ fn main() {
     BufReader::new(&file).read_line(&mut input).unwrap();
     assert!(!input.is_empty());
 
+    // Draw the route instead of looking for the HQ.
+    if env::args().nth(1).as_deref() == Some("--svg") {
+        let mut me = Navigator::new();
+        me.follow_instructions(&parse_instructions(&input).unwrap());
+        print!("{}", me.to_svg());
+        return;
+    }
+
     println!("The Easter Bunny Headquarters seems to be {} blocks away.",
-             follow_instructions(&input, false));
+             follow_instructions(&input, false).unwrap());
     println!("In fact, the Easter Bunny Headquarters is {} blocks away.",
-             follow_instructions(&input, true));
+             follow_instructions(&input, true).unwrap());
+
+    let mut me = Navigator::new();
+    me.follow_instructions(&parse_instructions(&input).unwrap());
+    println!("The whole trip is {} blocks long, with {} revisits.",
+             me.route().iter().map(Segment::len).sum::<i32>(),
+             me.revisited().len());
 }
 
 // {{{ Tests
 
 #[test]
 fn examples_part1() {
-    assert_eq!(follow_instructions("R2, L3",         false),  5);
-    assert_eq!(follow_instructions("R2, R2, R2",     false),  2);
-    assert_eq!(follow_instructions("R5, L5, R5, R3", false), 12);
+    assert_eq!(follow_instructions("R2, L3",         false), Ok(5));
+    assert_eq!(follow_instructions("R2, R2, R2",     false), Ok(2));
+    assert_eq!(follow_instructions("R5, L5, R5, R3", false), Ok(12));
 }
 
 #[test]
 fn examples_part2() {
-    assert_eq!(follow_instructions("R8, R4, R4, R8", true), 4);
+    assert_eq!(follow_instructions("R8, R4, R4, R8", true), Ok(4));
+}
+
+#[test]
+fn invalid_instructions() {
+    assert_eq!(follow_instructions("R2, U3", false),
+               Err("unexpected turn direction: U".to_owned()));
+    assert_eq!(follow_instructions("R2, L-3", false),
+               Err("invalid step count: \"-3\"".to_owned()));
+    assert_eq!(follow_instructions("R2,, L3", false),
+               Err("empty instruction".to_owned()));
+    assert_eq!(follow_instructions("R2147483647", false), Ok(2147483647));
+    assert_eq!(follow_instructions("R2147483648", false),
+               Err("invalid step count: \"2147483648\"".to_owned()));
+}
+
+#[test]
+fn crossings() {
+    let mut me = Navigator::new();
+    me.follow_instructions(&parse_instructions("R8, R4, R4, R8, R0, R6, L0, R3")
+                               .unwrap());
+
+    let route = me.route();
+    assert_eq!(route.len(), 6);
+    assert_eq!(route.iter().map(Segment::len).sum::<i32>(), 33);
+    assert_eq!(me.first_crossing(), Some(Point { x: 4, y: 0 }));
+    // The last two segments walk back along the previous one.
+    assert_eq!(me.revisited(), vec![
+        Point { x: 4, y:  0 },
+        Point { x: 4, y:  3 },
+        Point { x: 4, y:  2 },
+        Point { x: 4, y:  1 },
+        Point { x: 4, y:  0 },
+        Point { x: 4, y: -1 },
+        Point { x: 4, y: -2 },
+        Point { x: 4, y: -3 },
+        Point { x: 4, y: -4 },
+    ]);
+
+    let mut me = Navigator::new();
+    me.follow_instructions(&parse_instructions("R2, L3").unwrap());
+    assert_eq!(me.first_crossing(), None);
+    assert!(me.revisited().is_empty());
+}
+
+#[test]
+fn intersections() {
+    let horizontal = Segment { start: Point { x: 10, y: 0 },
+                               end:   Point { x: -10, y: 0 } };
+    let vertical   = Segment { start: Point { x: 3, y: -5 },
+                               end:   Point { x: 3, y: 5 } };
+    let parallel   = Segment { start: Point { x: -4, y: 0 },
+                               end:   Point { x: 20, y: 0 } };
+    let away       = Segment { start: Point { x: 0, y: 1 },
+                               end:   Point { x: 0, y: 9 } };
+
+    let crossing = horizontal.intersection(&vertical).unwrap();
+    assert_eq!((crossing.start, crossing.end),
+               (Point { x: 3, y: 0 }, Point { x: 3, y: 0 }));
+    // The overlap starts at the point nearest to the start of `self`.
+    let shared = horizontal.intersection(&parallel).unwrap();
+    assert_eq!((shared.start, shared.end),
+               (Point { x: 10, y: 0 }, Point { x: -4, y: 0 }));
+    assert_eq!(shared.points().count(), 15);
+    assert!(horizontal.intersection(&away).is_none());
+    // Long overlaps don't cost more than short ones.
+    let long = Segment { start: Point { x: 0, y: 0 },
+                         end:   Point { x: 1_000_000_000, y: 0 } };
+    assert_eq!(long.intersection(&long.tail()).unwrap().start,
+               Point { x: 1, y: 0 });
+}
+
+#[test]
+fn svg() {
+    let mut me = Navigator::new();
+    me.follow_instructions(&parse_instructions("R8, R4, R4, R8").unwrap());
+
+    let svg = me.to_svg();
+    assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\" \
+                             viewBox=\"-1 -5 10 10\">"));
+    assert!(svg.contains("points=\"0,0 8,0 8,4 4,4 4,-4\""));
+    assert!(svg.contains("<circle cx=\"4\" cy=\"0\" r=\"1\" fill=\"blue\"/>"));
+    assert!(svg.ends_with("</svg>\n"));
 }
 
 // }}}
@@ -12,9 +12,7 @@
 // }}}
 // {{{ Crates
 
-#[macro_use]
-extern crate lazy_static;
-
+use std::collections::{HashMap, VecDeque};
 use std::fs::File;
 use std::io::Read;
 
@@ -22,85 +20,107 @@ use std::io::Read;
 // {{{ Keypad
 
 /// Represents a location on the keypad.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 struct Point {
-    x:   i8,
-    y:   i8,
+    x:   i32,
+    y:   i32,
 }
 
-/// A square keypad that controls the access to a room.
+/// A keypad that controls the access to a room.
+#[derive(Debug)]
 struct Keypad {
-    keys: Vec<Option<char>>, // Keys layout.
-    size: i8,                // Size of the keypad's side.
-    key_5 : Point,           // Position of the key "5".
+    keys:   Vec<Option<char>>, // Keys layout, row by row.
+    width:  i32,               // Number of columns.
+    height: i32,               // Number of rows.
+    start:  Point,             // Starting position of the finger.
 }
 
 impl Keypad {
-    /// Creates a keypad with the specified size and layout.
-    fn new(keys: &[Option<char>], side: i8, starting_pos: Point) -> Keypad {
-        assert_eq!((side*side) as usize, keys.len());
-
-        let mut layout = Vec::with_capacity(keys.len());
-        layout.extend(keys.iter().cloned());
+    /// Creates a keypad from a drawing of its layout.
+    ///
+    /// Each line is a row and each character a key, spaces standing for the
+    /// holes. The rows are padded with holes to the width of the longest one.
+    /// The finger starts on the key `start`.
+    fn parse(layout: &str, start: char) -> Result<Keypad, String> {
+        let rows   = layout.lines().collect::<Vec<_>>();
+        let width  = rows.iter().map(|row| row.chars().count()).max()
+                         .unwrap_or(0);
+        let mut keys = Vec::with_capacity(width * rows.len());
+
+        for row in &rows {
+            let len = row.chars().count();
+            for key in row.chars() {
+                if key == ' ' {
+                    keys.push(None);
+                    continue;
+                }
+                if keys.contains(&Some(key)) {
+                    return Err(format!("duplicate key: {}", key));
+                }
+                keys.push(Some(key));
+            }
+            keys.extend((len..width).map(|_| None));
+        }
 
-        Keypad { keys: layout, size: side, key_5: starting_pos }
+        let idx = keys.iter().position(|&key| key == Some(start))
+                             .ok_or_else(|| format!("no key {}", start))?;
+        let width = width as i32;
+        let start = Point { x: idx as i32 % width, y: idx as i32 / width };
+        Ok(Keypad { keys, width, height: rows.len() as i32, start })
     }
 
-    /// Return the starting position (i.e the position of the key "5").
+    /// Return the starting position.
     fn starting_pos(&self) -> Point {
-        self.key_5
+        self.start
     }
 
     /// Checks if the position is valid.
     ///
     /// A position is valid if it is on the keypad, over a key.
     fn position_is_valid(&self, position: Point) -> bool {
-        let idx = position.y * self.size + position.x;
+        self.key_at(position).is_some()
+    }
 
-        position.y >= 0 && position.y < self.size &&
-        position.x >= 0 && position.x < self.size &&
-        self.keys[idx as usize].is_some()
+    /// Returns the key at this position, if any.
+    fn key_at(&self, position: Point) -> Option<char> {
+        if position.y < 0 || position.y >= self.height ||
+           position.x < 0 || position.x >= self.width {
+            return None;
+        }
+        self.keys[(position.y * self.width + position.x) as usize]
     }
 
     fn get_key_at(&self, position: Point) -> char {
-        let idx = position.y * self.size + position.x;
-        self.keys[idx as usize].unwrap()
-    }
-}
-
-/// The keypad to unlock the bathroom.
-lazy_static! {
-    /// Represent the following keypad:
-    /// ┌───────┐
-    /// │1  2  3│
-    /// │4  5  6│
-    /// │7  8  9│
-    /// └───────┘
-    static ref SIMPLE_KEYPAD: Keypad = {
-        Keypad::new(&[Some('1'), Some('2'), Some('3'),
-                      Some('4'), Some('5'), Some('6'),
-                      Some('7'), Some('8'), Some('9')],
-                    3, Point { x: 1, y: 1 })
-    };
-
-    /// Represent the following keypad:
-    /// ┌─────────────┐
-    /// │      1      │
-    /// │   2  3  4   │
-    /// │5  6  7  8  9│
-    /// │   A  B  C   │
-    /// │      D      │
-    /// └─────────────┘
-    static ref WTF_KEYPAD: Keypad = {
-        Keypad::new(&[  None,      None,    Some('1'),   None,      None,
-                        None,    Some('2'), Some('3'), Some('4'),   None,
-                      Some('5'), Some('6'), Some('7'), Some('8'), Some('9'),
-                        None,    Some('A'), Some('B'), Some('C'),   None,
-                        None,      None,    Some('D'),   None,      None],
-                    5, Point { x: 0, y: 2 })
-    };
+        self.key_at(position).expect("no key under the finger")
+    }
+
+    /// Returns the position of a key, if it's on the keypad.
+    fn find_key(&self, key: char) -> Option<Point> {
+        self.keys.iter().position(|&k| k == Some(key)).map(|idx| Point {
+            x: idx as i32 % self.width,
+            y: idx as i32 / self.width,
+        })
+    }
 }
 
+/// Represent the following keypad:
+/// ┌───────┐
+/// │1  2  3│
+/// │4  5  6│
+/// │7  8  9│
+/// └───────┘
+const SIMPLE_LAYOUT: &str = "123\n456\n789";
+
+/// Represent the following keypad:
+/// ┌─────────────┐
+/// │      1      │
+/// │   2  3  4   │
+/// │5  6  7  8  9│
+/// │   A  B  C   │
+/// │      D      │
+/// └─────────────┘
+const WTF_LAYOUT: &str = "  1\n 234\n56789\n ABC\n  D";
+
 // }}}
 // {{{ Finger
 
@@ -108,26 +128,23 @@ lazy_static! {
 #[derive(Debug)]
 struct Finger {
     position: Point,
+    path:     Vec<Point>, // Every position taken, starting position included.
 }
 
 impl Finger {
     /// Creates a finger.
     fn new(starting_pos: Point) -> Finger {
-        Finger { position: starting_pos }
+        Finger { position: starting_pos, path: vec![starting_pos] }
     }
 
     /// Move the finger over the keypad.
+    ///
+    /// Moves that would leave the keys are ignored.
     fn mv(&mut self, movement: &Move, keypad: &Keypad) {
-        let mut target = self.position;
-
-        match *movement {
-            Move::Up    => target.y -= 1,
-            Move::Down  => target.y += 1,
-            Move::Left  => target.x -= 1,
-            Move::Right => target.x += 1,
-        }
+        let target = movement.apply(self.position);
         if keypad.position_is_valid(target) {
             self.position = target;
+            self.path.push(target);
         }
     }
 
@@ -135,6 +152,11 @@ impl Finger {
     fn press_key(&self, keypad: &Keypad) -> char {
         keypad.get_key_at(self.position)
     }
+
+    /// Returns the positions the finger went through.
+    fn path(&self) -> &[Point] {
+        &self.path
+    }
 }
 
 // }}}
@@ -143,7 +165,7 @@ impl Finger {
 /// Represents a movement of the finger.
 ///
 /// The movement is relative to the current finger's position.
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug)]
 enum Move {
     Up,
     Down,
@@ -166,20 +188,99 @@ impl From<char> for Move {
     }
 }
 
+impl From<Move> for char {
+    fn from(movement: Move) -> char {
+        match movement {
+            Move::Up    => 'U',
+            Move::Down  => 'D',
+            Move::Left  => 'L',
+            Move::Right => 'R',
+        }
+    }
+}
+
+impl Move {
+    /// Returns the position reached from `position`.
+    fn apply(self, mut position: Point) -> Point {
+        match self {
+            Move::Up    => position.y -= 1,
+            Move::Down  => position.y += 1,
+            Move::Left  => position.x -= 1,
+            Move::Right => position.x += 1,
+        }
+        position
+    }
+}
+
 // }}}
 
+#[cfg(test)]
 fn follow_instructions(instructions: &str, keypad: &Keypad) -> String {
+    type_code(instructions.lines(), keypad).0
+}
+
+/// Types a key per line of instructions.
+///
+/// Returns the code and the positions the finger went through.
+fn type_code<'a, I>(lines: I, keypad: &Keypad) -> (String, Vec<Point>)
+    where I: IntoIterator<Item=&'a str> {
     let mut code  = String::new();
     let mut pinky = Finger::new(keypad.starting_pos());
 
-    for line in instructions.lines() {
+    for line in lines {
         let moves: Vec<Move> = line.chars().map(Move::from).collect();
         for movement in moves {
             pinky.mv(&movement, keypad);
         }
         code.push(pinky.press_key(keypad));
     }
-    code
+    (code, pinky.path().to_vec())
+}
+
+/// Computes the shortest instructions to type `code`, one line per key.
+///
+/// Fails if a key is not on the keypad, or cannot be reached.
+fn shortest_instructions(code: &str, keypad: &Keypad)
+    -> Result<Vec<String>, String> {
+    let mut position = keypad.starting_pos();
+    code.chars().map(|key| {
+        let target = keypad.find_key(key)
+                           .ok_or_else(|| format!("no key {}", key))?;
+        let moves  = shortest_path(keypad, position, target)
+                           .ok_or_else(|| format!("key {} is unreachable", key))?;
+        position = target;
+        Ok(moves)
+    }).collect()
+}
+
+/// Finds the shortest moves from `from` to `to` (breadth-first search).
+fn shortest_path(keypad: &Keypad, from: Point, to: Point) -> Option<String> {
+    let moves = [Move::Up, Move::Down, Move::Left, Move::Right];
+    // For each position reached, where it's coming from and how.
+    let mut previous = HashMap::new();
+    let mut queue    = VecDeque::new();
+
+    queue.push_back(from);
+    while let Some(position) = queue.pop_front() {
+        if position == to {
+            let mut path = Vec::new();
+            let mut current = to;
+            while let Some(&(prev, movement)) = previous.get(&current) {
+                path.push(char::from(movement));
+                current = prev;
+            }
+            return Some(path.into_iter().rev().collect());
+        }
+        for &movement in &moves {
+            let next = movement.apply(position);
+            if keypad.position_is_valid(next) && next != from &&
+               !previous.contains_key(&next) {
+                previous.insert(next, (position, movement));
+                queue.push_back(next);
+            }
+        }
+    }
+    None
 }
 
 fn main() {
@@ -187,24 +288,74 @@ fn main() {
     let mut input = String::new();
 
     file.read_to_string(&mut input).unwrap();
-    println!("The bathroom code should be {}.",
-             follow_instructions(&input, &SIMPLE_KEYPAD));
-    println!("Ho wait! On this crazy keypad, the code is {}.",
-             follow_instructions(&input, &WTF_KEYPAD));
+    let simple_keypad = Keypad::parse(SIMPLE_LAYOUT, '5').unwrap();
+    let wtf_keypad    = Keypad::parse(WTF_LAYOUT, '5').unwrap();
+
+    let (code, path) = type_code(input.lines(), &simple_keypad);
+    println!("The bathroom code should be {} ({} moves).", code, path.len() - 1);
+    let (code, path) = type_code(input.lines(), &wtf_keypad);
+    println!("Ho wait! On this crazy keypad, the code is {} ({} moves).",
+             code, path.len() - 1);
+
+    let shortest = shortest_instructions(&code, &wtf_keypad).unwrap();
+    println!("It could have been typed in {} moves.",
+             shortest.iter().map(String::len).sum::<usize>());
 }
 
 // {{{ Tests
 
 #[test]
 fn examples_part1() {
-    assert_eq!(follow_instructions("ULL\nRRDDD\nLURDL\nUUUUD", &SIMPLE_KEYPAD),
+    assert_eq!(follow_instructions("ULL\nRRDDD\nLURDL\nUUUUD", &Keypad::parse(SIMPLE_LAYOUT, '5').unwrap()),
                "1985");
 }
 
 #[test]
 fn examples_part2() {
-    assert_eq!(follow_instructions("ULL\nRRDDD\nLURDL\nUUUUD", &WTF_KEYPAD),
+    assert_eq!(follow_instructions("ULL\nRRDDD\nLURDL\nUUUUD", &Keypad::parse(WTF_LAYOUT, '5').unwrap()),
                "5DB3");
 }
 
+#[test]
+fn layouts() {
+    let keypad = Keypad::parse("ab\n c\nd", 'c').unwrap();
+    assert_eq!((keypad.width, keypad.height), (2, 3));
+    assert_eq!(keypad.starting_pos(), Point { x: 1, y: 1 });
+    assert_eq!(keypad.key_at(Point { x: 1, y: 0 }), Some('b'));
+    assert_eq!(keypad.key_at(Point { x: 1, y: 2 }), None);
+    assert_eq!(keypad.key_at(Point { x: 2, y: 0 }), None);
+    assert_eq!(follow_instructions("UL\nDD\nRDDLD", &keypad), "aac");
+
+    assert_eq!(Keypad::parse("123\n456", '7').unwrap_err(), "no key 7");
+    assert_eq!(Keypad::parse("121", '2').unwrap_err(), "duplicate key: 1");
+}
+
+#[test]
+fn finger_path() {
+    let keypad = Keypad::parse(WTF_LAYOUT, '5').unwrap();
+    let (code, path) = type_code("ULL\nRRDDD".lines(), &keypad);
+    let keys = path.iter().map(|&p| keypad.get_key_at(p)).collect::<String>();
+    assert_eq!(code, "5D");
+    assert_eq!(keys, "567BD");
+}
+
+#[test]
+fn inverse_solver() {
+    let keypad = Keypad::parse(WTF_LAYOUT, '5').unwrap();
+    let lines  = shortest_instructions("5DB3", &keypad).unwrap();
+    assert_eq!(lines, vec!["", "RDRD", "U", "UU"]);
+
+    let simple = Keypad::parse(SIMPLE_LAYOUT, '5').unwrap();
+    for &(keypad, code) in &[(&keypad, "1D9A5C"), (&simple, "1985193")] {
+        let lines = shortest_instructions(code, keypad).unwrap();
+        let (typed, _) = type_code(lines.iter().map(String::as_str), keypad);
+        assert_eq!(typed, code);
+    }
+
+    assert_eq!(shortest_instructions("12Z", &keypad).unwrap_err(), "no key Z");
+    let islands = Keypad::parse("1 2", '1').unwrap();
+    assert_eq!(shortest_instructions("12", &islands).unwrap_err(),
+               "key 2 is unreachable");
+}
+
 // }}}
@@ -25,6 +25,17 @@ struct Triangle {
     sides: [i32; 3],
 }
 
+/// The kinds of triangles, according to their sides.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Kind {
+    /// All sides are equal.
+    Equilateral,
+    /// Two sides are equal.
+    Isosceles,
+    /// All sides are different.
+    Scalene,
+}
+
 impl FromStr for Triangle {
     type Err = String;
 
@@ -33,7 +44,7 @@ impl FromStr for Triangle {
     /// The input string should contains three integers separated by whitespaces.
     fn from_str(s: &str) -> Result<Triangle, String> {
         let err_msg = "cannot build triangle with these sides";
-        let sides = parse_line(s);
+        let sides = table::parse_row::<i32>(s, 3)?;
         Triangle::new(&[sides[0], sides[1], sides[2]])
                  .ok_or_else(|| err_msg.to_string())
     }
@@ -54,24 +65,130 @@ impl Triangle {
         }
         None
     }
+
+    /// Classifies the triangle according to its sides.
+    fn kind(&self) -> Kind {
+        let [a, b, c] = self.sides;
+        if a == b && b == c {
+            Kind::Equilateral
+        } else if a == b || b == c || a == c {
+            Kind::Isosceles
+        } else {
+            Kind::Scalene
+        }
+    }
+
+    /// Tests if the triangle has a right angle.
+    fn is_right(&self) -> bool {
+        let mut sides = self.sides.iter().map(|&side| i64::from(side))
+                                         .collect::<Vec<_>>();
+        sides.sort();
+        sides[0]*sides[0] + sides[1]*sides[1] == sides[2]*sides[2]
+    }
 }
 
 // }}}
-// {{{ Parsing
+// {{{ Table
 
-/// Parses a single line of input and returns a vector of three integers.
-///
-/// The input line must contains **exactly** three integers, separated by
-/// spaces.
-fn parse_line(line : &str) -> Vec<i32> {
-    let sides: Vec<i32> = line.split(' ')
-                          .filter(|token| !token.is_empty())
-                          .map(   |side|   side.parse().unwrap())
-                          .collect();
-    assert!(sides.len() == 3, "invalid number of columns");
-    sides
+/// Reading tables of values, with one row per line.
+pub mod table {
+    use std::fmt;
+    use std::str::FromStr;
+
+    /// A malformed row, or block of rows.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub struct ParseError {
+        line:   usize,
+        reason: String,
+    }
+
+    impl ParseError {
+        fn new(line: usize, reason: String) -> Self {
+            ParseError { line, reason }
+        }
+
+        /// Returns the line (starting from 1) where the error occured.
+        pub fn line(&self) -> usize {
+            self.line
+        }
+    }
+
+    impl fmt::Display for ParseError {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "line {}: {}", self.line, self.reason)
+        }
+    }
+
+    /// Parses a row made of **exactly** `width` values, separated by
+    /// whitespaces.
+    pub fn parse_row<T: FromStr>(line: &str, width: usize)
+        -> Result<Vec<T>, String> {
+        let values = line.split_whitespace().map(|token| {
+            token.parse().map_err(|_| format!("invalid value: {}", token))
+        }).collect::<Result<Vec<T>, _>>()?;
+        if values.len() != width {
+            return Err(format!("expected {} columns, got {}",
+                               width, values.len()));
+        }
+        Ok(values)
+    }
+
+    /// Parses the rows of `width` values of a table.
+    ///
+    /// Blank lines are skipped.
+    pub fn rows<T: FromStr>(input: &str, width: usize)
+        -> Result<Vec<Vec<T>>, ParseError> {
+        numbered_rows(input, width)
+            .map(|rows| rows.into_iter().map(|(_, row)| row).collect())
+    }
+
+    /// Parses a table made of blocks of `height` rows of `width` values, and
+    /// returns the columns of each block: `width` records of `height`
+    /// values per block.
+    ///
+    /// Blank lines are skipped.
+    pub fn block_columns<T: FromStr + Clone>(input: &str, height: usize,
+                                             width: usize)
+        -> Result<Vec<Vec<T>>, ParseError> {
+        assert!(height > 0, "empty blocks");
+        let rows = numbered_rows::<T>(input, width)?;
+        let mut records = Vec::with_capacity(rows.len() / height * width);
+
+        for block in rows.chunks(height) {
+            if block.len() != height {
+                return Err(ParseError::new(block[0].0, format!(
+                    "incomplete block: {} rows out of {}",
+                    block.len(), height
+                )));
+            }
+            for column in 0..width {
+                records.push(block.iter().map(|(_, row)| {
+                    row[column].clone()
+                }).collect());
+            }
+        }
+        Ok(records)
+    }
+
+    /// Parses the rows of `width` values of a table, with their line number.
+    fn numbered_rows<T: FromStr>(input: &str, width: usize)
+        -> Result<Vec<(usize, Vec<T>)>, ParseError> {
+        input.lines().enumerate()
+             .filter(|&(_, line)| !line.trim().is_empty())
+             .map(|(idx, line)| {
+                 parse_row(line, width).map(|row| (idx + 1, row))
+                                       .map_err(|err| ParseError::new(idx + 1,
+                                                                      err))
+             })
+             .collect()
+    }
 }
 
+// }}}
+// {{{ Parsing
+
+use table::ParseError;
+
 /// Parses a list of triangles from a file organized by line.
 ///
 ///The input is expected to contains one triangle per line.
@@ -82,8 +199,8 @@ fn parse_line(line : &str) -> Vec<i32> {
 /// triangle2a    triangle2b    triangle2c
 /// triangle3a    triangle3b    triangle3c
 /// ```
-fn parse_by_line(input: &str) -> Vec<Triangle> {
-    input.lines().filter_map(|s| s.parse().ok()).collect()
+fn parse_by_line(input: &str) -> Result<Vec<Triangle>, ParseError> {
+    table::rows(input, 3).map(to_triangles)
 }
 
 /// Parses a list of triangles from a file organized by block.
@@ -96,24 +213,15 @@ fn parse_by_line(input: &str) -> Vec<Triangle> {
 /// triangle1b    triangle3b    triangle3b
 /// triangle1c    triangle3c    triangle3c
 /// ```
-fn parse_by_block(input: &str) -> Vec<Triangle> {
-    let mut res   = Vec::new();
-    let mut lines = 0;
-    // Staging buffer for triangles in construction.
-    let mut sides = [[0, 0, 0]; 3];
-
-    for (side_idx, line) in input.lines().enumerate() {
-        for (triangle_idx, val) in parse_line(line).iter().enumerate() {
-            sides[triangle_idx][side_idx % 3] = *val;
-        }
-        // If we got three sides, the triangles are complete.
-        if side_idx % 3 == 2 {
-            res.extend(&sides);
-        }
-        lines += 1;
-    }
-    assert!(lines % 3 == 0, "the last block is incomplete");
-    res.iter().filter_map(|sides| Triangle::new(sides)).collect()
+fn parse_by_block(input: &str) -> Result<Vec<Triangle>, ParseError> {
+    table::block_columns(input, 3, 3).map(to_triangles)
+}
+
+/// Keeps the valid triangles among the records of three sides.
+fn to_triangles(records: Vec<Vec<i32>>) -> Vec<Triangle> {
+    records.iter().filter_map(|sides| {
+        Triangle::new(&[sides[0], sides[1], sides[2]])
+    }).collect()
 }
 
 // }}}
@@ -123,10 +231,19 @@ fn main() {
     let mut input = String::new();
 
     file.read_to_string(&mut input).unwrap();
+    let by_line  = parse_by_line(&input).unwrap();
+    let by_block = parse_by_block(&input).unwrap();
     println!("Grouped by lines, there are {} valid triangles.",
-             parse_by_line(&input).len());
+             by_line.len());
     println!("Grouped by columns, there are {} valid triangles.",
-             parse_by_block(&input).len());
+             by_block.len());
+
+    let count = |kind| by_line.iter().filter(|t| t.kind() == kind).count();
+    println!("Grouped by lines, {} are equilateral, {} isosceles, {} scalene \
+              and {} right-angled.",
+             count(Kind::Equilateral), count(Kind::Isosceles),
+             count(Kind::Scalene), by_line.iter().filter(|t| t.is_right())
+                                              .count());
 }
 
 // {{{ Tests
@@ -136,4 +253,62 @@ fn examples_part1() {
     assert!(Triangle::new(&[5, 10, 25]).is_none());
 }
 
+#[test]
+fn layouts() {
+    let input = "101 301 501
+102 302 502
+
+103 303 503
+201 401 601
+202 402 602
+203 403 603
+";
+    let rows = table::rows::<i32>(input, 3).unwrap();
+    assert_eq!(rows[2], vec![103, 303, 503]);
+    assert_eq!(table::block_columns::<i32>(input, 3, 3).unwrap(), vec![
+        vec![101, 102, 103], vec![301, 302, 303], vec![501, 502, 503],
+        vec![201, 202, 203], vec![401, 402, 403], vec![601, 602, 603],
+    ]);
+    assert_eq!(parse_by_block(input).unwrap().len(), 6);
+
+    // Any block size and width.
+    assert_eq!(table::block_columns::<u8>("1 2\n3 4\n5 6\n7 8", 2, 2),
+               Ok(vec![vec![1, 3], vec![2, 4], vec![5, 7], vec![6, 8]]));
+    assert_eq!(table::block_columns::<u8>("1 2 3 4", 1, 4),
+               Ok(vec![vec![1], vec![2], vec![3], vec![4]]));
+}
+
+#[test]
+fn malformed_tables() {
+    let errors = [
+        (parse_by_line("3 4 5\n1 2\n"),
+         "line 2: expected 3 columns, got 2"),
+        (parse_by_line("3 4 5\n1 x 3\n"),
+         "line 2: invalid value: x"),
+        (parse_by_block("3 4 5\n1 2 3 4\n"),
+         "line 2: expected 3 columns, got 4"),
+        (parse_by_block("1 2 3\n1 2 3\n1 2 3\n\n4 5 6\n"),
+         "line 5: incomplete block: 1 rows out of 3"),
+    ];
+    for &(ref result, expected) in errors.iter() {
+        assert_eq!(result.as_ref().unwrap_err().to_string(), expected);
+    }
+    assert_eq!(parse_by_line("1 2").unwrap_err().line(), 1);
+    assert!("1 2 x".parse::<Triangle>().is_err());
+    assert!("3 4 5".parse::<Triangle>().is_ok());
+}
+
+#[test]
+fn classification() {
+    let triangle = |a, b, c| Triangle::new(&[a, b, c]).unwrap();
+
+    assert_eq!(triangle(3, 3, 3).kind(), Kind::Equilateral);
+    assert_eq!(triangle(3, 5, 3).kind(), Kind::Isosceles);
+    assert_eq!(triangle(3, 4, 5).kind(), Kind::Scalene);
+    assert!( triangle(5, 3, 4).is_right());
+    assert!( triangle(13, 12, 5).is_right());
+    assert!(!triangle(3, 3, 3).is_right());
+    assert!(!triangle(4, 5, 6).is_right());
+}
+
 // }}}
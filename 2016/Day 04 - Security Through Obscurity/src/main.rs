@@ -12,48 +12,143 @@
 // }}}
 // {{{ Crates
 
-use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::env;
 use std::fs::File;
 use std::io::Read;
 use std::str::FromStr;
 
+extern crate regex;
+use regex::Regex;
+
+// }}}
+// {{{ Cipher
+
+/// Shift ciphers, and the tools to break them.
+pub mod cipher {
+    use std::cmp::Ordering;
+    use std::collections::HashMap;
+
+    /// Frequencies of the letters in English texts, in percent.
+    const ENGLISH: [f64; 26] = [
+        8.167, 1.492, 2.782, 4.253, 12.702, 2.228, 2.015, 6.094, 6.966, 0.153,
+        0.772, 4.025, 2.406, 6.749,  7.507, 1.929, 0.095, 5.987, 6.327, 9.056,
+        2.758, 0.978, 2.360, 0.150,  1.974, 0.074,
+    ];
+
+    /// Shifts the ASCII letters of `text` by `shift` positions (ROT-N).
+    ///
+    /// The case is preserved, and other characters are left untouched.
+    pub fn rot(text: &str, shift: i32) -> String {
+        let shift = shift.rem_euclid(26) as u8;
+        text.chars().map(|c| {
+            let base = if c.is_ascii_lowercase() {
+                b'a'
+            } else if c.is_ascii_uppercase() {
+                b'A'
+            } else {
+                return c;
+            };
+            ((c as u8 - base + shift) % 26 + base) as char
+        }).collect()
+    }
+
+    /// Encodes a string using the Caesar cipher.
+    pub fn encode(plain: &str, key: i32) -> String {
+        rot(plain, key)
+    }
+
+    /// Decodes a string encoded using the Caesar cipher.
+    pub fn decode(cipher: &str, key: i32) -> String {
+        rot(cipher, -key)
+    }
+
+    /// Finds the key (in `[0, 26)`) that decodes `cipher` into the text
+    /// looking the most like English, using a chi-squared test on the letter
+    /// frequencies.
+    ///
+    /// The longer the text, the more reliable the result.
+    pub fn recover_key(cipher: &str) -> i32 {
+        let mut counts = [0u32; 26];
+        for c in cipher.chars().filter(char::is_ascii_alphabetic) {
+            counts[(c.to_ascii_lowercase() as u8 - b'a') as usize] += 1;
+        }
+        let total = f64::from(counts.iter().sum::<u32>());
+
+        let score = |key: i32| -> f64 {
+            (0..26).map(|plain| {
+                let observed = f64::from(counts[(plain + key as usize) % 26]);
+                let expected = total * ENGLISH[plain] / 100.;
+                (observed - expected).powi(2) / expected
+            }).sum()
+        };
+        (0..26).min_by(|&a, &b| {
+            score(a).partial_cmp(&score(b)).unwrap_or(Ordering::Equal)
+        }).expect("26 keys")
+    }
+
+    /// Computes the checksum of an encrypted name: its `len` most common
+    /// letters, ties being broken by alphabetical order.
+    pub fn checksum(name: &str, len: usize) -> String {
+        // Compute letter frequencies.
+        let mut ch_freq = HashMap::new();
+        for c in name.chars().filter(|c| *c != '-') {
+            *ch_freq.entry(c).or_insert(0) += 1;
+        }
+        // Sort by:
+        // - descending frequency.
+        // - ascending alphabetical order if the frequencies are equal.
+        let mut sorted_chars : Vec<(char, i32)> = ch_freq.into_iter().collect();
+        sorted_chars.sort_by(|&(ch1, cnt1), &(ch2, cnt2)| {
+            match cnt2.cmp(&cnt1) {
+                Ordering::Equal => ch1.cmp(&ch2),
+                res             => res,
+            }
+        });
+        sorted_chars.iter().take(len).map(|&(ch, _)| ch).collect()
+    }
+}
+
 // }}}
 // {{{ Room
 
+/// Length of the checksum of a room.
+const CHECKSUM_LEN: usize = 5;
+
 /// Represents a room, with its name and a sector ID.
+///
+/// The sector ID may be missing: the name is then decrypted by frequency
+/// analysis.
 #[derive(Debug)]
 struct Room {
     name: String,
-    sector: i32,
+    sector: Option<i32>,
 }
 
 impl FromStr for Room {
     type Err = String;
 
     /// Try to build a Room from a string formatted as "name-sector[checksum]"
+    /// (or "name-[checksum]" if the sector ID is unknown).
     ///
     /// The parsing will fail if the name doesn't match the checksum.
     fn from_str(s: &str) -> Result<Room, String> {
-        // Extract the encrypted name
-        // Extract until we reach a digit (the start of the sector ID).
-        let name : String = s.chars().take_while(|c| !c.is_digit(10)).collect();
-        // Extract the sector ID
-        // Skip the name and extract until '[' (start of the checksum).
-        let mut chars = s.chars();
-        let sector : i32 = chars.by_ref()
-                                .skip(name.len())
-                                .take_while(|c| *c != '[')
-                                .collect::<String>().parse().unwrap();
-        // Extract the checksum
-        // From the current position, extract until ']' (end of the checksum).
-        let checksum : String = chars.by_ref()
-                                     .take_while(|c| c.is_alphabetic())
-                                     .collect();
-        // Ensure that we are at the end of string.
-        assert!(chars.next() == None);
+        // Split the checksum, between square brackets, from the rest.
+        let (head, checksum) = s.split_once('[')
+                                .ok_or_else(|| "missing checksum".to_owned())?;
+        let checksum = checksum.strip_suffix(']')
+                               .ok_or("unterminated checksum")?;
+        // The sector ID is made of the trailing digits, if any.
+        let name   = head.trim_end_matches(|c: char| c.is_ascii_digit());
+        let sector = &head[name.len()..];
+        let sector = if sector.is_empty() {
+            None
+        } else {
+            Some(sector.parse().map_err(|_| {
+                format!("invalid sector ID: {}", sector)
+            })?)
+        };
         // Check if the room is legit.
-        Room::new(&name, sector, &checksum)
+        Room::new(name, sector, checksum)
              .ok_or_else(|| "the room is a decoy".into())
     }
 }
@@ -63,53 +158,64 @@ impl Room {
     ///
     /// The checksum is verified, if it doesn't match the encrypted name the
     /// initialization fails.
-    fn new(name: &str, sector: i32, checksum: &str) -> Option<Room> {
-        // Compute letter frequencies.
-        let mut ch_freq = HashMap::new();
-        for c in name.chars().filter(|c| *c != '-') {
-            *ch_freq.entry(c).or_insert(0) += 1;
-        }
-        // Sort by:
-        // - descending frequency.
-        // - ascending alphabetical order if the frequencies are equal.
-        let mut sorted_chars : Vec<(char, i32)> = ch_freq.into_iter().collect();
-        sorted_chars.sort_by(|&(ch1, cnt1), &(ch2, cnt2)| {
-            match cnt2.cmp(&cnt1) {
-                Ordering::Equal => ch1.cmp(&ch2),
-                res             => res,
-            }
-        });
-        // Compute and verify the checksum.
-        let current_checksum = sorted_chars.iter().take(checksum.len())
-                                           .map(|&(ch, _)| ch)
-                                           .collect::<String>();
-        if current_checksum == checksum {
+    fn new(name: &str, sector: Option<i32>, checksum: &str) -> Option<Room> {
+        if cipher::checksum(name, checksum.len()) == checksum {
+            let name = name.trim_end_matches('-');
             return Some(
-                Room { name: decode_caesar_cipher(name, sector), sector: sector }
+                Room {
+                    name: match sector {
+                        Some(sector) => decode_caesar_cipher(name, sector),
+                        None         => crack_room_name(name).1,
+                    },
+                    sector,
+                }
             )
         }
         None
     }
+
+    /// Encrypts a room name (words separated by spaces) into a legit room
+    /// description, with its checksum.
+    fn encrypt(name: &str, sector: i32) -> String {
+        let encrypted = cipher::rot(&name.replace(" ", "-"), -sector);
+        let checksum  = cipher::checksum(&encrypted, CHECKSUM_LEN);
+        format!("{}-{}[{}]", encrypted, sector, checksum)
+    }
 }
 
-/// Decodes a string encoded using the Caesar cipher.
+/// Decodes a room name encoded using the Caesar cipher.
+///
+/// Room names are decrypted by rotating the letters forward.
 fn decode_caesar_cipher(name : &str, key: i32) -> String {
-    let shift = (key % 26) as u8;
-    // XXX: This is safe because the input string only contains ASCII characters.
-    name.replace("-", " ").chars().map(|c| {
-        if c.is_alphabetic() {
-            let cipher = c as u8 - b'a';
-            let plain  = (cipher + shift) % 26;
-            (plain + b'a') as char
-        } else {
-            c
-        }
-    }).collect()
+    cipher::rot(&name.replace("-", " "), key)
+}
+
+/// Decodes a room name whose sector ID is unknown, with the key recovered by
+/// frequency analysis.
+///
+/// Returns the sector ID modulo 26 and the decoded name.
+fn crack_room_name(name: &str) -> (i32, String) {
+    let sector = (26 - cipher::recover_key(name)) % 26;
+    (sector, decode_caesar_cipher(name, sector))
+}
+
+/// Returns the rooms whose decoded name matches `pattern`.
+fn search<'a>(rooms: &'a [Room], pattern: &'a Regex)
+    -> impl Iterator<Item=&'a Room> + 'a {
+    rooms.iter().filter(move |room| pattern.is_match(&room.name))
 }
 
 // }}}
 
 fn main() {
+    // `--encrypt NAME SECTOR` prints the description of a legit room.
+    let args = env::args().collect::<Vec<_>>();
+    if args.len() == 4 && args[1] == "--encrypt" {
+        let sector = args[3].parse().expect("invalid sector ID");
+        println!("{}", Room::encrypt(&args[2], sector));
+        return;
+    }
+
     let mut file  = File::open("input.txt").expect("cannot open input.txt");
     let mut input = String::new();
 
@@ -118,10 +224,20 @@ fn main() {
     let rooms = input.lines().filter_map(|s| s.parse::<Room>().ok())
                              .collect::<Vec<_>>();
     println!("The sum of the sector IDs of the real room is {}.",
-             rooms.iter().fold(0, |acc, room| acc + room.sector));
-    println!("North Pole objects are stored in the sector {}.",
-             rooms.iter().find(|room| room.name.contains("northpole"))
-                  .unwrap().sector);
+             rooms.iter().filter_map(|room| room.sector).sum::<i32>());
+    let pattern = Regex::new(r"\bnorth ?pole\b").unwrap();
+    for room in search(&rooms, &pattern) {
+        match room.sector {
+            Some(sector) => {
+                println!("North Pole objects are stored in the sector {} \
+                          ({}).", sector, room.name);
+            },
+            None => {
+                println!("North Pole objects are stored in an unknown \
+                          sector ({}).", room.name);
+            },
+        }
+    }
 }
 
 // {{{ Tests
@@ -140,4 +256,66 @@ fn examples_part2() {
                "very encrypted name");
 }
 
+#[test]
+fn caesar_cipher() {
+    use cipher::{decode, encode, rot};
+
+    assert_eq!(rot("Hello, World!", 13), "Uryyb, Jbeyq!");
+    assert_eq!(rot("Uryyb, Jbeyq!", 13), "Hello, World!");
+    assert_eq!(encode("xyz-ABC", 3), "abc-DEF");
+    assert_eq!(decode("abc-DEF", 3 + 26*4), "xyz-ABC");
+    assert_eq!(decode(&encode("attack at dawn", -7), -7), "attack at dawn");
+}
+
+#[test]
+fn key_recovery() {
+    let plain = "It is a truth universally acknowledged, that a single man in \
+                 possession of a good fortune, must be in want of a wife.";
+    for key in 0..26 {
+        let cipher = cipher::encode(plain, key);
+        assert_eq!(cipher::recover_key(&cipher), key);
+    }
+
+    assert_eq!(crack_room_name("qzmt-zixmtkozy-ivhz"),
+               (343 % 26, "very encrypted name".to_owned()));
+}
+
+#[test]
+fn unknown_sectors() {
+    let description = Room::encrypt("northpole object storage", 267);
+    let unknown     = description.replace("-267[", "-[");
+    let room = unknown.parse::<Room>().unwrap();
+    assert_eq!(room.sector, None);
+    assert_eq!(room.name, "northpole object storage");
+
+    assert_eq!("abc-12".parse::<Room>().unwrap_err(), "missing checksum");
+    assert_eq!("abc-12[abc".parse::<Room>().unwrap_err(),
+               "unterminated checksum");
+    assert_eq!("abc-99999999999[abcxy]".parse::<Room>().unwrap_err(),
+               "invalid sector ID: 99999999999");
+}
+
+#[test]
+fn synthesized_rooms() {
+    assert_eq!(cipher::checksum("aaaaa-bbb-z-y-x", 5), "abxyz");
+    assert_eq!(cipher::checksum("not-a-real-room", 3), "oar");
+
+    let names = ["northpole object storage", "very encrypted name", "a b c"];
+    let rooms = names.iter().enumerate().map(|(i, name)| {
+        let description = Room::encrypt(name, 100 * i as i32 + 42);
+        description.parse::<Room>().unwrap()
+    }).collect::<Vec<_>>();
+    for (room, name) in rooms.iter().zip(names.iter()) {
+        assert_eq!(room.name, *name);
+    }
+    assert_eq!(rooms[1].sector, Some(142));
+    assert_eq!(Room::encrypt("very encrypted name", 343),
+               "qzmt-zixmtkozy-ivhz-343[zimth]");
+
+    let pattern = Regex::new(r"^(northpole|very) ").unwrap();
+    let sectors = search(&rooms, &pattern).filter_map(|room| room.sector)
+                                          .collect::<Vec<_>>();
+    assert_eq!(sectors, vec![42, 142]);
+}
+
 // }}}
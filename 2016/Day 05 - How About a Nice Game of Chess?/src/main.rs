@@ -16,13 +16,19 @@ use std::fs::File;
 use std::io::BufRead;
 use std::io::BufReader;
 use std::io::{self, Write};
+use std::fmt::Write as FmtWrite;
+use std::thread;
 
-extern crate crypto;
-use crypto::md5::Md5;
-use crypto::digest::Digest;
+extern crate md5;
 
 // }}}
 
+/// Number of indexes hashed by each thread, per batch.
+const BATCH_SIZE: u64 = 50_000;
+
+/// Notified with the partially decoded password.
+type Callback<'a> = &'a mut dyn FnMut(&[u8]);
+
 /// Updates the cinematic "decrypting" animation.
 fn update_decrypting_animation(door: &str,  password : &[u8]) {
     print!("\rThe password of the {} is {}",
@@ -30,53 +36,89 @@ fn update_decrypting_animation(door: &str,  password : &[u8]) {
     io::stdout().flush().unwrap();
 }
 
-/// Computes the password from the door ID.
-fn decode_password(door: &str, door_id : &[u8], is_in_order: bool) -> String {
-    let hexdigit = [b'0', b'1', b'2', b'3', b'4', b'5', b'6', b'7',
-                    b'8', b'9', b'a', b'b', b'c', b'd', b'e', b'f'];
-    let mut password = [b'_';  8];
-    let mut idx = 0;
-    let mut md5 = Md5::new();
-
-    update_decrypting_animation(door, &password);
-    let _ : Vec<char> = (0u64..).filter_map(|i| {
-        let mut hash = [0; 16]; // A MD5 digest is 128 bits (i.e 16 bytes).
+/// Returns, in index order, the hashes that start with five zeroes for the
+/// indexes in `[start, start + count)`.
+fn find_hashes(prefix: &md5::Md5, start: u64, count: u64) -> Vec<[u8; 16]> {
+    let mut digits = String::with_capacity(20);
 
-        md5.input(door_id);
-        md5.input(i.to_string().as_bytes());
-        md5.result(&mut hash);
-        md5.reset();
+    (start..start + count).filter_map(|i| {
+        let mut md5 = *prefix;
+        digits.clear();
+        write!(digits, "{}", i).unwrap();
+        md5.update(digits.as_bytes());
+        let hash = md5.finalize();
 
         // As one byte == two hex character, we have to tests the 2.5 bytes (two
         // whole bytes + the 4 high bits of the third byte) to see if the digest
         // starts with 5 zeroes.
-        if (hash[0] | hash[1] | (hash[2] >> 4)) != 0 {
-            return None
-        }
-        if is_in_order {
-            // When the letters are already ordered the letter is the 6th
-            // character of the hash
-            password[idx] = hexdigit[(hash[2] & 0x0f) as usize];
-            idx += 1;
-            update_decrypting_animation(door, &password);
-            Some('_')
+        if (hash[0] | hash[1] | (hash[2] >> 4)) == 0 {
+            Some(hash)
         } else {
-            // When the letters are not ordered:
-            // - the index is the 6th character of the hash
-            // - the letter is the 7th character of the hash
-            let pos = hash[2] & 0x0f;
-            let ch  = hexdigit[(hash[3] >> 4) as usize];
-
-            if pos < 8 && password[pos as usize] == b'_' {
-                password[pos as usize] = ch;
-                update_decrypting_animation(door, &password);
-                Some('_')
+            None
+        }
+    }).collect()
+}
+
+/// Computes the password from the door ID.
+///
+/// The indexes are hashed by batches, split between `threads` threads, but the
+/// interesting hashes are processed in index order so the result doesn't
+/// depend on the scheduling. `on_update` is called every time a new character
+/// of the password is found.
+fn decode_password(door_id : &[u8], is_in_order: bool, threads: usize,
+                   mut on_update: Option<Callback>) -> String {
+    let hexdigit = [b'0', b'1', b'2', b'3', b'4', b'5', b'6', b'7',
+                    b'8', b'9', b'a', b'b', b'c', b'd', b'e', b'f'];
+    let mut password = [b'_';  8];
+    let mut found = 0;
+    // The door ID is the same for every index: hash it only once.
+    let mut prefix = md5::Md5::new();
+    prefix.update(door_id);
+
+    if let Some(ref mut callback) = on_update {
+        callback(&password);
+    }
+    let threads = threads.max(1) as u64;
+    let mut start = 0;
+    while found < password.len() {
+        // Hash a batch in parallel, each thread handling a contiguous range.
+        let hashes = thread::scope(|scope| {
+            let workers = (0..threads).map(|n| {
+                let prefix = &prefix;
+                let offset = start + n * BATCH_SIZE;
+                scope.spawn(move || find_hashes(prefix, offset, BATCH_SIZE))
+            }).collect::<Vec<_>>();
+            // Join in spawning order to keep the hashes sorted by index.
+            workers.into_iter()
+                   .flat_map(|worker| worker.join().unwrap())
+                   .collect::<Vec<_>>()
+        });
+        start += threads * BATCH_SIZE;
+
+        for hash in hashes {
+            if is_in_order {
+                // When the letters are already ordered the letter is the 6th
+                // character of the hash
+                password[found] = hexdigit[(hash[2] & 0x0f) as usize];
             } else {
-                None
+                // When the letters are not ordered:
+                // - the index is the 6th character of the hash
+                // - the letter is the 7th character of the hash
+                let pos = (hash[2] & 0x0f) as usize;
+                if pos >= password.len() || password[pos] != b'_' {
+                    continue;
+                }
+                password[pos] = hexdigit[(hash[3] >> 4) as usize];
+            }
+            found += 1;
+            if let Some(ref mut callback) = on_update {
+                callback(&password);
+            }
+            if found == password.len() {
+                break;
             }
         }
-    }).take(8).collect();
-    println!();
+    }
     String::from_utf8_lossy(&password).to_string()
 }
 
@@ -87,20 +129,36 @@ fn main() {
     // The Door ID is on the first line.
     BufReader::new(&file).read_line(&mut input).unwrap();
     let door_id = input.trim().as_bytes();
-    decode_password("first door",  door_id, true);
-    decode_password("second door", door_id, false);
+    let threads = thread::available_parallelism().map(|n| n.get())
+                                                 .unwrap_or(1);
+    for &(door, is_in_order) in &[("first door", true),
+                                  ("second door", false)] {
+        decode_password(door_id, is_in_order, threads,
+                        Some(&mut |password: &[u8]| {
+            update_decrypting_animation(door, password)
+        }));
+        println!();
+    }
 }
 
 // {{{ Tests
 
 #[test]
 fn examples_part1() {
-    assert_eq!(decode_password("abc".as_bytes(), true), "18f47a30");
+    assert_eq!(decode_password(b"abc", true, 1, None), "18f47a30");
+    assert_eq!(decode_password(b"abc", true, 3, None), "18f47a30");
 }
 
 #[test]
 fn examples_part2() {
-    assert_eq!(decode_password("abc".as_bytes(), false), "05ace8e3");
+    let mut updates = vec![];
+
+    assert_eq!(decode_password(b"abc", false, 3, Some(&mut |password: &[u8]| {
+        updates.push(String::from_utf8_lossy(password).to_string())
+    })), "05ace8e3");
+    // Index 5017308 is skipped: its position (8) is out of the password.
+    assert_eq!(updates[..3], ["________", "_5______", "_5__e___"]);
+    assert_eq!(updates.len(), 9);
 }
 
 // }}}
@@ -12,71 +12,228 @@
 // }}}
 // {{{ Crates
 
-use std::collections::HashMap;
 use std::fs::File;
 use std::io::Read;
 
+// }}}
+// {{{ Decoder
+
+/// Error-correcting decoding of repeated messages.
+mod decoder {
+    use std::cmp::Ordering;
+    use std::collections::BTreeMap;
+    use std::error;
+    use std::fmt;
+
+    /// Reasons why a message can't be added to the decoder.
+    #[derive(Clone, Copy, Debug, PartialEq)]
+    pub enum Error {
+        /// The reliability isn't a positive finite number.
+        InvalidReliability(f64),
+    }
+
+    impl fmt::Display for Error {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match *self {
+                Error::InvalidReliability(reliability)
+                    => write!(f, "invalid reliability: {}", reliability),
+            }
+        }
+    }
+
+    impl error::Error for Error {}
+
+    /// The letters seen in a column, with their (weighted) frequencies.
+    #[derive(Clone, Debug)]
+    pub struct Column {
+        // Sorted from the most common letter to the least common one, ties
+        // being broken by alphabetical order.
+        ranking: Vec<(char, f64)>,
+        total:   f64,
+    }
+
+    impl Column {
+        fn new(counts: &BTreeMap<char, f64>) -> Self {
+            let mut ranking = counts.iter().map(|(&ch, &weight)| (ch, weight))
+                                           .collect::<Vec<_>>();
+            // Stable sort: equal weights stay in alphabetical order.
+            ranking.sort_by(|a, b| {
+                b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal)
+            });
+            let total = ranking.iter().map(|&(_, weight)| weight).sum();
+            Column { ranking, total }
+        }
+
+        /// Returns the letters, from the most common to the least common one.
+        pub fn ranking(&self) -> &[(char, f64)] {
+            &self.ranking
+        }
+
+        /// Returns the total weight of the messages covering this column.
+        pub fn total(&self) -> f64 {
+            self.total
+        }
+
+        /// Returns the share of the weight held by `ch`, in `[0, 1]`.
+        pub fn frequency(&self, ch: char) -> f64 {
+            self.weight(ch) / self.total
+        }
+
+        /// Returns how much `ch` stands out from the closest other letter, as
+        /// a share of the total weight (1 if `ch` is the only letter, 0 on a
+        /// tie).
+        pub fn margin(&self, ch: char) -> f64 {
+            let weight = self.weight(ch);
+            self.ranking.iter().filter(|&&(other, _)| other != ch)
+                .map(|&(_, other)| (weight - other).abs() / self.total)
+                .fold(1.0, f64::min)
+        }
+
+        fn weight(&self, ch: char) -> f64 {
+            self.ranking.iter().find(|&&(other, _)| other == ch)
+                               .map_or(0.0, |&(_, weight)| weight)
+        }
+    }
+
+    /// How to pick the letter of a column.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum Strategy {
+        /// The most common letter (simple repetition code).
+        MostCommon,
+        /// The least common letter (modified repetition code).
+        LeastCommon,
+        /// The letter at this rank, `Rank(0)` being the most common one.
+        Rank(usize),
+    }
+
+    impl Strategy {
+        /// Picks a letter among the ones of the column, if any fits.
+        ///
+        /// Ties are broken by alphabetical order.
+        pub fn select(self, column: &Column) -> Option<char> {
+            let ranking = column.ranking();
+            match self {
+                Strategy::MostCommon  => ranking.first().map(|&(ch, _)| ch),
+                Strategy::LeastCommon => ranking.last().map(|&(_, least)| {
+                    // The first one of the least common letters.
+                    ranking.iter().find(|&&(_, weight)| weight == least)
+                                  .map(|&(ch, _)| ch)
+                                  .expect("last letter")
+                }),
+                Strategy::Rank(rank)  => ranking.get(rank).map(|&(ch, _)| ch),
+            }
+        }
+    }
+
+    /// The letter picked for a column.
+    #[derive(Clone, Debug)]
+    pub struct Choice {
+        /// The letters seen in the column.
+        pub column:     Column,
+        /// The letter picked, if the strategy found one.
+        pub chosen:     Option<char>,
+        /// The margin of the picked letter (0 if none).
+        pub confidence: f64,
+    }
+
+    /// Recovers a message from its corrupted repetitions.
+    ///
+    /// The messages may have different lengths: a column only takes into
+    /// account the messages long enough to cover it.
+    #[derive(Debug, Default)]
+    pub struct Decoder {
+        columns: Vec<BTreeMap<char, f64>>,
+    }
+
+    impl Decoder {
+        /// Creates a decoder without any message.
+        pub fn new() -> Self {
+            Decoder { columns: Vec::new() }
+        }
+
+        /// Adds messages, all of them equally reliable.
+        pub fn with_messages(messages: &[&str]) -> Self {
+            let mut decoder = Decoder::new();
+            for message in messages {
+                decoder.add(message, 1.0).expect("valid reliability");
+            }
+            decoder
+        }
+
+        /// Adds a message, its letters weighing `reliability`.
+        ///
+        /// Fails if the reliability is zero, negative, infinite or NaN: every
+        /// column must have a positive total weight.
+        pub fn add(&mut self, message: &str, reliability: f64)
+            -> Result<(), Error> {
+            if !(reliability.is_finite() && reliability > 0.0) {
+                return Err(Error::InvalidReliability(reliability));
+            }
+            for (i, ch) in message.chars().enumerate() {
+                if i == self.columns.len() {
+                    self.columns.push(BTreeMap::new());
+                }
+                *self.columns[i].entry(ch).or_insert(0.0) += reliability;
+            }
+            Ok(())
+        }
+
+        /// Returns the letter frequencies of each column.
+        pub fn columns(&self) -> Vec<Column> {
+            self.columns.iter().map(Column::new).collect()
+        }
+
+        /// Picks a letter in each column according to `strategy`.
+        pub fn decode(&self, strategy: Strategy) -> Vec<Choice> {
+            self.decode_with(|column| strategy.select(column))
+        }
+
+        /// Picks a letter in each column with a custom selection.
+        pub fn decode_with<F>(&self, select: F) -> Vec<Choice>
+            where F: Fn(&Column) -> Option<char>
+        {
+            self.columns().into_iter().map(|column| {
+                let chosen     = select(&column);
+                let confidence = chosen.map_or(0.0, |ch| column.margin(ch));
+                Choice { column, chosen, confidence }
+            }).collect()
+        }
+    }
+
+    /// Returns the message made of the picked letters, `?` standing for the
+    /// columns without one.
+    pub fn message(choices: &[Choice]) -> String {
+        choices.iter().map(|choice| choice.chosen.unwrap_or('?')).collect()
+    }
+}
+
 // }}}
 // {{{ Consensus computation
 
+use decoder::{Decoder, Strategy};
+
 /// Computes the original message from a list of corrupted messages.
 ///
-/// XXX: every messages should have the same length.
-///
 /// For each letter, the chosen one is the most common letter among all the
 /// corrupted messages.
 fn compute_consensus_most_common(messages: &[&str]) -> String {
-    compute_consensus(messages, 0, |(cur_ch, cur_cnt), (ch, cnt)| {
-        if cnt > &cur_cnt {
-            (*ch, *cnt)
-        } else {
-            (cur_ch, cur_cnt)
-        }
-    })
+    compute_consensus(messages, Strategy::MostCommon)
 }
 
 /// Computes the original message from a list of corrupted messages.
 ///
-/// XXX: every messages should have the same length.
-///
 /// For each letter, the chosen one is the least common letter among all the
 /// corrupted messages.
 fn compute_consensus_least_common(messages: &[&str]) -> String {
-    compute_consensus(messages, std::i32::MAX, |(cur_ch, cur_cnt), (ch, cnt)| {
-        if cnt <= &cur_cnt {
-            (*ch, *cnt)
-        } else {
-            (cur_ch, cur_cnt)
-        }
-    })
+    compute_consensus(messages, Strategy::LeastCommon)
 }
 
 /// Computes the original message from a list of corrupted messages.
 ///
-/// XXX: every messages should have the same length.
-///
 /// For each letter, the best candidate is chosen among all the corrupted
-/// messages according to the function `select`.
-fn compute_consensus<F>(messages: &[&str], init: i32, select: F) -> String
-    where F: Fn((char, i32), (&char, &i32)) -> (char, i32)
-{
-    let msg_len = messages[0].len();
-    let mut message = String::with_capacity(msg_len);
-
-    let mut ch_freq = HashMap::new();
-    for ch_idx in 0..msg_len {
-        // Compute letter frequencies.
-        for message in messages {
-            let ch = message.as_bytes()[ch_idx] as char;
-            *ch_freq.entry(ch).or_insert(0) += 1;
-        }
-        // Find the letter that match the criterion.
-        let (ch, _) = ch_freq.iter().fold((' ', init), &select);
-        message.push(ch);
-
-        ch_freq.clear();
-    }
-    message
+/// messages according to `strategy`.
+fn compute_consensus(messages: &[&str], strategy: Strategy) -> String {
+    decoder::message(&Decoder::with_messages(messages).decode(strategy))
 }
 
 // }}}
@@ -92,6 +249,20 @@ fn main() {
              compute_consensus_most_common(&messages));
     println!("The error-corrected message (modified repetition code) is {:?}.",
              compute_consensus_least_common(&messages));
+
+    let choices = Decoder::with_messages(&messages).decode(Strategy::MostCommon);
+    let weakest = choices.iter().enumerate().min_by(|a, b| {
+        a.1.confidence.total_cmp(&b.1.confidence)
+    });
+    if let Some((column, choice)) = weakest {
+        let letter = choice.chosen.expect("most common letter");
+        println!("The least certain letter is the column {}: {:?} is in \
+                  {:.1}% of the {} messages, with a margin of {:.1}%.",
+                 column, letter, choice.column.frequency(letter) * 100.,
+                 choice.column.total(), choice.confidence * 100.);
+    }
+    println!("The runner-up letters give {:?}.",
+             compute_consensus(&messages, Strategy::Rank(1)));
 }
 
 // {{{ Tests
@@ -120,4 +291,71 @@ fn examples() {
     assert_eq!(compute_consensus_least_common(&messages), "advent");
 }
 
+#[test]
+fn distributions() {
+    let decoder  = Decoder::with_messages(&["abc", "abd", "acd", "bcd"]);
+    let columns  = decoder.columns();
+    assert_eq!(columns.len(), 3);
+    assert_eq!(columns[0].ranking(), &[('a', 3.), ('b', 1.)]);
+    assert_eq!(columns[1].ranking(), &[('b', 2.), ('c', 2.)]);
+    assert_eq!(columns[0].frequency('a'), 0.75);
+    assert_eq!(columns[0].margin('a'), 0.5);
+    assert_eq!(columns[1].margin('c'), 0.);
+
+    let choices = decoder.decode(Strategy::MostCommon);
+    assert_eq!(decoder::message(&choices), "abd");
+    let confidences = choices.iter().map(|c| c.confidence).collect::<Vec<_>>();
+    assert_eq!(confidences, vec![0.5, 0., 0.5]);
+
+    // Ties are broken by alphabetical order, whatever the strategy.
+    for _ in 0..10 {
+        let decoder = Decoder::with_messages(&["zyx", "xyz", "yzx"]);
+        assert_eq!(decoder::message(&decoder.decode(Strategy::MostCommon)),
+                   "xyx");
+        assert_eq!(decoder::message(&decoder.decode(Strategy::LeastCommon)),
+                   "xzz");
+    }
+}
+
+#[test]
+fn strategies() {
+    let decoder = Decoder::with_messages(&["aaa", "aab", "abc", "abc", "b"]);
+
+    let message = |strategy| decoder::message(&decoder.decode(strategy));
+    assert_eq!(message(Strategy::Rank(0)), "aac");
+    assert_eq!(message(Strategy::Rank(1)), "bba");
+    assert_eq!(message(Strategy::Rank(2)), "??b");
+    assert_eq!(message(Strategy::LeastCommon), "baa");
+
+    // Ragged messages: the last column only has four of them.
+    let columns = decoder.columns();
+    assert_eq!(columns.iter().map(|c| c.total()).collect::<Vec<_>>(),
+               vec![5., 4., 4.]);
+
+    // Weigh the messages by their reliability.
+    let mut decoder = Decoder::new();
+    decoder.add("abc", 0.25).unwrap();
+    decoder.add("abd", 0.25).unwrap();
+    decoder.add("xyz", 0.75).unwrap();
+    assert_eq!(decoder.add("abc", -1.0),
+               Err(decoder::Error::InvalidReliability(-1.0)));
+    assert_eq!(decoder.add("abc", 0.0),
+               Err(decoder::Error::InvalidReliability(0.0)));
+    assert_eq!(decoder.add("abc", f64::INFINITY),
+               Err(decoder::Error::InvalidReliability(f64::INFINITY)));
+    assert_eq!(decoder.add("abc", f64::NAN).unwrap_err().to_string(),
+               "invalid reliability: NaN");
+    let choices = decoder.decode(Strategy::MostCommon);
+    assert_eq!(decoder::message(&choices), "xyz");
+    assert_eq!(choices[0].confidence, 0.25 / 1.25);
+    assert_eq!(choices[0].column.ranking(), &[('x', 0.75), ('a', 0.5)]);
+
+    // Any selection: the first vowel, if any.
+    let choices = decoder.decode_with(|column| {
+        column.ranking().iter().map(|&(ch, _)| ch)
+                               .find(|&ch| "aeiouy".contains(ch))
+    });
+    assert_eq!(decoder::message(&choices), "ay?");
+}
+
 // }}}
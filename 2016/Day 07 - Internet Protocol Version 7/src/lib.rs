@@ -0,0 +1,388 @@
+//! IPv7 addresses: parsing, TLS/SSL support and generation.
+
+// {{{ Lints
+
+#![deny(missing_debug_implementations,
+        missing_copy_implementations,
+        missing_docs,
+        trivial_casts,
+        trivial_numeric_casts,
+        unstable_features,
+        unused_import_braces,
+        unused_qualifications
+)]
+#![cfg_attr(feature = "cargo-clippy", allow(doc_markdown))]
+
+// }}}
+// {{{ Crates
+
+use std::error;
+use std::fmt;
+use std::str::FromStr;
+
+// }}}
+// {{{ IPv7
+
+/// An IPv7 address.
+///
+/// An address is made of supernet sequences separated by hypernet
+/// sequences, the latter being enclosed in square brackets.
+#[derive(Debug)]
+pub struct IPv7 {
+    address:   Vec<u8>,
+    supernets: Vec<(usize, usize)>,
+    hypernets: Vec<(usize, usize)>,
+}
+
+/// Reasons why a string is not an IPv7 address.
+///
+/// Errors come with the offset where the problem was found.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    /// A bracket is closed without having been opened, or the reverse.
+    UnbalancedBracket(usize),
+    /// A hypernet sequence is opened inside another one.
+    NestedHypernet(usize),
+    /// A sequence is empty (including at the start or the end).
+    EmptySegment(usize),
+    /// The address has no hypernet sequence.
+    MissingHypernet,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ParseError::UnbalancedBracket(offset)
+                => write!(f, "unbalanced bracket at offset {}", offset),
+            ParseError::NestedHypernet(offset)
+                => write!(f, "nested hypernet at offset {}", offset),
+            ParseError::EmptySegment(offset)
+                => write!(f, "empty sequence at offset {}", offset),
+            ParseError::MissingHypernet
+                => write!(f, "no hypernet sequences"),
+        }
+    }
+}
+
+impl error::Error for ParseError {}
+
+impl FromStr for IPv7 {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<IPv7, ParseError> {
+        let mut s_net = Vec::new();
+        let mut h_net = Vec::new();
+        let mut begin = 0;      // Start of the current slice.
+        let mut opened = None;  // Offset of the opened bracket, if any.
+
+        for (end, b) in s.bytes().enumerate() {
+            if b == b'[' {
+                if opened.is_some() {
+                    return Err(ParseError::NestedHypernet(end));
+                }
+                opened = Some(end);
+                s_net.push(segment(begin, end)?);
+                begin = end + 1;
+            } else if b == b']' {
+                if opened.take().is_none() {
+                    return Err(ParseError::UnbalancedBracket(end));
+                }
+                h_net.push(segment(begin, end)?);
+                begin = end + 1;
+            }
+        }
+        if let Some(offset) = opened {
+            return Err(ParseError::UnbalancedBracket(offset));
+        }
+        // The address must end with a supernet.
+        s_net.push(segment(begin, s.len())?);
+
+        if h_net.is_empty() {
+            Err(ParseError::MissingHypernet)
+        } else {
+            Ok(IPv7 {
+                address:   s.to_owned().into_bytes(),
+                supernets: s_net,
+                hypernets: h_net
+            })
+        }
+    }
+}
+
+/// Returns the sequence `[begin, end)`, unless empty.
+fn segment(begin: usize, end: usize)
+    -> Result<(usize, usize), ParseError> {
+    if begin == end {
+        Err(ParseError::EmptySegment(begin))
+    } else {
+        Ok((begin, end))
+    }
+}
+
+// {{{ Verdicts
+
+/// A pattern found in an address.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Found {
+    /// Offset of the pattern in the address.
+    pub offset: usize,
+    /// The pattern itself.
+    pub text:   String,
+}
+
+/// Why an address supports TLS, or not.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum TlsVerdict {
+    /// There is an ABBA in a supernet, and none in the hypernets.
+    Supported(Found),
+    /// There is no ABBA at all.
+    NoAbba,
+    /// There is an ABBA in a hypernet.
+    AbbaInHypernet(Found),
+}
+
+/// Why an address supports SSL, or not.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SslVerdict {
+    /// An ABA in a supernet has its BAB in a hypernet.
+    Supported {
+        /// The ABA, in a supernet.
+        aba: Found,
+        /// The matching BAB, in a hypernet.
+        bab: Found,
+    },
+    /// There is no ABA in the supernets.
+    NoAba,
+    /// No ABA of the supernets has its BAB in a hypernet.
+    NoMatchingBab,
+}
+
+// }}}
+
+impl IPv7 {
+    /// Test if the IP supports TLS.
+    ///
+    /// An IP supports TLS if it has an ABBA in its supernets, and no ABBA
+    /// in its hypernet sequences.
+    pub fn has_tls_support(&self) -> bool {
+        matches!(self.explain_tls(), TlsVerdict::Supported(_))
+    }
+
+    /// Test if the IP supports SSL.
+    ///
+    /// An IP supports SSL if it has an ABA, anywhere in the supernet
+    /// sequences, and a corresponding BAB, anywhere in the hypernet
+    /// sequences.
+    pub fn has_ssl_support(&self) -> bool {
+        matches!(self.explain_ssl(), SslVerdict::Supported { .. })
+    }
+
+    /// Explains the TLS support, with the first ABBA deciding it.
+    pub fn explain_tls(&self) -> TlsVerdict {
+        if let Some(abba) = self.find(&self.hypernets, 4, is_abba).next() {
+            return TlsVerdict::AbbaInHypernet(abba);
+        }
+        self.find(&self.supernets, 4, is_abba).next()
+            .map_or(TlsVerdict::NoAbba, TlsVerdict::Supported)
+    }
+
+    /// Explains the SSL support, with the first ABA having a BAB.
+    pub fn explain_ssl(&self) -> SslVerdict {
+        let mut abas = self.find(&self.supernets, 3, is_aba).peekable();
+        if abas.peek().is_none() {
+            return SslVerdict::NoAba;
+        }
+        for aba in abas {
+            let bab = aba.text.as_bytes();
+            let bab = [bab[1], bab[0], bab[1]];
+            let found = self.find(&self.hypernets, 3, |candidate| {
+                candidate == bab
+            }).next();
+            if let Some(bab) = found {
+                return SslVerdict::Supported { aba, bab };
+            }
+        }
+        SslVerdict::NoMatchingBab
+    }
+
+    /// Finds the `len`-byte patterns matching `pred` in the sequences.
+    fn find<'a, F>(&'a self, sequences: &'a [(usize, usize)], len: usize,
+                   pred: F) -> impl Iterator<Item=Found> + 'a
+        where F: Fn(&[u8]) -> bool + 'a {
+        sequences.iter().flat_map(move |&(a, z)| {
+            self.address[a..z].windows(len).enumerate()
+                .filter(|&(_, window)| pred(window))
+                .map(move |(i, window)| Found {
+                    offset: a + i,
+                    text:   String::from_utf8_lossy(window).into_owned(),
+                })
+                .collect::<Vec<_>>()
+        })
+    }
+}
+
+impl fmt::Display for IPv7 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", String::from_utf8_lossy(&self.address))
+    }
+}
+
+// {{{ ABBA helpers
+
+/// Test if `s` is an ABBA (Autonomous Bridge Bypass Annotation).
+///
+/// An ABBA is any four-character sequence which consists of a pair of two
+/// different characters followed by the reverse of that pair, such as
+/// `xyyx` or `abba`.
+pub fn is_abba(s: &[u8]) -> bool {
+    s.len() == 4    &&
+    s[0]    == s[3] &&
+    s[1]    == s[2] &&
+    s[0]    != s[1]
+}
+
+/// Test if `s` contains an ABBA.
+pub fn has_abba(s: &[u8]) -> bool {
+    s.windows(4).any(is_abba)
+}
+
+// }}}
+// {{{ ABA/BAB helpers
+
+/// Test if `s` is an ABA (Area-Broadcast Accessor).
+///
+/// An ABA is any three-character sequence which consists of the same
+/// character twice with a different character between them, such as
+/// `xyx` or `aba`.
+pub fn is_aba(s: &[u8]) -> bool {
+    s.len() == 3    &&
+    s[0]    == s[2] &&
+    s[0]    != s[1]
+}
+
+/// Returns a list of ABAs contained in `s`.
+pub fn collect_aba(s: &[u8]) -> Vec<&[u8]> {
+    s.windows(3).filter(|candidate| is_aba(candidate)).collect()
+}
+
+/// Test if `s` contains an BAB matching the given ABA.
+pub fn has_matching_bab_for(s: &[u8], aba: &[u8]) -> bool {
+    assert!(is_aba(aba));
+    let bab = [ aba[1], aba[0], aba[1] ];
+    s.windows(3).any(|candidate| candidate == bab)
+}
+
+// }}}
+// {{{ Generator
+
+/// Letters used by the generated patterns (ABBA, ABA, BAB).
+const PATTERN_LETTERS: &[u8] = b"qrstuvwxyz";
+/// Letters used around the patterns, never forming a pattern.
+const FILLER_LETTERS:  &[u8] = b"abcdefghijklmnop";
+
+/// A generator of random addresses, with the requested TLS and SSL
+/// support.
+///
+/// Patterns and filler use distinct letters, and the filler never repeats
+/// one of the two previous letters, so no pattern appears by accident.
+#[derive(Clone, Copy, Debug)]
+pub struct Generator {
+    state: u64,
+}
+
+impl Generator {
+    /// Creates a generator; the same seed gives the same addresses.
+    pub fn new(seed: u64) -> Self {
+        Generator { state: seed }
+    }
+
+    /// Generates an address supporting TLS and SSL as specified.
+    pub fn generate(&mut self, tls: bool, ssl: bool) -> IPv7 {
+        let hypernets = self.below(3) + 1;
+        // Supernets at even indices, hypernets at odd ones.
+        let mut patterns = vec![Vec::new(); 2*hypernets + 1];
+        let supernet = 2 * self.below(hypernets + 1);
+        let hypernet = 2 * self.below(hypernets) + 1;
+
+        let (x, y) = self.pair();
+        let abba = vec![x, y, y, x];
+        match (tls, self.below(2)) {
+            (true, _)  => patterns[supernet].push(abba),
+            (false, 0) => (),
+            (false, _) => {
+                // Whether or not the supernets have one too.
+                if self.below(2) == 0 {
+                    patterns[supernet].push(abba.clone());
+                }
+                patterns[hypernet].push(abba);
+            },
+        }
+
+        let (x, y) = self.pair();
+        match (ssl, self.below(2)) {
+            (true, _)  => {
+                patterns[supernet].push(vec![x, y, x]);
+                patterns[hypernet].push(vec![y, x, y]);
+            },
+            (false, 0) => (),
+            (false, _) => {
+                // The ABA is in both, but that's not a BAB.
+                patterns[supernet].push(vec![x, y, x]);
+                patterns[hypernet].push(vec![x, y, x]);
+            },
+        }
+
+        let mut address = Vec::new();
+        for (i, patterns) in patterns.iter().enumerate() {
+            if i % 2 == 1 {
+                address.push(b'[');
+            }
+            let min = if patterns.is_empty() { 1 } else { 0 };
+            self.filler(&mut address, min);
+            for pattern in patterns {
+                address.extend_from_slice(pattern);
+                self.filler(&mut address, 1);
+            }
+            if i % 2 == 1 {
+                address.push(b']');
+            }
+        }
+        String::from_utf8(address).expect("ASCII address")
+                                  .parse().expect("valid address")
+    }
+
+    /// Appends between `min` and `min + 4` filler letters.
+    fn filler(&mut self, address: &mut Vec<u8>, min: usize) {
+        let len   = min + self.below(5);
+        let start = address.len();
+        while address.len() < start + len {
+            let letter = FILLER_LETTERS[self.below(FILLER_LETTERS.len())];
+            let from   = start.max(address.len().saturating_sub(2));
+            let recent = &address[from..];
+            if !recent.contains(&letter) {
+                address.push(letter);
+            }
+        }
+    }
+
+    /// Returns two distinct pattern letters.
+    fn pair(&mut self) -> (u8, u8) {
+        let x = self.below(PATTERN_LETTERS.len());
+        let y = (x + 1 + self.below(PATTERN_LETTERS.len() - 1))
+              % PATTERN_LETTERS.len();
+        (PATTERN_LETTERS[x], PATTERN_LETTERS[y])
+    }
+
+    /// Returns a number in `[0, bound)` (xorshift64*).
+    fn below(&mut self, bound: usize) -> usize {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        let value = self.state.wrapping_mul(0x2545_F491_4F6C_DD1D);
+        ((value >> 32) % bound as u64) as usize
+    }
+}
+
+// }}}
+
+// }}}
@@ -12,146 +12,41 @@
 // }}}
 // {{{ Crates
 
+use std::env;
 use std::fs::File;
 use std::io::Read;
-use std::str::FromStr;
 
-// }}}
-// {{{ IPv7
-
-/// An IPv7 address.
-#[cfg_attr(feature = "cargo-clippy", allow(doc_markdown))]
-#[derive(Debug)]
-struct IPv7 {
-    address:   Vec<u8>,
-    supernets: Vec<(usize, usize)>,
-    hypernets: Vec<(usize, usize)>,
-}
+extern crate internet_protocol_version_7 as ipv7;
 
-impl FromStr for IPv7 {
-    type Err = String;
-
-    fn from_str(s: &str) -> Result<IPv7, String> {
-        let mut s_net = Vec::new();
-        let mut h_net = Vec::new();
-        let mut begin = 0;     // Start of the current slice.
-        let mut end   = 0;     // End of the current slice.
-        let mut in_hypernet = false; // Are we in an hypernet sequence?
-
-        for b in s.as_bytes() {
-            end += 1;
-            if *b == b'[' {
-                assert_eq!(in_hypernet, false);
-                in_hypernet = true;
-                s_net.push((begin, end-1));
-                begin = end;
-            } else if *b == b']' {
-                assert_eq!(in_hypernet, true);
-                in_hypernet = false;
-                h_net.push((begin, end-1));
-                begin = end;
-            }
-        }
-        // Check for a trailing supernet.
-        if end - begin != 0 {
-            s_net.push((begin, end));
-        }
+use ipv7::{Generator, IPv7};
 
-        if s_net.is_empty() {
-            Err("no supernet sequences".to_owned())
-        } else if h_net.is_empty() {
-            Err("no hypernet sequences".to_owned())
-        } else {
-            Ok(IPv7 {
-                address:   s.to_owned().into_bytes(),
-                supernets: s_net,
-                hypernets: h_net
-            })
+fn main() {
+    // `--generate N` prints N random addresses, cycling through the four
+    // combinations of TLS and SSL support.
+    let args = env::args().collect::<Vec<_>>();
+    if args.len() == 3 && args[1] == "--generate" {
+        let count = args[2].parse::<usize>().expect("invalid count");
+        let mut generator = Generator::new(42);
+        for i in 0..count {
+            let (tls, ssl) = (i % 2 == 0, i % 4 < 2);
+            println!("{} (TLS: {}, SSL: {})",
+                     generator.generate(tls, ssl), tls, ssl);
         }
+        return;
     }
-}
-
-impl IPv7 {
-    /// Test if the IP supports TLS.
-    ///
-    /// An IP supports TLS if it has an ABBA in its supernets, and no ABBA in
-    /// its hypernet sequences.
-    fn has_tls_support(&self) -> bool {
-        self.supernets.iter().any(|&(a, z)| has_abba(&self.address[a..z])) &&
-       !self.hypernets.iter().any(|&(a, z)| has_abba(&self.address[a..z]))
-    }
-
-    /// Test if the IP supports SSL.
-    ///
-    /// An IP supports SSL if it has an ABA, anywhere in the supernet sequences,
-    /// and a corresponding BAB, anywhere in the hypernet sequences.
-    fn has_ssl_support(&self) -> bool {
-        let abas = self.supernets.iter().fold(Vec::new(), |mut acc, &(a, z)| {
-            acc.extend(collect_aba(&self.address[a..z]));
-            acc
-        });
-        abas.iter().any(|aba| {
-            self.hypernets.iter().any(|&(a, z)| {
-                has_matching_bab_for(&self.address[a..z], aba)
-            })
-        })
-    }
-}
-
-// {{{ ABBA helpers
-
-/// Test if `s` is an ABBA (Autonomous Bridge Bypass Annotation).
-///
-/// An ABBA is any four-character sequence which consists of a pair of two
-/// different characters followed by the reverse of that pair, such as xyyx or
-/// `abba`.
-pub fn is_abba(s: &[u8]) -> bool {
-    s.len() == 4    &&
-    s[0]    == s[3] &&
-    s[1]    == s[2] &&
-    s[0]    != s[1]
-}
-
-/// Test if `s` contains an ABBA.
-pub fn has_abba(s: &[u8]) -> bool {
-    s.windows(4).any(is_abba)
-}
-
-// }}}
-// {{{ ABA/BAB helpers
-
-/// Test if `s` is an ABA (Area-Broadcast Accessor).
-///
-/// An ABA is any three-character sequence which consists of the same character
-/// twice with a different character between them, such as `xyx` or `aba`.
-pub fn is_aba(s: &[u8]) -> bool {
-    s.len() == 3    &&
-    s[0]    == s[2] &&
-    s[0]    != s[1]
-}
 
-/// Returns a list of ABAs contained in `s`.
-pub fn collect_aba(s: &[u8]) -> Vec<&[u8]> {
-    s.windows(3).filter(|candidate| is_aba(candidate)).collect()
-}
-
-/// Test if `s` contains an BAB matching the given ABA.
-pub fn has_matching_bab_for(s: &[u8], aba: &[u8]) -> bool {
-    assert!(is_aba(aba));
-    let bab = [ aba[1], aba[0], aba[1] ];
-    s.windows(3).any(|candidate| candidate == bab)
-}
-
-// }}}
-// }}}
-
-fn main() {
     let mut file  = File::open("input.txt").expect("cannot open input.txt");
     let mut input = String::new();
 
     file.read_to_string(&mut input).unwrap();
-    let ips = input.lines()
-                   .filter_map(|ip| ip.parse::<IPv7>().ok())
+    let ips = input.lines().enumerate()
+                   .filter_map(|(i, ip)| match ip.parse::<IPv7>() {
+                       Ok(ip)   => Some(ip),
+                       Err(err) => {
+                           eprintln!("line {}: {}", i + 1, err);
+                           None
+                       },
+                   })
                    .collect::<Vec<_>>();
     println!("There are {} IPs with TLS support.",
              ips.iter().filter(|ip| ip.has_tls_support()).count());
@@ -181,4 +76,75 @@ fn examples_part2() {
     assert_eq!("zazbz[bzb]cdb".parse::<IPv7>().unwrap().has_ssl_support(), true);
 }
 
+#[test]
+fn invalid_addresses() {
+    use ipv7::ParseError;
+
+    let testcases = [
+        ("abba]mnop[qrst", ParseError::UnbalancedBracket(4)),
+        ("abba[mnop",      ParseError::UnbalancedBracket(4)),
+        ("ab[cd[ef]gh]ij", ParseError::NestedHypernet(5)),
+        ("abba[]qrst",     ParseError::EmptySegment(5)),
+        ("ab[cd][ef]gh",   ParseError::EmptySegment(6)),
+        ("[mnop]qrst",     ParseError::EmptySegment(0)),
+        ("abba[mnop]",     ParseError::EmptySegment(10)),
+        ("abba",           ParseError::MissingHypernet),
+    ];
+    for &(input, expected) in testcases.iter() {
+        assert_eq!(input.parse::<IPv7>().unwrap_err(), expected);
+    }
+}
+
+#[test]
+fn explanations() {
+    use ipv7::{Found, SslVerdict, TlsVerdict};
+
+    let found = |offset, text: &str| Found { offset, text: text.to_owned() };
+    let tls = |ip: &str| ip.parse::<IPv7>().unwrap().explain_tls();
+    let ssl = |ip: &str| ip.parse::<IPv7>().unwrap().explain_ssl();
+
+    assert_eq!(tls("abba[mnop]qrst"), TlsVerdict::Supported(found(0, "abba")));
+    assert_eq!(tls("abcd[bddb]xyyx"),
+               TlsVerdict::AbbaInHypernet(found(5, "bddb")));
+    assert_eq!(tls("aaaa[qwer]tyui"), TlsVerdict::NoAbba);
+    assert_eq!(tls("ioxxoj[asdfgh]zxcvbn"),
+               TlsVerdict::Supported(found(1, "oxxo")));
+
+    assert_eq!(ssl("aba[bab]xyz"), SslVerdict::Supported {
+        aba: found(0, "aba"),
+        bab: found(4, "bab"),
+    });
+    assert_eq!(ssl("xyx[xyx]xyx"), SslVerdict::NoMatchingBab);
+    assert_eq!(ssl("zazbz[bzb]cdb"), SslVerdict::Supported {
+        aba: found(2, "zbz"),
+        bab: found(6, "bzb"),
+    });
+    assert_eq!(ssl("abcd[bab]xyz"), SslVerdict::NoAba);
+}
+
+#[test]
+fn generated_addresses() {
+    use ipv7::{has_abba, collect_aba, has_matching_bab_for, Generator};
+
+    let mut generator = Generator::new(7);
+    for i in 0..1000 {
+        let (tls, ssl) = (i % 2 == 0, i % 4 < 2);
+        let ip = generator.generate(tls, ssl);
+        assert_eq!(ip.has_tls_support(), tls, "{}", ip);
+        assert_eq!(ip.has_ssl_support(), ssl, "{}", ip);
+
+        // Cross-check with the helpers, on the raw sequences.
+        let text      = ip.to_string();
+        let sequences = text.split(&['[', ']'][..])
+                            .map(str::as_bytes)
+                            .collect::<Vec<_>>();
+        let supernets = sequences.iter().step_by(2);
+        let hypernets = sequences.iter().skip(1).step_by(2);
+        assert_eq!(supernets.clone().any(|s| has_abba(s))
+                && !hypernets.clone().any(|s| has_abba(s)), tls, "{}", ip);
+        assert_eq!(supernets.flat_map(|s| collect_aba(s)).any(|aba| {
+            hypernets.clone().any(|s| has_matching_bab_for(s, aba))
+        }), ssl, "{}", ip);
+    }
+}
 // }}}
@@ -12,19 +12,22 @@
 // }}}
 // {{{ Crates
 
+use std::env;
 use std::fs::File;
 use std::io::Read;
 
 #[macro_use]
 extern crate nom;
+extern crate ocr;
 
 // }}}
 // {{{ Screen
 
 mod screen {
     use std::fmt;
+    use ocr;
 
-    #[derive(Debug)]
+    #[derive(Clone, Debug, PartialEq, Eq)]
     pub struct Screen {
         width  : u16,
         height : u16,
@@ -41,6 +44,11 @@ mod screen {
             }
         }
 
+        /// Reads the letters displayed on the screen.
+        pub fn read(&self) -> Result<String, ocr::Error> {
+            ocr::recognize(&self.pixels, self.width as usize)
+        }
+
         /// Returns the number of pixels lit on the screen.
         pub fn pixels_lit(&self) -> i32 {
             self.pixels.iter().fold(0, |sum, &p| if p { sum + 1 } else { sum })
@@ -56,6 +64,30 @@ mod screen {
             }
         }
 
+        /// Returns the pixels of a rectangle at the top-left of the screen,
+        /// row by row.
+        pub fn rect(&self, width: u16, height: u16) -> Vec<bool> {
+            let mut pixels = Vec::with_capacity((width*height) as usize);
+            for i in 0 .. height {
+                for j in 0 .. width {
+                    pixels.push(self.pixels[indices_to_index(i, j, self.width)]);
+                }
+            }
+            pixels
+        }
+
+        /// Sets the pixels of a rectangle at the top-left of the screen, from
+        /// pixels given row by row.
+        pub fn set_rect(&mut self, width: u16, height: u16, pixels: &[bool]) {
+            assert_eq!(pixels.len(), (width*height) as usize);
+            for i in 0 .. height {
+                for j in 0 .. width {
+                    let idx = indices_to_index(i, j, self.width);
+                    self.pixels[idx] = pixels[(i*width + j) as usize];
+                }
+            }
+        }
+
         /// Rotates the row `row` by `shift` pixels.
         ///
         /// This function shifts all of the pixels in row `row` (0 is the top
@@ -81,6 +113,18 @@ mod screen {
             self.rotate(start, len, shift, stride);
         }
 
+        /// Undoes `rotate_row(row, shift)`, shifting the pixels to the left.
+        pub fn unrotate_row(&mut self, row: usize, shift: usize) {
+            let len = self.width as usize;
+            self.rotate_row(row, (len - shift) % len);
+        }
+
+        /// Undoes `rotate_col(col, shift)`, shifting the pixels up.
+        pub fn unrotate_col(&mut self, col: usize, shift: usize) {
+            let len = self.height as usize;
+            self.rotate_col(col, (len - shift) % len);
+        }
+
         /// Rotates an array or the part of an array.
         ///
         /// The rotation is done with the Reversal Algorithm (John Bentley's
@@ -91,6 +135,9 @@ mod screen {
             let pivot = start + shift*stride;
 
             assert!(len > shift);
+            if shift == 0 {
+                return;
+            }
             reverse(&mut self.pixels, start, len,       stride);
             reverse(&mut self.pixels, start, shift,     stride);
             reverse(&mut self.pixels, pivot, len-shift, stride);
@@ -299,22 +346,167 @@ mod instruction {
     // }}}
 }
 
+// }}}
+// {{{ History
+
+mod history {
+    use super::apply;
+    use instruction::Instruction;
+    use screen::Screen;
+
+    /// A screen that records the instructions applied on it, so that they
+    /// can be undone and replayed.
+    #[derive(Debug)]
+    pub struct History {
+        screen : Screen,
+        log    : Vec<Entry>,
+        // Number of entries of the log currently applied on the screen.
+        cursor : usize,
+    }
+
+    #[derive(Debug)]
+    struct Entry {
+        instruction : Instruction,
+        // Pixels overwritten by a rect, lost otherwise.
+        overwritten : Vec<bool>,
+    }
+
+    impl History {
+        /// Starts recording the instructions applied on `screen`.
+        pub fn new(screen: Screen) -> Self {
+            History { screen, log: Vec::new(), cursor: 0 }
+        }
+
+        /// Returns the screen, in its current state.
+        pub fn screen(&self) -> &Screen {
+            &self.screen
+        }
+
+        /// Returns the number of instructions recorded.
+        pub fn len(&self) -> usize {
+            self.log.len()
+        }
+
+        /// Returns the number of instructions currently applied.
+        pub fn position(&self) -> usize {
+            self.cursor
+        }
+
+        /// Applies an instruction and records it.
+        ///
+        /// The instructions that were undone are forgotten.
+        pub fn apply(&mut self, instruction: Instruction) {
+            self.log.truncate(self.cursor);
+            self.log.push(Entry { instruction, overwritten: Vec::new() });
+            self.redo();
+        }
+
+        /// Undoes the last applied instruction.
+        ///
+        /// Returns false if there is nothing to undo.
+        pub fn undo(&mut self) -> bool {
+            if self.cursor == 0 {
+                return false;
+            }
+            self.cursor -= 1;
+            let entry = &self.log[self.cursor];
+            match entry.instruction {
+                Instruction::TurnOn { width, height }
+                    => self.screen.set_rect(width, height, &entry.overwritten),
+                Instruction::RotateRow { row, shift }
+                    => self.screen.unrotate_row(row, shift),
+                Instruction::RotateColumn { col, shift }
+                    => self.screen.unrotate_col(col, shift),
+            }
+            true
+        }
+
+        /// Replays the last undone instruction.
+        ///
+        /// Returns false if there is nothing to replay.
+        pub fn redo(&mut self) -> bool {
+            if self.cursor == self.log.len() {
+                return false;
+            }
+            let entry = &mut self.log[self.cursor];
+            if let Instruction::TurnOn { width, height } = entry.instruction {
+                entry.overwritten = self.screen.rect(width, height);
+            }
+            apply(&mut self.screen, &entry.instruction);
+            self.cursor += 1;
+            true
+        }
+
+        /// Moves to the state of the screen after the first `step`
+        /// instructions.
+        ///
+        /// # Panics
+        ///
+        /// Panics if fewer than `step` instructions were recorded.
+        pub fn seek(&mut self, step: usize) -> &Screen {
+            assert!(step <= self.log.len(), "step {} out of range", step);
+            while self.cursor > step {
+                self.undo();
+            }
+            while self.cursor < step {
+                self.redo();
+            }
+            &self.screen
+        }
+    }
+}
+
 // }}}
 
 use screen::Screen;
 use instruction::Instruction;
+use history::History;
+
+/// Applies an instruction on the screen.
+pub fn apply(screen: &mut Screen, instruction: &Instruction) {
+    match *instruction {
+        Instruction::TurnOn { width, height }
+            => screen.turn_on(width, height),
+        Instruction::RotateRow { row, shift }
+            => screen.rotate_row(row, shift),
+        Instruction::RotateColumn { col, shift }
+            => screen.rotate_col(col, shift),
+    }
+}
 
 pub fn execute(screen : &mut Screen, instructions: &[Instruction]) {
     for instruction in instructions.iter() {
+        apply(screen, instruction);
+    }
+}
+
+/// Runs the instructions backward, to recover a screen that gives the current
+/// display once the instructions are executed.
+///
+/// A rect loses what it overwrote: these pixels are turned off, which gives the
+/// pattern with the fewest pixels lit.
+/// Fails with the index of the rect that cannot have produced the display
+/// because some of its pixels are off. The screen is left untouched then.
+pub fn unexecute(screen: &mut Screen, instructions: &[Instruction])
+    -> Result<(), usize> {
+    let mut rewound = screen.clone();
+    for (idx, instruction) in instructions.iter().enumerate().rev() {
         match *instruction {
-            Instruction::TurnOn { width, height }
-                => screen.turn_on(width, height),
+            Instruction::TurnOn { width, height } => {
+                if rewound.rect(width, height).contains(&false) {
+                    return Err(idx);
+                }
+                let off = vec![false; (width*height) as usize];
+                rewound.set_rect(width, height, &off);
+            },
             Instruction::RotateRow { row, shift }
-                => screen.rotate_row(row, shift),
+                => rewound.unrotate_row(row, shift),
             Instruction::RotateColumn { col, shift }
-                => screen.rotate_col(col, shift),
+                => rewound.unrotate_col(col, shift),
         }
     }
+    *screen = rewound;
+    Ok(())
 }
 
 fn main() {
@@ -328,7 +520,30 @@ fn main() {
     execute(&mut screen, &instructions);
     println!("After swiping the card, there are {} pixels lit on the screen.",
              screen.pixels_lit());
-    println!("{}", screen);
+    match screen.read() {
+        Ok(code) => println!("The screen displays the code {}.", code),
+        Err(err) => println!("Cannot read the screen ({}), here it is:\n{}",
+                             err, screen),
+    }
+
+    // `--step N` shows the screen after the first N instructions.
+    let args = env::args().collect::<Vec<_>>();
+    if let Some(pos) = args.iter().position(|arg| arg == "--step") {
+        let step = args.get(pos + 1).and_then(|n| n.parse().ok())
+                                    .expect("invalid step");
+        let mut history = History::new(Screen::new(50, 6));
+        for &instruction in instructions.iter() {
+            history.apply(instruction);
+        }
+        if step > history.len() {
+            println!("There are only {} instructions.", history.len());
+        } else {
+            history.seek(step);
+            println!("After {} of the {} instructions, {} pixels are lit:\n{}",
+                     history.position(), history.len(),
+                     history.screen().pixels_lit(), history.screen());
+        }
+    }
 }
 
 // {{{ Tests
@@ -345,4 +560,74 @@ fn examples_part1() {
     assert_eq!(screen.pixels_lit(), 6);
 }
 
+#[test]
+fn history() {
+    let instructions = [
+        Instruction::TurnOn       { width: 3, height: 2 },
+        Instruction::RotateColumn { col: 1, shift: 1 },
+        Instruction::RotateRow    { row: 0, shift: 4 },
+        Instruction::RotateColumn { col: 1, shift: 1 },
+    ];
+    let mut history = History::new(Screen::new(7, 3));
+    let mut states  = vec![history.screen().clone()];
+    for &instruction in instructions.iter() {
+        history.apply(instruction);
+        states.push(history.screen().clone());
+    }
+    assert_eq!(history.len(), 4);
+    assert_eq!(format!("{}", history.screen()), ".#..#.#\n#.#....\n.#.....\n");
+
+    assert_eq!(*history.seek(1), states[1]);
+    assert_eq!(*history.seek(3), states[3]);
+    assert!(history.undo());
+    assert_eq!(*history.screen(), states[2]);
+    assert!(history.redo());
+    assert_eq!(*history.screen(), states[3]);
+    history.seek(4);
+    assert!(!history.redo());
+    assert_eq!(*history.seek(0), states[0]);
+    assert!(!history.undo());
+
+    // A new instruction forgets the undone ones.
+    history.seek(2);
+    history.apply(Instruction::TurnOn { width: 1, height: 3 });
+    assert_eq!(history.len(), 3);
+    assert_eq!(history.position(), 3);
+    history.undo();
+    assert_eq!(*history.screen(), states[2]);
+}
+
+#[test]
+fn reverse_run() {
+    let instructions = [
+        Instruction::TurnOn       { width: 3, height: 2 },
+        Instruction::RotateColumn { col: 1, shift: 1 },
+        Instruction::RotateRow    { row: 0, shift: 4 },
+        Instruction::RotateColumn { col: 1, shift: 0 },
+        Instruction::RotateColumn { col: 1, shift: 1 },
+    ];
+    // Rotations alone are reversible...
+    let mut initial = Screen::new(7, 3);
+    initial.turn_on(1, 3);
+    initial.rotate_row(2, 5);
+    let mut screen = initial.clone();
+    execute(&mut screen, &instructions[1..]);
+    assert_eq!(unexecute(&mut screen, &instructions[1..]), Ok(()));
+    assert_eq!(screen, initial);
+
+    // ... while rects give the smallest pattern.
+    let mut screen = initial.clone();
+    execute(&mut screen, &instructions);
+    assert_eq!(unexecute(&mut screen, &instructions), Ok(()));
+    let mut expected = initial.clone();
+    expected.set_rect(3, 2, &[false; 6]);
+    assert_eq!(screen, expected);
+
+    // Some displays can't be produced at all.
+    let mut screen = Screen::new(7, 3);
+    assert_eq!(unexecute(&mut screen, &instructions), Err(0));
+    let mut screen = initial.clone();
+    assert_eq!(unexecute(&mut screen, &instructions), Err(0));
+    assert_eq!(screen, initial);
+}
 // }}}
@@ -12,13 +12,20 @@
 // }}}
 // {{{ Crates
 
+use std::env;
 use std::fs::File;
-use std::io::Read;
+use std::io::{self, Read, Write};
+use std::process;
 
 // }}}
 // {{{ Compress
 
 mod compress {
+    use std::cmp;
+    use std::error;
+    use std::fmt;
+    use std::io;
+
     /// A compression marker.
     #[derive(Debug)]
     struct Marker {
@@ -26,62 +33,276 @@ mod compress {
         repeat: usize,
     }
 
-    /// Parses an usize value from a stream of bytes.
-    fn parse_usize(stream: &mut Iterator<Item=&u8>) -> usize {
-        stream.map(|&b| b as char).collect::<String>().parse().unwrap()
+    /// Errors found in a compressed stream.
+    ///
+    /// Each error comes with the offset of the faulty marker.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub enum Error {
+        /// The marker isn't of the form `(AxB)`.
+        InvalidMarker(usize),
+        /// The data repeated by the marker goes past the end of the input (or
+        /// of the enclosing marker, for the v2 of the algorithm).
+        Truncated(usize),
+    }
+
+    impl fmt::Display for Error {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match *self {
+                Error::InvalidMarker(offset)
+                    => write!(f, "invalid marker at offset {}", offset),
+                Error::Truncated(offset)
+                    => write!(f, "truncated data for the marker at offset {}",
+                              offset),
+            }
+        }
+    }
+
+    impl error::Error for Error {}
+
+    impl From<Error> for io::Error {
+        fn from(err: Error) -> Self {
+            io::Error::new(io::ErrorKind::InvalidData, err)
+        }
+    }
+
+    /// Parses an usize value from a slice of digits.
+    fn parse_usize(digits: &[u8]) -> Option<usize> {
+        if digits.is_empty() || !digits.iter().all(u8::is_ascii_digit) {
+            return None;
+        }
+        digits.iter().try_fold(0usize, |acc, &d| {
+            acc.checked_mul(10)?.checked_add(usize::from(d - b'0'))
+        })
     }
 
-    /// Extracts a marker from the stream.
-    fn extract_marker(stream: &mut Iterator<Item=&u8>) -> Marker {
-        let mut bytes = stream.take_while(|&b| *b != b')');
-        let length = parse_usize(&mut bytes.by_ref()
-                                           .take_while(|&b| *b != b'x'));
-        let repeat = parse_usize(&mut bytes);
-        assert!(bytes.next().is_none());
+    /// Extracts the marker starting at `pos`.
+    ///
+    /// Returns the marker and the position of the data it repeats.
+    fn extract_marker(data: &[u8], pos: usize) -> Result<(Marker, usize), Error> {
+        let invalid = Error::InvalidMarker(pos);
+        let len     = data[pos..].iter().position(|&b| b == b')')
+                                        .ok_or(invalid)?;
+        let content = &data[pos + 1 .. pos + len];
+        let sep     = content.iter().position(|&b| b == b'x').ok_or(invalid)?;
+        let length  = parse_usize(&content[..sep]).ok_or(invalid)?;
+        let repeat  = parse_usize(&content[sep + 1..]).ok_or(invalid)?;
 
-        Marker { length: length, repeat: repeat }
+        Ok((Marker { length, repeat }, pos + len + 1))
     }
 
-    /// Computes the size of the string after decompression.
-    fn compute_final_size(mut bytes : &mut Iterator<Item=&u8>,
-                          do_recursive_expand: bool) -> u64
+    /// Computes the size of `data[start..end]` after decompression.
+    fn compute_final_size(data: &[u8], start: usize, end: usize,
+                          do_recursive_expand: bool) -> Result<u64, Error>
     {
         let mut size = 0;
+        let mut pos  = start;
 
-        while let Some(&b) = bytes.next() {
-            if b == b'(' {
-                let marker   = extract_marker(&mut bytes);
-                let mut data = bytes.take(marker.length);
+        while pos < end {
+            if data[pos] == b'(' {
+                let (marker, next) = extract_marker(&data[..end], pos)?;
+                let stop = next.checked_add(marker.length)
+                               .filter(|&stop| stop <= end)
+                               .ok_or(Error::Truncated(pos))?;
                 let len = if do_recursive_expand {
-                    compute_final_size(&mut data, true)
+                    compute_final_size(data, next, stop, true)?
                 } else {
-                    // Consumes the iterator.
-                    let _ = data.last();
                     marker.length as u64
                 };
                 size += len * (marker.repeat as u64);
+                pos   = stop;
             } else {
                 size += 1;
+                pos  += 1;
             }
         }
-        size
+        Ok(size)
     }
 
     /// Computes the size of the string after decompression (algorithm v1).
-    pub fn compute_final_size_v1(s : &str) -> u64 {
-        compute_final_size(&mut s.as_bytes().iter(), false)
+    pub fn compute_final_size_v1(s : &str) -> Result<u64, Error> {
+        compute_final_size(s.as_bytes(), 0, s.len(), false)
     }
 
     /// Computes the size of the string after decompression (algorithm v2).
     ///
     /// The v2 of the algorithm use recursive expansion of the markers.
-    pub fn compute_final_size_v2(s : &str) -> u64 {
-        compute_final_size(&mut s.as_bytes().iter(), true)
+    pub fn compute_final_size_v2(s : &str) -> Result<u64, Error> {
+        compute_final_size(s.as_bytes(), 0, s.len(), true)
+    }
+
+    // {{{ Decompressor
+
+    /// A part of the input, being repeated.
+    #[derive(Debug)]
+    struct Section {
+        start  : usize,
+        end    : usize,
+        pos    : usize,
+        // Number of passes left, including the current one.
+        repeat : usize,
+        // Are the markers expanded, or copied as is?
+        expand : bool,
+    }
+
+    /// A streaming decompressor.
+    ///
+    /// The output is produced on demand, only the stack of the markers being
+    /// expanded is kept in memory.
+    #[derive(Debug)]
+    pub struct Decompressor<'a> {
+        data      : &'a [u8],
+        recursive : bool,
+        stack     : Vec<Section>,
+    }
+
+    impl<'a> Decompressor<'a> {
+        /// Decompresses `data` using the v1 of the algorithm.
+        pub fn v1(data: &'a [u8]) -> Self {
+            Self::new(data, false)
+        }
+
+        /// Decompresses `data` using the v2 of the algorithm, where the
+        /// markers are expanded recursively.
+        pub fn v2(data: &'a [u8]) -> Self {
+            Self::new(data, true)
+        }
+
+        fn new(data: &'a [u8], recursive: bool) -> Self {
+            let root = Section {
+                start: 0, end: data.len(), pos: 0, repeat: 1, expand: true,
+            };
+            Decompressor { data, recursive, stack: vec![root] }
+        }
+    }
+
+    impl<'a> io::Read for Decompressor<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            while !buf.is_empty() {
+                let section = match self.stack.last_mut() {
+                    Some(section) => section,
+                    None          => break,
+                };
+
+                if section.pos == section.end {
+                    section.repeat -= 1;
+                    section.pos     = section.start;
+                    if section.repeat == 0 {
+                        self.stack.pop();
+                    }
+                    continue;
+                }
+
+                let pos = section.pos;
+                if section.expand && self.data[pos] == b'(' {
+                    let data = &self.data[..section.end];
+                    let (marker, next) = extract_marker(data, pos)?;
+                    let end = next.checked_add(marker.length)
+                                  .filter(|&end| end <= section.end)
+                                  .ok_or(Error::Truncated(pos))?;
+                    section.pos = end;
+                    if marker.length != 0 && marker.repeat != 0 {
+                        self.stack.push(Section {
+                            start:  next,
+                            end,
+                            pos:    next,
+                            repeat: marker.repeat,
+                            expand: self.recursive,
+                        });
+                    }
+                    continue;
+                }
+
+                // Copy the bytes up to the next marker.
+                let chunk = &self.data[pos .. section.end];
+                let mut len = cmp::min(chunk.len(), buf.len());
+                if section.expand {
+                    len = chunk[..len].iter().position(|&b| b == b'(')
+                                             .unwrap_or(len);
+                }
+                buf[..len].copy_from_slice(&chunk[..len]);
+                section.pos += len;
+                return Ok(len);
+            }
+            Ok(0)
+        }
+    }
+
+    // }}}
+    // {{{ Compressor
+
+    /// Compresses `data` by replacing consecutive repetitions with markers.
+    ///
+    /// The output is meant for the v1 of the algorithm: markers are never
+    /// nested, and a `(` outside of a repeated part is escaped as `(1x1)(`.
+    /// If `data` contains no `(`, the output is decompressed the same way by
+    /// the v2 of the algorithm.
+    pub fn compress(data: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(data.len());
+        let mut pos = 0;
+
+        while pos < data.len() {
+            match best_repetition(&data[pos..]) {
+                Some(Marker { length, repeat }) => {
+                    out.extend_from_slice(
+                        format!("({}x{})", length, repeat).as_bytes()
+                    );
+                    out.extend_from_slice(&data[pos .. pos + length]);
+                    pos += length * repeat;
+                },
+                None => {
+                    if data[pos] == b'(' {
+                        out.extend_from_slice(b"(1x1)");
+                    }
+                    out.push(data[pos]);
+                    pos += 1;
+                },
+            }
+        }
+        out
     }
+
+    /// Finds the repetition at the start of `data` which saves the most
+    /// bytes, if any saves something.
+    fn best_repetition(data: &[u8]) -> Option<Marker> {
+        // Longer patterns are unlikely to repeat, and would be costly to look
+        // for.
+        const MAX_LENGTH: usize = 256;
+
+        let mut best  = None;
+        let mut saved = 0;
+        for length in 1 .. cmp::min(MAX_LENGTH, data.len() / 2) + 1 {
+            // Even if the pattern repeats until the end of the input, it
+            // costs at least the smallest marker, `(LxR)`, and itself: longer
+            // patterns can only save less.
+            if data.len() <= saved + length + 5 {
+                break;
+            }
+            let pattern = &data[..length];
+            let repeat  = data.chunks_exact(length)
+                              .take_while(|&chunk| chunk == pattern)
+                              .count();
+            // Marker and pattern replace the repeated pattern.
+            let cost = marker_len(length, repeat) + length;
+            if length * repeat > cost + saved {
+                saved = length * repeat - cost;
+                best  = Some(Marker { length, repeat });
+            }
+        }
+        best
+    }
+
+    /// Returns the length of the marker `(LxR)`.
+    fn marker_len(length: usize, repeat: usize) -> usize {
+        let digits = |n: usize| n.checked_ilog10().unwrap_or(0) as usize + 1;
+        digits(length) + digits(repeat) + 3
+    }
+
+    // }}}
 }
 
 // }}}
-use compress::{compute_final_size_v1,compute_final_size_v2};
+use compress::{compress, compute_final_size_v1, compute_final_size_v2};
+use compress::{Decompressor, Error};
 
 fn main() {
     let mut file  = File::open("input.txt").unwrap();
@@ -90,32 +311,187 @@ fn main() {
     file.read_to_string(&mut input).unwrap();
 
     let input = input.trim();
-    println!("The decompressed length of the file is {} bytes.",
-             compute_final_size_v1(input));
-    println!("The real decompressed length of the file is {} bytes.",
-             compute_final_size_v2(input));
+
+    // Write the (de)compressed file on the standard output instead.
+    let stdout = io::stdout();
+    let mut stdout = stdout.lock();
+    for arg in env::args().skip(1) {
+        let result = match arg.as_str() {
+            "--compress"      => stdout.write_all(&compress(input.as_bytes())),
+            "--decompress"    => io::copy(&mut Decompressor::v1(input.as_bytes()),
+                                          &mut stdout).map(|_| ()),
+            "--decompress-v2" => io::copy(&mut Decompressor::v2(input.as_bytes()),
+                                          &mut stdout).map(|_| ()),
+            _ => continue,
+        };
+        if let Err(err) = result {
+            // The decompressor reports invalid input as I/O errors.
+            if err.get_ref().is_some_and(|inner| inner.is::<Error>()) {
+                eprintln!("Cannot decompress the input: {}.", err);
+            } else {
+                eprintln!("Cannot write the file: {}.", err);
+            }
+            process::exit(1);
+        }
+        return;
+    }
+
+    match compute_final_size_v1(input) {
+        Ok(size) => println!("The decompressed length of the file is {} bytes.",
+                             size),
+        Err(err) => println!("Cannot decompress the file: {}.", err),
+    }
+    match compute_final_size_v2(input) {
+        Ok(size) => println!("The real decompressed length of the file is {} bytes.",
+                             size),
+        Err(err) => println!("Cannot decompress the file: {}.", err),
+    }
 }
 
 // {{{ Tests
 
 #[test]
 fn examples_part1() {
-    assert_eq!(compute_final_size_v1("ADVENT"),             6);
-    assert_eq!(compute_final_size_v1("A(1x5)BC"),           7);
-    assert_eq!(compute_final_size_v1("(3x3)XYZ"),           9);
-    assert_eq!(compute_final_size_v1("A(2x2)BCD(2x2)EFG"), 11);
-    assert_eq!(compute_final_size_v1("(6x1)(1x3)A"),        6);
-    assert_eq!(compute_final_size_v1("X(8x2)(3x3)ABCY"),   18);
+    assert_eq!(compute_final_size_v1("ADVENT"),             Ok(6));
+    assert_eq!(compute_final_size_v1("A(1x5)BC"),           Ok(7));
+    assert_eq!(compute_final_size_v1("(3x3)XYZ"),           Ok(9));
+    assert_eq!(compute_final_size_v1("A(2x2)BCD(2x2)EFG"), Ok(11));
+    assert_eq!(compute_final_size_v1("(6x1)(1x3)A"),        Ok(6));
+    assert_eq!(compute_final_size_v1("X(8x2)(3x3)ABCY"),   Ok(18));
 }
 
 #[test]
 fn examples_part2() {
-    assert_eq!(compute_final_size_v2("(3x3)XYZ"),           9);
-    assert_eq!(compute_final_size_v2("X(8x2)(3x3)ABCY"),   20);
+    assert_eq!(compute_final_size_v2("(3x3)XYZ"),           Ok(9));
+    assert_eq!(compute_final_size_v2("X(8x2)(3x3)ABCY"),   Ok(20));
     assert_eq!(compute_final_size_v2(
-            "(27x12)(20x12)(13x14)(7x10)(1x12)A"), 241920);
+            "(27x12)(20x12)(13x14)(7x10)(1x12)A"), Ok(241920));
     assert_eq!(compute_final_size_v2(
-            "(25x3)(3x3)ABC(2x3)XY(5x2)PQRSTX(18x9)(3x2)TWO(5x7)SEVEN"), 445);
+            "(25x3)(3x3)ABC(2x3)XY(5x2)PQRSTX(18x9)(3x2)TWO(5x7)SEVEN"), Ok(445));
+}
+
+#[cfg(test)]
+fn decompress(decompressor: Decompressor) -> io::Result<String> {
+    let mut output = String::new();
+    let mut reader = decompressor;
+    reader.read_to_string(&mut output).map(|_| output)
+}
+
+#[test]
+fn streaming_decompression() {
+    let testcases = [
+        ("ADVENT",            "ADVENT",             "ADVENT"),
+        ("A(1x5)BC",          "ABBBBBC",            "ABBBBBC"),
+        ("(3x3)XYZ",          "XYZXYZXYZ",          "XYZXYZXYZ"),
+        ("A(2x2)BCD(2x2)EFG", "ABCBCDEFEFG",        "ABCBCDEFEFG"),
+        ("(6x1)(1x3)A",       "(1x3)A",             "AAA"),
+        ("X(8x2)(3x3)ABCY",   "X(3x3)ABC(3x3)ABCY", "XABCABCABCABCABCABCY"),
+        ("(0x3)A(2x0)BC",     "A",                  "A"),
+    ];
+    for &(input, v1, v2) in testcases.iter() {
+        let bytes = input.as_bytes();
+        assert_eq!(decompress(Decompressor::v1(bytes)).unwrap(), v1);
+        assert_eq!(decompress(Decompressor::v2(bytes)).unwrap(), v2);
+    }
+
+    // Stream a huge output through a tiny buffer, without keeping it.
+    let input = b"(27x12)(20x12)(13x14)(7x10)(1x12)A";
+    let mut reader = Decompressor::v2(input);
+    let mut buffer = [0; 7];
+    let mut size   = 0;
+    loop {
+        let len = reader.read(&mut buffer).unwrap();
+        if len == 0 {
+            break;
+        }
+        assert!(buffer[..len].iter().all(|&b| b == b'A'));
+        size += len;
+    }
+    assert_eq!(size, 241920);
+}
+
+#[test]
+fn malformed_markers() {
+    use compress::Error;
+
+    let testcases = [
+        ("AB(3x3",     Err(Error::InvalidMarker(2))),
+        ("(3y3)ABC",   Err(Error::InvalidMarker(0))),
+        ("(x3)ABC",    Err(Error::InvalidMarker(0))),
+        ("(3x-1)ABC",  Err(Error::InvalidMarker(0))),
+        ("(3x3)AB",    Err(Error::Truncated(0))),
+        ("(99999999999999999999x1)A", Err(Error::InvalidMarker(0))),
+    ];
+    for &(input, expected) in testcases.iter() {
+        assert_eq!(compute_final_size_v1(input), expected);
+        assert_eq!(compute_final_size_v2(input), expected);
+    }
+    // Nested markers must fit in their parent for the v2 only.
+    assert_eq!(compute_final_size_v1("(5x2)(3x3)ABC"), Ok(13));
+    assert_eq!(compute_final_size_v2("(5x2)(3x3)ABC"),
+               Err(Error::Truncated(5)));
+
+    let err = decompress(Decompressor::v2(b"X(8x2)(3x3)AB"))
+        .unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    assert_eq!(err.to_string(), "truncated data for the marker at offset 1");
+}
+
+#[test]
+fn round_trip() {
+    use std::str;
+
+    assert_eq!(compress(b"ABABABABAB"), b"(2x5)AB");
+    assert_eq!(compress(b"ADVENT"), b"ADVENT");
+    assert_eq!(compress(b"A(B"), b"A(1x1)(B");
+    // Markers get longer with the number of digits.
+    assert_eq!(compress(b"AAAAAA"), b"AAAAAA");
+    assert_eq!(compress(&[b'A'; 7]), b"(1x7)A");
+    assert_eq!(compress(&[b'A'; 100]), b"(1x100)A");
+
+    let testcases: &[&[u8]] = &[
+        b"",
+        b"ADVENT",
+        b"XABCABCABCABCABCABCY",
+        b"AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAB",
+        b"(1x3)A(((((",
+        b"ABC(2x5)ABC(2x5)ABC(2x5)ABC(2x5)DEF",
+    ];
+    for &data in testcases.iter() {
+        let compressed = compress(data);
+        let output = decompress(Decompressor::v1(&compressed)).unwrap();
+        assert_eq!(output.as_bytes(), data);
+        assert_eq!(compute_final_size_v1(str::from_utf8(&compressed)
+                                                  .unwrap()),
+                   Ok(data.len() as u64));
+        if !data.contains(&b'(') {
+            let output = decompress(Decompressor::v2(&compressed)).unwrap();
+            assert_eq!(output.as_bytes(), data);
+        }
+    }
+
+    // Pseudo-random texts, with a lot of repetitions.
+    let mut seed = 42u32;
+    for _ in 0..50 {
+        let mut data = Vec::new();
+        while data.len() < 200 {
+            seed = seed.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            let len    = (seed >> 16) as usize % 5 + 1;
+            let repeat = (seed >> 24) as usize % 12 + 1;
+            let start  = data.len();
+            for i in 0..len {
+                data.push(b"AB(CD)x0123"[(seed as usize >> i) % 11]);
+            }
+            for _ in 1..repeat {
+                let pattern = data[start .. start + len].to_vec();
+                data.extend_from_slice(&pattern);
+            }
+        }
+        let compressed = compress(&data);
+        assert!(compressed.len() < data.len());
+        let output = decompress(Decompressor::v1(&compressed)).unwrap();
+        assert_eq!(output.as_bytes(), &data[..]);
+    }
 }
 
 // }}}
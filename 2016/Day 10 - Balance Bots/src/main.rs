@@ -12,6 +12,7 @@
 // }}}
 // {{{ Crates
 
+use std::env;
 use std::fs::File;
 use std::io::Read;
 
@@ -24,18 +25,29 @@ extern crate nom;
 mod factory {
     use std::collections::HashMap;
     use std::collections::VecDeque;
+    use std::error;
+    use std::fmt::{self, Write};
 
     use instruction::Instruction;
 
     // A bot's output.
     //
     // A bot can connected to another bot or to an output bin.
-    #[derive(Clone, Copy, Debug)]
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
     pub enum Output {
         Bot(u32),
         Bin(u32)
     }
 
+    impl fmt::Display for Output {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match *self {
+                Output::Bot(id) => write!(f, "bot {}", id),
+                Output::Bin(id) => write!(f, "output {}", id),
+            }
+        }
+    }
+
     // Represent an action.
     //
     // It can be used to keep track of which bot compares which values.
@@ -46,6 +58,42 @@ mod factory {
         pub high: i32,
     }
 
+    // Reasons why a factory cannot be built or run.
+    #[derive(Clone, Debug, PartialEq, Eq)]
+    pub enum Error {
+        // A bot is connected more than once.
+        DuplicateBot(u32),
+        // A bot is used but never connected.
+        UnknownBot(u32),
+        // A bot is given a value while already holding two.
+        BotOverflow { bot: u32, value: i32 },
+        // An output bin is given a second value.
+        BinOverflow { bin: u32, value: i32 },
+        // Some bots still hold a value nobody will complete (sorted by ID).
+        Deadlock(Vec<u32>),
+    }
+
+    impl fmt::Display for Error {
+        fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            match *self {
+                Error::DuplicateBot(bot) =>
+                    write!(f, "bot {} is connected twice", bot),
+                Error::UnknownBot(bot) =>
+                    write!(f, "bot {} is not connected", bot),
+                Error::BotOverflow { bot, value } =>
+                    write!(f, "bot {} is full and cannot take {}", bot, value),
+                Error::BinOverflow { bin, value } =>
+                    write!(f, "output {} is full and cannot take {}",
+                           bin, value),
+                Error::Deadlock(ref bots) =>
+                    write!(f, "deadlock, waiting for a second value: bots {:?}",
+                           bots),
+            }
+        }
+    }
+
+    impl error::Error for Error {}
+
     // {{{ Bot
 
     // A factory's bot.
@@ -72,14 +120,17 @@ mod factory {
 
         // Take a value.
         //
-        // This will panic if the bot is already full.
-        pub fn take(&mut self, value: i32) {
-            assert!(!self.is_full());
+        // This fails if the bot is already full.
+        pub fn take(&mut self, value: i32) -> Result<(), Error> {
+            if self.is_full() {
+                return Err(Error::BotOverflow { bot: self.id, value });
+            }
             if self.v1.is_none() {
                 self.v1 = Some(value);
             } else {
                 self.v2 = Some(value);
             }
+            Ok(())
         }
 
         // Check if the bot is full (i.e: already has two values).
@@ -99,7 +150,11 @@ mod factory {
     pub struct Factory {
         bots: HashMap<u32, Bot>,
         bins: HashMap<u32, Option<i32>>,
-        start_bot: Option<u32>,
+        // Initial values, and the bot they are given to.
+        inputs: Vec<(i32, u32)>,
+        // Bots holding two values, waiting to dispatch them.
+        ready: VecDeque<u32>,
+        log: Vec<Action>,
     }
 
     impl Factory {
@@ -109,103 +164,225 @@ mod factory {
         // - create the bots and the output bins
         // - create the connections
         // - give an initial value to some bots
-        pub fn new(instructions : &[Instruction]) -> Self {
+        pub fn new(instructions : &[Instruction]) -> Result<Self, Error> {
             let mut fact = Factory {
-                bots: HashMap::new(),
-                bins: HashMap::new(),
-                start_bot: None,
+                bots:  HashMap::new(),
+                bins:  HashMap::new(),
+                inputs: Vec::new(),
+                ready: VecDeque::new(),
+                log:   Vec::new(),
             };
             // 1. Create and connect the bots and output bins.
             for instr in instructions {
                 if let Instruction::Connect { bot, lo, hi } = *instr {
                     // Create bot.
-                    assert_eq!(fact.bots.contains_key(&bot), false);
-                    fact.bots.insert(bot, Bot::new(bot, lo, hi));
+                    if fact.bots.insert(bot, Bot::new(bot, lo, hi)).is_some() {
+                        return Err(Error::DuplicateBot(bot));
+                    }
                     // Create output bins, if any.
                     for out in &[lo, hi] {
                         if let Output::Bin(id) = *out {
-                            assert_eq!(fact.bins.contains_key(&id), false);
                             fact.bins.insert(id, None);
                         }
                     }
                 }
             }
-            // 2. Give initial value to some bots.
+            // 2. Check that the bots are connected to existing bots.
+            for bot in fact.bots.values() {
+                for out in &[bot.low, bot.high] {
+                    if let Output::Bot(id) = *out {
+                        if !fact.bots.contains_key(&id) {
+                            return Err(Error::UnknownBot(id));
+                        }
+                    }
+                }
+            }
+            // 3. Give initial value to some bots.
             for instr in instructions {
                 if let Instruction::GiveValue{ bot: bot_id, value } = *instr {
-                    // At this point, bot must exists!
-                    let bot = fact.bots.get_mut(&bot_id).unwrap();
-                    bot.take(value);
-                    // If bot has two values this is the starting point.
+                    let bot = fact.bots.get_mut(&bot_id)
+                                       .ok_or(Error::UnknownBot(bot_id))?;
+                    bot.take(value)?;
+                    fact.inputs.push((value, bot_id));
+                    // Bots with two values are the starting points.
                     if bot.is_full() {
-                        // We should have a single starting point.
-                        assert!(fact.start_bot.is_none());
-                        fact.start_bot = Some(bot_id);
+                        fact.ready.push_back(bot_id);
                     }
                 }
             }
-            fact
+            Ok(fact)
         }
 
         // Run the factory until all bots are in a stable state.
         //
-        // A log of the actions performed is returned.
-        pub fn run(&mut self) -> Vec<Action> {
-            // We must have a starting bot to run the simulation!
-            let start_bot = self.start_bot.unwrap();
-            let mut queue = VecDeque::new();
-            let mut log   = Vec::new();
-
-            queue.push_back(start_bot);
-            while let Some(bot_id) = queue.pop_front() {
-                // At this point, bot must exists!
-                let mut bot = self.bots.remove(&bot_id).unwrap();
-                if bot.is_full() {
-                    // Add the output bots, if any, to the queue.
-                    for out in &[bot.low, bot.high] {
-                        if let Output::Bot(out_id) = *out {
-                            queue.push_back(out_id);
-                        }
-                    }
-                    // Dispatch the values and log the action.
-                    log.push(self.dispatch(&mut bot));
-                    self.bots.insert(bot.id, bot);
-                }
-            };
-            log
+        // The bots holding two values dispatch them in turn, in the order
+        // they got their second value.
+        // This fails if a bot or an output bin is given too many values, or
+        // if a bot ends up holding a single value.
+        pub fn run(&mut self) -> Result<(), Error> {
+            while let Some(bot_id) = self.ready.pop_front() {
+                // Dispatch the values and log the action.
+                let action = self.dispatch(bot_id)?;
+                self.log.push(action);
+            }
+
+            let mut waiting = self.bots.values().filter(|bot| bot.v1.is_some())
+                                                .map(|bot| bot.id)
+                                                .collect::<Vec<_>>();
+            if waiting.is_empty() {
+                Ok(())
+            } else {
+                waiting.sort();
+                Err(Error::Deadlock(waiting))
+            }
         }
 
-        // Dispatch the values of `bot` throught its output connections.
-        fn dispatch(&mut self, bot: &mut Bot) -> Action {
-            assert!(bot.is_full());
-            let (v1, v2) = (bot.v1.take().unwrap(), bot.v2.take().unwrap());
-            let (lo, hi) = if v1 < v2 { (v1, v2) } else { (v2, v1) };
-            self.forward_value(&mut bot.low,  lo);
-            self.forward_value(&mut bot.high, hi);
-            Action { bot: bot.id, low: lo, high: hi }
+        // Returns the actions performed so far.
+        pub fn log(&self) -> &[Action] {
+            &self.log
+        }
+
+        // Returns the ID of the bot that compared `a` and `b`, if any.
+        pub fn compared(&self, a: i32, b: i32) -> Option<u32> {
+            let (low, high) = if a < b { (a, b) } else { (b, a) };
+            self.log.iter().find(|action| action.low == low
+                                       && action.high == high)
+                           .map(|action| action.bot)
         }
 
         pub fn get_bin_value(&self, id: u32) -> Option<i32> {
-            self.bins[&id]
+            self.bins.get(&id).and_then(|&value| value)
+        }
+
+        // Returns the bots that handled the value of the output bin `id`,
+        // from the one it was given to the one that put it in the bin.
+        pub fn value_path(&self, id: u32) -> Vec<u32> {
+            let value = match self.get_bin_value(id) {
+                Some(value) => value,
+                None        => return Vec::new(),
+            };
+            let mut path   = Vec::new();
+            let mut target = Output::Bin(id);
+            let mut before = self.log.len();
+
+            // Walk back from the bin, through the bots that sent the value,
+            // until its initial bot.
+            while let Some(idx) = self.log[..before].iter().rposition(|action| {
+                self.sent(action, value, target)
+            }) {
+                let bot = self.log[idx].bot;
+                path.push(bot);
+                target = Output::Bot(bot);
+                before = idx;
+            }
+            path.reverse();
+            path
+        }
+
+        // Checks if the action sent `value` to `target`.
+        fn sent(&self, action: &Action, value: i32, target: Output) -> bool {
+            let bot = &self.bots[&action.bot];
+            (action.low == value && bot.low == target)
+                || (action.high == value && bot.high == target)
+        }
+
+        // {{{ Export
+
+        // Render the factory as a Graphviz DOT graph.
+        //
+        // Bots are boxes whose edges, labelled `low` and `high`, lead to
+        // other bots or to output bins. Initial values are plain text nodes
+        // pointing to the bot they are given to.
+        pub fn to_dot(&self) -> String {
+            let mut dot = String::from("digraph factory {\n");
+
+            for id in sorted(self.bots.keys()) {
+                let bot = &self.bots[&id];
+                writeln!(dot, "    \"bot {}\" [shape=box];", id).unwrap();
+                writeln!(dot, "    \"bot {}\" -> \"{}\" [label=\"low\"];",
+                         id, bot.low).unwrap();
+                writeln!(dot, "    \"bot {}\" -> \"{}\" [label=\"high\"];",
+                         id, bot.high).unwrap();
+            }
+            for id in sorted(self.bins.keys()) {
+                writeln!(dot, "    \"output {}\" [shape=doublecircle];",
+                         id).unwrap();
+            }
+            for (i, &(value, bot)) in self.inputs.iter().enumerate() {
+                writeln!(dot, "    \"value#{}\" [label=\"{}\", shape=plaintext];",
+                         i, value).unwrap();
+                writeln!(dot, "    \"value#{}\" -> \"bot {}\";", i, bot).unwrap();
+            }
+
+            dot.push_str("}\n");
+            dot
+        }
+
+        // Render the actions performed so far as a JSON array.
+        //
+        // Each action is numbered from 1, and tells where its values went.
+        pub fn trace_json(&self) -> String {
+            let actions = self.log.iter().enumerate().map(|(i, action)| {
+                let bot = &self.bots[&action.bot];
+                format!("  {{\"step\": {}, \"bot\": {}, \"low\": {}, \
+                         \"high\": {}, \"low_to\": \"{}\", \"high_to\": \"{}\"}}",
+                        i + 1, action.bot, action.low, action.high,
+                        bot.low, bot.high)
+            }).collect::<Vec<_>>();
+
+            if actions.is_empty() {
+                "[]\n".to_owned()
+            } else {
+                format!("[\n{}\n]\n", actions.join(",\n"))
+            }
+        }
+
+        // }}}
+
+        // Dispatch the values of the bot `id` throught its output connections.
+        fn dispatch(&mut self, id: u32) -> Result<Action, Error> {
+            let (low, high, v1, v2) = {
+                let bot = self.bots.get_mut(&id).unwrap();
+                assert!(bot.is_full());
+                let (v1, v2) = (bot.v1.take().unwrap(), bot.v2.take().unwrap());
+                (bot.low, bot.high, v1, v2)
+            };
+            let (lo, hi) = if v1 < v2 { (v1, v2) } else { (v2, v1) };
+            self.forward_value(low,  lo)?;
+            self.forward_value(high, hi)?;
+            Ok(Action { bot: id, low: lo, high: hi })
         }
 
         // Forward the value `val` into `out`.
-        fn forward_value(&mut self, out: &mut Output, val: i32) {
-            match *out {
+        fn forward_value(&mut self, out: Output, val: i32) -> Result<(), Error> {
+            match out {
                 Output::Bin(id) => {
                     let bin = self.bins.get_mut(&id).unwrap();
-                    assert!(bin.is_none());
+                    if bin.is_some() {
+                        return Err(Error::BinOverflow { bin: id, value: val });
+                    }
                     *bin = Some(val);
                 },
                 Output::Bot(id) => {
                     let bot = self.bots.get_mut(&id).unwrap();
-                    bot.take(val);
+                    bot.take(val)?;
+                    if bot.is_full() {
+                        self.ready.push_back(id);
+                    }
                 },
             };
+            Ok(())
         }
     }
 
     // }}}
+
+    // Returns the IDs in ascending order.
+    fn sorted<'a, I: Iterator<Item=&'a u32>>(ids: I) -> Vec<u32> {
+        let mut ids = ids.cloned().collect::<Vec<_>>();
+        ids.sort();
+        ids
+    }
 }
 
 // }}}
@@ -303,13 +480,27 @@ fn main() {
     let instructions = input.lines().map(|l| l.parse::<Instruction>().unwrap())
                                     .collect::<Vec<_>>();
 
-    let mut fact = Factory::new(&instructions);
-    let log = fact.run();
-    if let Some(action) = log.iter().find(|a| a.low == 17 && a.high == 61) {
-        println!("Bot {} compared the value 17 and 61.", action.bot);
+    let mut fact = Factory::new(&instructions).unwrap();
+
+    // Export the factory instead of only running it.
+    let export = env::args().nth(1);
+    if export.as_deref() == Some("--dot") {
+        print!("{}", fact.to_dot());
+        return;
+    }
+    fact.run().unwrap();
+    if export.as_deref() == Some("--trace") {
+        print!("{}", fact.trace_json());
+        return;
+    }
+
+    if let Some(bot) = fact.compared(17, 61) {
+        println!("Bot {} compared the value 17 and 61.", bot);
     }
     println!("The product of the output bins 0, 1 and 2 is {}.",
              output_product(&fact, &[0, 1, 2]));
+    println!("The bots made {} comparisons, the value of output 0 went \
+              through the bots {:?}.", fact.log().len(), fact.value_path(0));
 }
 
 // {{{ Tests
@@ -326,8 +517,8 @@ value 2 goes to bot 2";
     let instructions = input.lines().map(|l| l.parse::<Instruction>().unwrap())
                                     .collect::<Vec<_>>();
 
-    let mut fact = Factory::new(&instructions);
-    fact.run();
+    let mut fact = Factory::new(&instructions).unwrap();
+    fact.run().unwrap();
     assert_eq!(fact.get_bin_value(0), Some(5));
     assert_eq!(fact.get_bin_value(1), Some(2));
     assert_eq!(fact.get_bin_value(2), Some(3));
@@ -345,9 +536,166 @@ value 2 goes to bot 2";
     let instructions = input.lines().map(|l| l.parse::<Instruction>().unwrap())
                                     .collect::<Vec<_>>();
 
-    let mut fact = Factory::new(&instructions);
-    fact.run();
+    let mut fact = Factory::new(&instructions).unwrap();
+    fact.run().unwrap();
     assert_eq!(output_product(&fact, &[0, 1, 2]), 30);
 }
 
+#[cfg(test)]
+fn parse(input: &str) -> Vec<Instruction> {
+    input.lines().map(|l| l.parse::<Instruction>().unwrap()).collect()
+}
+
+#[test]
+fn queries() {
+    let instructions = parse("value 5 goes to bot 2
+bot 2 gives low to bot 1 and high to bot 0
+value 3 goes to bot 1
+bot 1 gives low to output 1 and high to bot 0
+bot 0 gives low to output 2 and high to output 0
+value 2 goes to bot 2");
+
+    let mut fact = Factory::new(&instructions).unwrap();
+    fact.run().unwrap();
+    assert_eq!(fact.log().len(), 3);
+    assert_eq!(fact.compared(5, 2), Some(2));
+    assert_eq!(fact.compared(3, 5), Some(0));
+    assert_eq!(fact.compared(2, 3), Some(1));
+    assert_eq!(fact.compared(2, 2), None);
+    assert_eq!(fact.value_path(0), vec![2, 0]);
+    assert_eq!(fact.value_path(1), vec![2, 1]);
+    assert_eq!(fact.value_path(2), vec![1, 0]);
+    assert_eq!(fact.value_path(3), vec![]);
+}
+
+#[test]
+fn several_starting_bots() {
+    let instructions = parse("value 1 goes to bot 0
+value 2 goes to bot 0
+value 3 goes to bot 1
+value 4 goes to bot 1
+bot 0 gives low to output 0 and high to bot 2
+bot 1 gives low to bot 2 and high to output 1
+bot 2 gives low to output 2 and high to output 3");
+
+    let mut fact = Factory::new(&instructions).unwrap();
+    fact.run().unwrap();
+    let bins = (0..4).map(|id| fact.get_bin_value(id)).collect::<Vec<_>>();
+    assert_eq!(bins, vec![Some(1), Some(4), Some(2), Some(3)]);
+    assert_eq!(fact.value_path(0), vec![0]);
+    assert_eq!(fact.value_path(3), vec![1, 2]);
+    assert_eq!(fact.compared(2, 3), Some(2));
+}
+
+#[test]
+fn invalid_factories() {
+    use factory::Error;
+
+    let testcases = [
+        ("bot 0 gives low to output 0 and high to output 1
+bot 0 gives low to output 2 and high to output 3", Error::DuplicateBot(0)),
+        ("bot 0 gives low to bot 1 and high to output 1", Error::UnknownBot(1)),
+        ("value 1 goes to bot 3
+bot 0 gives low to output 0 and high to output 1", Error::UnknownBot(3)),
+        ("value 1 goes to bot 0
+value 2 goes to bot 0
+value 3 goes to bot 0
+bot 0 gives low to output 0 and high to output 1",
+         Error::BotOverflow { bot: 0, value: 3 }),
+    ];
+    for &(input, ref expected) in testcases.iter() {
+        assert_eq!(Factory::new(&parse(input)).unwrap_err(), *expected);
+    }
+
+    let testcases = [
+        // Bot 1 never gets its second value.
+        ("value 1 goes to bot 0
+value 2 goes to bot 0
+value 3 goes to bot 2
+bot 0 gives low to bot 1 and high to output 0
+bot 1 gives low to output 1 and high to output 2
+bot 2 gives low to output 3 and high to output 4",
+         Error::Deadlock(vec![1, 2])),
+        ("value 1 goes to bot 0
+value 2 goes to bot 0
+bot 0 gives low to output 0 and high to output 0",
+         Error::BinOverflow { bin: 0, value: 2 }),
+        // Bot 1 is still full when bot 0 gives it a value.
+        ("value 1 goes to bot 0
+value 2 goes to bot 0
+value 3 goes to bot 1
+value 4 goes to bot 1
+bot 0 gives low to bot 1 and high to output 0
+bot 1 gives low to output 1 and high to output 2",
+         Error::BotOverflow { bot: 1, value: 1 }),
+    ];
+    for &(input, ref expected) in testcases.iter() {
+        let mut fact = Factory::new(&parse(input)).unwrap();
+        assert_eq!(fact.run().unwrap_err(), *expected);
+    }
+}
+
+#[test]
+fn export() {
+    let instructions = parse("value 5 goes to bot 2
+bot 2 gives low to bot 1 and high to bot 0
+value 3 goes to bot 1
+bot 1 gives low to output 1 and high to bot 0
+bot 0 gives low to output 2 and high to output 0
+value 2 goes to bot 2");
+
+    let mut fact = Factory::new(&instructions).unwrap();
+    assert_eq!(fact.trace_json(), "[]\n");
+    assert_eq!(fact.to_dot(), "\
+digraph factory {
+    \"bot 0\" [shape=box];
+    \"bot 0\" -> \"output 2\" [label=\"low\"];
+    \"bot 0\" -> \"output 0\" [label=\"high\"];
+    \"bot 1\" [shape=box];
+    \"bot 1\" -> \"output 1\" [label=\"low\"];
+    \"bot 1\" -> \"bot 0\" [label=\"high\"];
+    \"bot 2\" [shape=box];
+    \"bot 2\" -> \"bot 1\" [label=\"low\"];
+    \"bot 2\" -> \"bot 0\" [label=\"high\"];
+    \"output 0\" [shape=doublecircle];
+    \"output 1\" [shape=doublecircle];
+    \"output 2\" [shape=doublecircle];
+    \"value#0\" [label=\"5\", shape=plaintext];
+    \"value#0\" -> \"bot 2\";
+    \"value#1\" [label=\"3\", shape=plaintext];
+    \"value#1\" -> \"bot 1\";
+    \"value#2\" [label=\"2\", shape=plaintext];
+    \"value#2\" -> \"bot 2\";
+}
+");
+
+    fact.run().unwrap();
+    assert_eq!(fact.trace_json(), "\
+[
+  {\"step\": 1, \"bot\": 2, \"low\": 2, \"high\": 5, \"low_to\": \"bot 1\", \"high_to\": \"bot 0\"},
+  {\"step\": 2, \"bot\": 1, \"low\": 2, \"high\": 3, \"low_to\": \"output 1\", \"high_to\": \"bot 0\"},
+  {\"step\": 3, \"bot\": 0, \"low\": 3, \"high\": 5, \"low_to\": \"output 2\", \"high_to\": \"output 0\"}
+]
+");
+}
+
+#[test]
+fn value_path_with_duplicates() {
+    // Both output 0 and output 1 hold a 1, from different bots.
+    let instructions = parse("value 1 goes to bot 0
+value 2 goes to bot 0
+value 1 goes to bot 1
+value 3 goes to bot 1
+bot 0 gives low to output 0 and high to bot 2
+bot 1 gives low to output 1 and high to bot 2
+bot 2 gives low to output 2 and high to output 3");
+
+    let mut fact = Factory::new(&instructions).unwrap();
+    fact.run().unwrap();
+    assert_eq!(fact.value_path(0), vec![0]);
+    assert_eq!(fact.value_path(1), vec![1]);
+    assert_eq!(fact.value_path(2), vec![0, 2]);
+    assert_eq!(fact.value_path(3), vec![1, 2]);
+}
+
 // }}}
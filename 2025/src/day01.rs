@@ -1,81 +1,199 @@
+use crate::ParseError;
+
+/// Parse the rotations, one per line: left ones are negative, right ones
+/// positive.
+///
+/// Blank lines are ignored.
+///
+/// # Errors
+///
+/// If a line isn't a direction (`L` or `R`) followed by a distance.
+pub fn parse(input: &str) -> Result<Vec<i32>, ParseError> {
+    input
+        .lines()
+        .enumerate()
+        .map(|(idx, line)| (idx, line.trim_end()))
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(idx, line)| {
+            let error = |reason| ParseError::new(idx + 1, reason);
+
+            let (direction, distance) = line
+                .split_at_checked(1)
+                .ok_or_else(|| error(format!("invalid rotation: {line}")))?;
+            let distance = distance
+                .parse::<i32>()
+                .ok()
+                .filter(|distance| *distance >= 0)
+                .ok_or_else(|| {
+                    error(format!("invalid distance: {distance}"))
+                })?;
+
+            match direction {
+                "L" => Ok(-distance),
+                "R" => Ok(distance),
+                _ => Err(error(format!("invalid direction: {direction}"))),
+            }
+        })
+        .collect()
+}
+
 /// Solution for day 1, part 1.
 #[must_use]
 pub fn solve_part1(rotations: &[i32]) -> usize {
-    rotations
-        .iter()
-        .scan(50, |state, distance| {
-            *state = (*state + distance).rem_euclid(100);
-            Some(*state)
-        })
-        .filter(|state| *state == 0)
+    Dial::default()
+        .events(rotations)
+        .filter(|event| *event == Event::LandedOnZero)
         .count()
 }
 
 /// Solution for day 1, part 2.
 #[must_use]
 pub fn solve_part2(rotations: &[i32]) -> i32 {
-    rotations
-        .iter()
-        .scan(50, |state, distance| {
-            // Count the number of complete turns.
-            let mut hits = distance.abs() / 100;
-
-            // Now check the leftover rotation.
-            let distance = distance % 100;
-            let start = *state;
-            *state += distance;
-
-            if *state <= 0 {
-                // Ignore case where we start at 0 because that's a full
-                // rotation and already accounted for above.
-                hits += i32::from(start != 0);
-                // Only adjust if necessary.
-                *state += 100 * i32::from(*state < 0);
-            } else if *state >= 100 {
-                hits += 1;
-                *state -= 100;
-            }
-
-            Some(hits)
+    Dial::default()
+        .events(rotations)
+        .map(|event| match event {
+            // At most `i32::MAX / 100` times: the dial has 100 positions.
+            Event::CrossedZero(times) => times.cast_signed(),
+            Event::LandedOnZero => 1,
+            Event::FullTurns(_) => 0,
         })
         .sum()
 }
 
+/// A circular dial, with positions numbered from 0 to `size - 1`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Dial {
+    size: u32,
+    position: u32,
+}
+
+impl Dial {
+    /// Initialize a new dial of `size` positions, pointing at `start`.
+    ///
+    /// # Panics
+    ///
+    /// If `size` is zero or `start` is out of the dial.
+    #[must_use]
+    pub const fn new(size: u32, start: u32) -> Self {
+        assert!(size != 0, "empty dial");
+        assert!(start < size, "start position out of the dial");
+
+        Self {
+            size,
+            position: start,
+        }
+    }
+
+    /// Current position of the dial.
+    #[must_use]
+    pub const fn position(&self) -> u32 {
+        self.position
+    }
+
+    /// Rotate the dial by `distance` clicks (left if negative, right
+    /// otherwise), returning the resulting events.
+    ///
+    /// A null rotation doesn't move the dial, thus doesn't produce any event.
+    pub fn rotate(
+        &mut self,
+        distance: i32,
+    ) -> impl Iterator<Item = Event> + use<> {
+        let clicks = distance.unsigned_abs();
+        let leftover = clicks % self.size;
+        let start = self.position;
+
+        // Number of clicks before reaching zero for the first time.
+        let first_zero = if distance > 0 {
+            self.size - start
+        } else if start == 0 {
+            self.size
+        } else {
+            start
+        };
+        let hits = if clicks < first_zero {
+            0
+        } else {
+            1 + (clicks - first_zero) / self.size
+        };
+
+        self.position = if distance > 0 {
+            if leftover < self.size - start {
+                start + leftover
+            } else {
+                leftover - (self.size - start)
+            }
+        } else if leftover <= start {
+            start - leftover
+        } else {
+            start + (self.size - leftover)
+        };
+
+        let full_turns = clicks / self.size;
+        let landed = clicks != 0 && self.position == 0;
+        let crossed = hits - u32::from(landed);
+
+        [
+            (full_turns != 0).then_some(Event::FullTurns(full_turns)),
+            (crossed != 0).then_some(Event::CrossedZero(crossed)),
+            landed.then_some(Event::LandedOnZero),
+        ]
+        .into_iter()
+        .flatten()
+    }
+
+    /// Apply every rotation in order, yielding the stream of events.
+    pub fn events(
+        mut self,
+        rotations: &[i32],
+    ) -> impl Iterator<Item = Event> + '_ {
+        rotations
+            .iter()
+            .flat_map(move |&distance| self.rotate(distance))
+    }
+}
+
+impl Default for Dial {
+    /// The dial from the puzzle: 100 positions, starting at 50.
+    fn default() -> Self {
+        Self::new(100, 50)
+    }
+}
+
+/// Something noteworthy that happened during a rotation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Event {
+    /// The rotation made that many complete turns of the dial.
+    FullTurns(u32),
+    /// The dial pointed at zero that many times, without stopping there.
+    CrossedZero(u32),
+    /// The dial stopped on zero at the end of the rotation.
+    LandedOnZero,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use anyhow::{Context as _, Result as AnyResult, bail, ensure};
-    use std::{fs::File, io::prelude::*};
+    use anyhow::{Context as _, Result as AnyResult};
+    use quickcheck::{TestResult, quickcheck};
+
+    const EXAMPLE: &str = "L68\nL30\nR48\nL5\nR60\nL55\nL1\nL99\nR14\nL82\n";
 
     fn load_input() -> AnyResult<Vec<i32>> {
-        let file = File::open("input/day01.txt").context("open input file")?;
-
-        std::io::BufReader::new(file).lines().try_fold(
-            Vec::new(),
-            |mut acc, line| {
-                let line = line.context("read input line")?;
-                let (direction, distance) = line.split_at(1);
-                let distance =
-                    distance.parse::<i32>().context("invalid distance")?;
-                ensure!(distance >= 0, "negative distance: {distance}");
-
-                match direction {
-                    "L" => acc.push(-distance),
-                    "R" => acc.push(distance),
-                    _ => bail!("invalid direction: {direction}"),
-                }
+        let input =
+            std::fs::read_to_string("input/day01.txt").context("read input")?;
 
-                Ok(acc)
-            },
-        )
+        parse(&input).context("parse input")
     }
 
     #[test]
-    fn example_part1() {
-        let rotations = vec![-68, -30, 48, -5, 60, -55, -1, -99, 14, -82];
+    fn example_part1() -> AnyResult<()> {
+        let rotations = parse(EXAMPLE).context("parse example")?;
         let result = solve_part1(&rotations);
 
         assert_eq!(result, 3);
+
+        Ok(())
     }
 
     #[test]
@@ -89,11 +207,13 @@ mod tests {
     }
 
     #[test]
-    fn example_part2() {
-        let rotations = vec![-68, -30, 48, -5, 60, -55, -1, -99, 14, -82];
+    fn example_part2() -> AnyResult<()> {
+        let rotations = parse(EXAMPLE).context("parse example")?;
         let result = solve_part2(&rotations);
 
         assert_eq!(result, 6);
+
+        Ok(())
     }
 
     #[test]
@@ -105,4 +225,104 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn parse_rotations() {
+        assert_eq!(
+            parse(EXAMPLE).ok(),
+            Some(vec![-68, -30, 48, -5, 60, -55, -1, -99, 14, -82])
+        );
+
+        let error = parse("L1\nR-2\n").unwrap_err();
+        assert_eq!(error.line(), 2);
+        assert_eq!(error.to_string(), "line 2: invalid distance: -2");
+
+        assert_eq!(parse("L1\n\nR2\n\n").ok(), Some(vec![-1, 2]));
+
+        let error = parse("U3").unwrap_err();
+        assert_eq!(error.to_string(), "line 1: invalid direction: U");
+    }
+
+    /// Click-by-click simulation: returns, for each rotation, the number of
+    /// clicks pointing at zero and whether the dial stopped there.
+    fn naive(size: u32, start: u32, rotations: &[i32]) -> Vec<(u32, bool)> {
+        let mut position = start;
+
+        rotations
+            .iter()
+            .map(|&distance| {
+                let mut hits = 0;
+                for _ in 0..distance.unsigned_abs() {
+                    position = if distance > 0 {
+                        (position + 1) % size
+                    } else {
+                        (position + size - 1) % size
+                    };
+                    hits += u32::from(position == 0);
+                }
+                (hits, distance != 0 && position == 0)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn rotate_from_zero() {
+        let mut dial = Dial::new(100, 0);
+
+        let events = dial.rotate(-250).collect::<Vec<_>>();
+
+        assert_eq!(dial.position(), 50);
+        assert_eq!(events, vec![Event::FullTurns(2), Event::CrossedZero(2)]);
+
+        let events = dial.rotate(150).collect::<Vec<_>>();
+
+        assert_eq!(dial.position(), 0);
+        assert_eq!(
+            events,
+            vec![
+                Event::FullTurns(1),
+                Event::CrossedZero(1),
+                Event::LandedOnZero
+            ]
+        );
+
+        assert_eq!(dial.rotate(0).count(), 0);
+    }
+
+    fn matches_naive(size: u8, start: u8, rotations: Vec<i16>) -> TestResult {
+        if size == 0 {
+            return TestResult::discard();
+        }
+        let size = u32::from(size);
+        let start = u32::from(start) % size;
+        let rotations =
+            rotations.into_iter().map(i32::from).collect::<Vec<_>>();
+        let expected = naive(size, start, &rotations);
+        let mut dial = Dial::new(size, start);
+
+        TestResult::from_bool(rotations.iter().zip(expected).all(
+            |(&distance, (hits, landed))| {
+                let (mut full_turns, mut zeros, mut stopped) = (0, 0, false);
+                for event in dial.rotate(distance) {
+                    match event {
+                        Event::FullTurns(turns) => full_turns += turns,
+                        Event::CrossedZero(times) => zeros += times,
+                        Event::LandedOnZero => {
+                            zeros += 1;
+                            stopped = true;
+                        }
+                    }
+                }
+
+                zeros == hits
+                    && stopped == landed
+                    && full_turns == distance.unsigned_abs() / size
+            },
+        ))
+    }
+
+    #[test]
+    fn dial_vs_naive() {
+        quickcheck(matches_naive as fn(u8, u8, Vec<i16>) -> TestResult);
+    }
 }
@@ -1,5 +1,37 @@
+use crate::ParseError;
 use std::ops::RangeInclusive;
 
+/// Parse the comma-separated product ID ranges (`lo-hi`).
+///
+/// # Errors
+///
+/// If a range is malformed or its bounds aren't numbers.
+pub fn parse(input: &str) -> Result<Vec<RangeInclusive<i64>>, ParseError> {
+    input
+        .lines()
+        .enumerate()
+        .flat_map(|(idx, line)| {
+            line.trim_end()
+                .split_terminator(',')
+                .map(move |range| (idx + 1, range))
+        })
+        .map(|(line, range)| {
+            let error = |reason| ParseError::new(line, reason);
+            let (lo, hi) = range
+                .split_once('-')
+                .ok_or_else(|| error(format!("invalid range: {range}")))?;
+            let lo = lo
+                .parse::<i64>()
+                .map_err(|_| error(format!("invalid lower bound: {lo}")))?;
+            let hi = hi
+                .parse::<i64>()
+                .map_err(|_| error(format!("invalid upper bound: {hi}")))?;
+
+            Ok(lo..=hi)
+        })
+        .collect()
+}
+
 /// Solution for day 2, part 1.
 #[must_use]
 #[expect(clippy::string_slice, reason = "numbers as string are ASCII")]
@@ -84,39 +116,25 @@ mod tests {
     use super::*;
     use anyhow::{Context as _, Result as AnyResult};
 
+    const EXAMPLE: &str = "11-22,95-115,998-1012,1188511880-1188511890,\
+        222220-222224,1698522-1698528,446443-446449,38593856-38593862,\
+        565653-565659,824824821-824824827,2121212118-2121212124\n";
+
     fn load_input() -> AnyResult<Vec<RangeInclusive<i64>>> {
-        std::fs::read_to_string("input/day02.txt")
-            .context("read input")?
-            .trim_end()
-            .split(',')
-            .map(|value| {
-                let range = value.split_once('-').context("parse range")?;
-                let lo = range.0.parse::<i64>().context("parse lower bound")?;
-                let hi = range.1.parse::<i64>().context("parse upper bound")?;
-
-                Ok(lo..=hi)
-            })
-            .collect::<AnyResult<_>>()
+        let input =
+            std::fs::read_to_string("input/day02.txt").context("read input")?;
+
+        parse(&input).context("parse input")
     }
 
     #[test]
-    fn example_part1() {
-        let ranges = vec![
-            11..=22,
-            95..=115,
-            998..=1012,
-            1_188_511_880..=1_188_511_890,
-            222_220..=222_224,
-            1_698_522..=1_698_528,
-            446_443..=446_449,
-            38_593_856..=38_593_862,
-            565_653..=565_659,
-            824_824_821..=824_824_827,
-            2_121_212_118..=2_121_212_124,
-        ];
+    fn example_part1() -> AnyResult<()> {
+        let ranges = parse(EXAMPLE).context("parse example")?;
         let result = solve_part1(&ranges);
 
         assert_eq!(result, 1_227_775_554);
+
+        Ok(())
     }
 
     #[test]
@@ -129,6 +147,17 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn parse_ranges() {
+        assert_eq!(parse("11-22,95-115\n").ok(), Some(vec![11..=22, 95..=115]));
+
+        let error = parse("11-22,95\n").unwrap_err();
+        assert_eq!(error.to_string(), "line 1: invalid range: 95");
+
+        let error = parse("11-22,\n95-x").unwrap_err();
+        assert_eq!(error.to_string(), "line 2: invalid upper bound: x");
+    }
+
     #[test]
     fn repeat_blk() {
         assert_eq!(repeat_block(1, 3, 1), 111);
@@ -138,23 +167,13 @@ mod tests {
     }
 
     #[test]
-    fn example_part2() {
-        let ranges = vec![
-            11..=22,
-            95..=115,
-            998..=1012,
-            1_188_511_880..=1_188_511_890,
-            222_220..=222_224,
-            1_698_522..=1_698_528,
-            446_443..=446_449,
-            38_593_856..=38_593_862,
-            565_653..=565_659,
-            824_824_821..=824_824_827,
-            2_121_212_118..=2_121_212_124,
-        ];
+    fn example_part2() -> AnyResult<()> {
+        let ranges = parse(EXAMPLE).context("parse example")?;
         let result = solve_part2(ranges);
 
         assert_eq!(result, 4_174_379_265);
+
+        Ok(())
     }
 
     #[test]
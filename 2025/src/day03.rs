@@ -1,3 +1,31 @@
+use crate::ParseError;
+
+/// Parse the battery banks, one line of joltage digits per bank.
+///
+/// Blank lines are ignored.
+///
+/// # Errors
+///
+/// If a bank contains something else than digits.
+pub fn parse(input: &str) -> Result<Vec<String>, ParseError> {
+    input
+        .lines()
+        .enumerate()
+        .map(|(idx, line)| (idx, line.trim_end()))
+        .filter(|(_, bank)| !bank.is_empty())
+        .map(|(idx, bank)| {
+            if !bank.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(ParseError::new(
+                    idx + 1,
+                    format!("invalid bank: {bank}"),
+                ));
+            }
+
+            Ok(bank.to_owned())
+        })
+        .collect()
+}
+
 /// Solution for day 3, part 1.
 #[must_use]
 pub fn solve_part1(banks: &[String]) -> usize {
@@ -49,32 +77,25 @@ fn pick_first_max(bytes: &[u8]) -> (usize, u8) {
 mod tests {
     use super::*;
     use anyhow::{Context as _, Result as AnyResult};
-    use std::{fs::File, io::prelude::*};
+
+    const EXAMPLE: &str = "987654321111111\n811111111111119\n\
+        234234234234278\n818181911112111\n";
 
     fn load_input() -> AnyResult<Vec<String>> {
-        let file = File::open("input/day03.txt").context("open input file")?;
-
-        std::io::BufReader::new(file).lines().try_fold(
-            Vec::new(),
-            |mut acc, line| {
-                let line = line.context("read input line")?;
-                acc.push(line.trim_end().to_owned());
-                Ok(acc)
-            },
-        )
+        let input =
+            std::fs::read_to_string("input/day03.txt").context("read input")?;
+
+        parse(&input).context("parse input")
     }
 
     #[test]
-    fn example_part1() {
-        let banks = vec![
-            "987654321111111".to_owned(),
-            "811111111111119".to_owned(),
-            "234234234234278".to_owned(),
-            "818181911112111".to_owned(),
-        ];
+    fn example_part1() -> AnyResult<()> {
+        let banks = parse(EXAMPLE).context("parse example")?;
         let result = solve_part1(&banks);
 
         assert_eq!(result, 357);
+
+        Ok(())
     }
 
     #[test]
@@ -88,16 +109,13 @@ mod tests {
     }
 
     #[test]
-    fn example_part2() {
-        let banks = vec![
-            "987654321111111".to_owned(),
-            "811111111111119".to_owned(),
-            "234234234234278".to_owned(),
-            "818181911112111".to_owned(),
-        ];
+    fn example_part2() -> AnyResult<()> {
+        let banks = parse(EXAMPLE).context("parse example")?;
         let result = solve_part2(&banks);
 
         assert_eq!(result, 3_121_910_778_619);
+
+        Ok(())
     }
 
     #[test]
@@ -109,4 +127,20 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn parse_banks() {
+        assert_eq!(
+            parse("12\n34\n").ok(),
+            Some(vec!["12".to_owned(), "34".to_owned()])
+        );
+
+        let error = parse("12\n3a4\n").unwrap_err();
+        assert_eq!(error.to_string(), "line 2: invalid bank: 3a4");
+
+        assert_eq!(
+            parse("12\n\n34\n\n").ok(),
+            Some(vec!["12".to_owned(), "34".to_owned()])
+        );
+    }
 }
@@ -1,125 +1,264 @@
+use crate::ParseError;
+use std::fmt;
+
+/// Parse the map of the paper rolls (`@`) and empty cells (`.`).
+///
+/// Blank lines are ignored.
+///
+/// # Errors
+///
+/// If the map contains an unexpected character or isn't rectangular.
+pub fn parse(input: &str) -> Result<Vec<Vec<bool>>, ParseError> {
+    let rows = input
+        .lines()
+        .enumerate()
+        .map(|(idx, line)| (idx + 1, line.trim_end()))
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(line, cells)| {
+            let row = cells
+                .chars()
+                .map(|ch| match ch {
+                    '@' => Ok(true),
+                    '.' => Ok(false),
+                    _ => Err(ParseError::new(
+                        line,
+                        format!("invalid cell: {ch}"),
+                    )),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok((line, row))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let width = rows.first().map_or(0, |(_, row)| row.len());
+    if let Some((line, _)) = rows.iter().find(|(_, row)| row.len() != width) {
+        return Err(ParseError::new(*line, "ragged map"));
+    }
+
+    Ok(rows.into_iter().map(|(_, row)| row).collect())
+}
+
 /// Solution for day 4, part 1.
 #[must_use]
 pub fn solve_part1(grid: Vec<Vec<bool>>) -> usize {
-    Map::new(grid).pick_paper()
+    Removal::new(grid).next().map_or(0, |wave| wave.len())
 }
 
 /// Solution for day 4, part 2.
 #[must_use]
 pub fn solve_part2(grid: Vec<Vec<bool>>) -> usize {
-    let mut map = Map::new(grid);
-    let mut count = 0;
+    Removal::new(grid).map(|wave| wave.len()).sum()
+}
 
-    loop {
-        let update = map.pick_paper();
-        if update == 0 {
-            break;
+/// A roll can be picked once it has less than this many neighbors.
+const MAX_NEIGHBORS: u8 = 4;
+
+/// Incremental simulation of the paper rolls removal.
+///
+/// Yields the position `(y, x)` of the rolls removed at each wave, until no
+/// roll can be picked anymore.
+///
+/// Neighbor counts are computed once, then only the neighbors of the removed
+/// rolls are re-examined instead of rescanning the whole grid at every wave.
+#[derive(Debug)]
+pub struct Removal {
+    h: usize,
+    w: usize,
+    /// Number of remaining neighbors of each cell.
+    neighbors: Vec<u8>,
+    /// Rolls still on the map and not yet scheduled for removal.
+    present: Vec<bool>,
+    /// Rolls removed by the next wave.
+    wave: Vec<usize>,
+}
+
+impl Removal {
+    /// Initialize a new simulation from a rectangular grid (`true` for a roll).
+    #[must_use]
+    pub fn new(grid: Vec<Vec<bool>>) -> Self {
+        let h = grid.len();
+        let w = grid.first().map_or(0, Vec::len);
+        debug_assert!(grid.iter().all(|row| row.len() == w), "ragged grid");
+        let present = grid.into_iter().flatten().collect::<Vec<_>>();
+
+        let neighbors = (0..present.len())
+            .map(|idx| {
+                adjacent_cells(h, w, idx)
+                    .map(|cell| u8::from(present[cell]))
+                    .sum()
+            })
+            .collect::<Vec<_>>();
+
+        let mut removal = Self {
+            h,
+            w,
+            neighbors,
+            present,
+            wave: Vec::new(),
+        };
+        removal.wave = (0..removal.present.len())
+            .filter(|&idx| removal.is_pickable(idx))
+            .collect();
+        for &idx in &removal.wave {
+            removal.present[idx] = false;
         }
-        count += update;
+
+        removal
     }
 
-    count
+    fn is_pickable(&self, idx: usize) -> bool {
+        self.present[idx] && self.neighbors[idx] < MAX_NEIGHBORS
+    }
 }
 
-struct Map {
-    grid: Vec<Vec<bool>>,
+impl Iterator for Removal {
+    type Item = Vec<(usize, usize)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.wave.is_empty() {
+            return None;
+        }
+
+        // Only the neighbors of the removed rolls can become pickable.
+        let mut next_wave = Vec::new();
+        for &idx in &self.wave {
+            for cell in adjacent_cells(self.h, self.w, idx) {
+                self.neighbors[cell] -= 1;
+                if self.is_pickable(cell) {
+                    self.present[cell] = false;
+                    next_wave.push(cell);
+                }
+            }
+        }
+
+        let wave = std::mem::replace(&mut self.wave, next_wave);
+        Some(
+            wave.into_iter()
+                .map(|idx| (idx / self.w, idx % self.w))
+                .collect(),
+        )
+    }
+}
+
+/// Indices of the (up to 8) cells surrounding `idx` in a `h`×`w` grid.
+fn adjacent_cells(
     h: usize,
     w: usize,
+    idx: usize,
+) -> impl Iterator<Item = usize> {
+    let (y, x) = (idx / w, idx % w);
+
+    (y.saturating_sub(1)..=(y + 1).min(h - 1))
+        .flat_map(move |ny| {
+            (x.saturating_sub(1)..=(x + 1).min(w - 1))
+                .map(move |nx| ny * w + nx)
+        })
+        .filter(move |&cell| cell != idx)
 }
 
-impl Map {
-    fn new(grid: Vec<Vec<bool>>) -> Self {
-        let h = grid.len();
-        let w = grid[0].len();
-        Self { grid, h, w }
-    }
+/// Per-wave history of the paper rolls removal.
+///
+/// Rendered as a layered map where each removed roll is labelled with the
+/// wave that picked it (`0`-`9`, then `a`-`z` and `A`-`Z`, `+` beyond), rolls
+/// that are never picked stay as `@` and empty cells as `.`.
+#[derive(Debug)]
+pub struct History {
+    layers: Vec<Vec<Layer>>,
+    waves: Vec<Vec<(usize, usize)>>,
+}
+
+impl History {
+    /// Run the whole removal simulation on `grid` and record every wave.
+    #[must_use]
+    pub fn new(grid: Vec<Vec<bool>>) -> Self {
+        let mut layers = grid
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .map(|&roll| if roll { Layer::Kept } else { Layer::Empty })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        let waves = Removal::new(grid).collect::<Vec<_>>();
 
-    #[expect(
-        clippy::cast_possible_wrap,
-        clippy::cast_sign_loss,
-        reason = "non-issue with our range"
-    )]
-    fn is_reachable(&self, y: usize, x: usize, dy: isize, dx: isize) -> bool {
-        let ny = y as isize + dy;
-        let nx = x as isize + dx;
-
-        if ny < 0 || ny >= self.h as isize || nx < 0 || nx >= self.w as isize {
-            return false;
+        for (round, wave) in waves.iter().enumerate() {
+            for &(y, x) in wave {
+                layers[y][x] = Layer::Removed(round);
+            }
         }
 
-        self.grid[ny as usize][nx as usize]
+        Self { layers, waves }
     }
 
-    #[expect(clippy::needless_range_loop, reason = "more readable that way")]
-    fn pick_paper(&mut self) -> usize {
-        let mut count = 0;
-        let mut next = self.grid.clone();
+    /// Positions `(y, x)` of the rolls removed by each wave, in order.
+    #[must_use]
+    pub fn waves(&self) -> &[Vec<(usize, usize)>] {
+        &self.waves
+    }
 
-        for y in 0..self.h {
-            for x in 0..self.w {
-                if !self.grid[y][x] {
-                    continue;
-                }
+    /// Total number of removed rolls.
+    #[must_use]
+    pub fn removed_count(&self) -> usize {
+        self.waves.iter().map(Vec::len).sum()
+    }
+}
 
-                let neighbors = usize::from(self.is_reachable(y, x, -1, -1))
-                    + usize::from(self.is_reachable(y, x, -1, 0))
-                    + usize::from(self.is_reachable(y, x, -1, 1))
-                    + usize::from(self.is_reachable(y, x, 0, -1))
-                    + usize::from(self.is_reachable(y, x, 0, 1))
-                    + usize::from(self.is_reachable(y, x, 1, -1))
-                    + usize::from(self.is_reachable(y, x, 1, 0))
-                    + usize::from(self.is_reachable(y, x, 1, 1));
-
-                if neighbors < 4 {
-                    next[y][x] = false;
-                    count += 1;
-                }
+impl fmt::Display for History {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const LABELS: &[u8] =
+            b"0123456789abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ";
+
+        for row in &self.layers {
+            for layer in row {
+                let ch = match *layer {
+                    Layer::Empty => '.',
+                    Layer::Kept => '@',
+                    Layer::Removed(round) => {
+                        LABELS.get(round).map_or('+', |&label| label.into())
+                    }
+                };
+                write!(f, "{ch}")?;
             }
+            writeln!(f)?;
         }
-        self.grid = next;
-        count
+
+        Ok(())
     }
 }
 
+/// State of a cell at the end of the simulation.
+#[derive(Debug, Clone, Copy)]
+enum Layer {
+    Empty,
+    Kept,
+    Removed(usize),
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use anyhow::{Context as _, Result as AnyResult};
-    use std::{fs::File, io::prelude::*};
+
+    const EXAMPLE: &str = "..@@.@@@@.\n@@@.@.@.@@\n@@@@@.@.@@\n@.@@@@..@.\n\
+        @@.@@@@.@@\n.@@@@@@@.@\n.@.@.@.@@@\n@.@@@.@@@@\n.@@@@@@@@.\n\
+        @.@.@@@.@.\n";
 
     fn load_input() -> AnyResult<Vec<Vec<bool>>> {
-        let file = File::open("input/day04.txt").context("open input file")?;
+        let input =
+            std::fs::read_to_string("input/day04.txt").context("read input")?;
 
-        std::io::BufReader::new(file)
-            .lines()
-            .map(|line| {
-                let line = line.context("read input line")?;
-                Ok(line.chars().map(|ch| ch == '@').collect::<Vec<_>>())
-            })
-            .collect()
+        parse(&input).context("parse input")
     }
 
     #[test]
-    fn example_part1() {
-        let map = [
-            "..@@.@@@@.",
-            "@@@.@.@.@@",
-            "@@@@@.@.@@",
-            "@.@@@@..@.",
-            "@@.@@@@.@@",
-            ".@@@@@@@.@",
-            ".@.@.@.@@@",
-            "@.@@@.@@@@",
-            ".@@@@@@@@.",
-            "@.@.@@@.@.",
-        ]
-        .iter()
-        .map(|row| row.chars().map(|ch| ch == '@').collect::<Vec<_>>())
-        .collect::<Vec<_>>();
+    fn example_part1() -> AnyResult<()> {
+        let map = parse(EXAMPLE).context("parse example")?;
 
         let result = solve_part1(map);
 
         assert_eq!(result, 13);
+
+        Ok(())
     }
 
     #[test]
@@ -133,26 +272,41 @@ mod tests {
     }
 
     #[test]
-    fn example_part2() {
-        let map = [
-            "..@@.@@@@.",
-            "@@@.@.@.@@",
-            "@@@@@.@.@@",
-            "@.@@@@..@.",
-            "@@.@@@@.@@",
-            ".@@@@@@@.@",
-            ".@.@.@.@@@",
-            "@.@@@.@@@@",
-            ".@@@@@@@@.",
-            "@.@.@@@.@.",
-        ]
-        .iter()
-        .map(|row| row.chars().map(|ch| ch == '@').collect::<Vec<_>>())
-        .collect::<Vec<_>>();
+    fn example_part2() -> AnyResult<()> {
+        let map = parse(EXAMPLE).context("parse example")?;
 
         let result = solve_part2(map);
 
         assert_eq!(result, 43);
+
+        Ok(())
+    }
+
+    #[test]
+    fn example_history() -> AnyResult<()> {
+        let map = parse(EXAMPLE).context("parse example")?;
+
+        let history = History::new(map);
+        let sizes = history.waves().iter().map(Vec::len).collect::<Vec<_>>();
+        let expected = [
+            "..00.0010.\n",
+            "023.1.1.21\n",
+            "13467.0.22\n",
+            "1.58@@..1.\n",
+            "02.@@@@.10\n",
+            ".13@@@@@.1\n",
+            ".1.@.@.@@2\n",
+            "0.3@@.@@@3\n",
+            ".12@@@@@4.\n",
+            "0.0.@@@.0.\n",
+        ]
+        .concat();
+
+        assert_eq!(sizes, vec![13, 12, 7, 5, 2, 1, 1, 1, 1]);
+        assert_eq!(history.removed_count(), 43);
+        assert_eq!(history.to_string(), expected);
+
+        Ok(())
     }
 
     #[test]
@@ -164,4 +318,25 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn parse_map() {
+        assert_eq!(
+            parse("@.\n.@\n").ok(),
+            Some(vec![vec![true, false], vec![false, true]])
+        );
+
+        let error = parse("@.\n.#\n").unwrap_err();
+        assert_eq!(error.to_string(), "line 2: invalid cell: #");
+
+        let error = parse("@.\n.@\n@\n").unwrap_err();
+        assert_eq!(error.to_string(), "line 3: ragged map");
+
+        assert_eq!(
+            parse("@.\n\n.@\n\n").ok(),
+            Some(vec![vec![true, false], vec![false, true]])
+        );
+        let error = parse("@.\n\n.@\n@\n").unwrap_err();
+        assert_eq!(error.to_string(), "line 4: ragged map");
+    }
 }
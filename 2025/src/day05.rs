@@ -1,5 +1,52 @@
+use crate::ParseError;
 use std::ops::RangeInclusive;
 
+/// Parse the inventory: the fresh ingredient ID ranges (`lo-hi`), a blank line,
+/// then the available ingredient IDs.
+///
+/// # Errors
+///
+/// If a range or an ingredient ID is malformed.
+pub fn parse(input: &str) -> Result<Inventory, ParseError> {
+    let mut lines = input
+        .lines()
+        .enumerate()
+        .map(|(idx, line)| (idx + 1, line.trim()));
+
+    // First section: ranges (until a blank line).
+    let mut fresh_ranges = Vec::new();
+    for (idx, line) in &mut lines {
+        if line.is_empty() {
+            break;
+        }
+
+        let error = |reason| ParseError::new(idx, reason);
+        let (lo, hi) = line
+            .split_once('-')
+            .ok_or_else(|| error(format!("invalid range: {line}")))?;
+        let lo = lo
+            .parse::<i64>()
+            .map_err(|_| error(format!("invalid lower bound: {lo}")))?;
+        let hi = hi
+            .parse::<i64>()
+            .map_err(|_| error(format!("invalid upper bound: {hi}")))?;
+
+        fresh_ranges.push(lo..=hi);
+    }
+
+    // Second section: ingredient IDs (blank lines are ignored).
+    let ingredients = lines
+        .filter(|(_, line)| !line.is_empty())
+        .map(|(idx, line)| {
+            line.parse().map_err(|_| {
+                ParseError::new(idx, format!("invalid ingredient: {line}"))
+            })
+        })
+        .collect::<Result<_, _>>()?;
+
+    Ok(Inventory::new(fresh_ranges, ingredients))
+}
+
 /// Solution for day 5, part 1.
 #[must_use]
 pub fn solve_part1(inventory: &Inventory) -> usize {
@@ -32,78 +79,58 @@ pub struct Inventory {
     ingredients: Vec<i64>,
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use anyhow::{Context as _, Result as AnyResult};
-
-    impl Inventory {
-        /// Initialize a new inventory.
-        ///
-        /// Merge overlapping ranges together.
-        fn new(
-            mut fresh_ranges: Vec<RangeInclusive<i64>>,
-            ingredients: Vec<i64>,
-        ) -> Self {
-            fresh_ranges.sort_unstable_by_key(|range| *range.start());
-
-            let mut merged_ranges = Vec::with_capacity(fresh_ranges.len());
-            let mut curr = (*fresh_ranges[0].start(), *fresh_ranges[0].end());
-            for range in fresh_ranges {
-                if *range.start() <= curr.1 {
-                    curr.1 = curr.1.max(*range.end());
-                } else {
-                    merged_ranges.push(curr.0..=curr.1);
-                    curr = range.into_inner();
+impl Inventory {
+    /// Initialize a new inventory.
+    ///
+    /// Merge overlapping ranges together.
+    fn new(
+        mut fresh_ranges: Vec<RangeInclusive<i64>>,
+        ingredients: Vec<i64>,
+    ) -> Self {
+        fresh_ranges.sort_unstable_by_key(|range| *range.start());
+
+        let mut merged_ranges: Vec<RangeInclusive<i64>> =
+            Vec::with_capacity(fresh_ranges.len());
+        for range in fresh_ranges {
+            match merged_ranges.last_mut() {
+                Some(curr) if range.start() <= curr.end() => {
+                    let end = *curr.end().max(range.end());
+                    *curr = *curr.start()..=end;
                 }
+                _ => merged_ranges.push(range),
             }
-            merged_ranges.push(curr.0..=curr.1);
+        }
 
-            Self {
-                fresh_ranges: merged_ranges,
-                ingredients,
-            }
+        Self {
+            fresh_ranges: merged_ranges,
+            ingredients,
         }
     }
+}
 
-    fn load_input() -> AnyResult<Inventory> {
-        let input = std::fs::read_to_string("input/day05.txt")?;
-
-        let mut lines = input.lines().map(str::trim);
-
-        // First section: ranges (until a blank line).
-        let mut fresh_ranges = Vec::new();
-        for line in &mut lines {
-            if line.is_empty() {
-                break;
-            }
-
-            let range = line.split_once('-').context("parse range")?;
-            let lo = range.0.parse::<i64>().context("parse lower bound")?;
-            let hi = range.1.parse::<i64>().context("parse upper bound")?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::{Context as _, Result as AnyResult};
 
-            fresh_ranges.push(lo..=hi);
-        }
+    const EXAMPLE: &str = "3-5\n10-14\n16-20\n12-18\n\n1\n5\n8\n11\n17\n32\n";
 
-        // Second section: ingredient IDs
-        let mut ingredients = Vec::new();
-        for line in lines {
-            ingredients.push(line.parse().expect("parse ingredient"));
-        }
+    fn load_input() -> AnyResult<Inventory> {
+        let input =
+            std::fs::read_to_string("input/day05.txt").context("read input")?;
 
-        Ok(Inventory::new(fresh_ranges, ingredients))
+        parse(&input).context("parse input")
     }
 
     #[test]
-    fn example_part1() {
-        let inventory = Inventory::new(
-            vec![3..=5, 10..=14, 16..=20, 12..=18],
-            vec![1, 5, 8, 11, 17, 32],
-        );
+    fn example_part1() -> AnyResult<()> {
+        let inventory = parse(EXAMPLE).context("parse example")?;
 
         let result = solve_part1(&inventory);
 
         assert_eq!(result, 3);
+
+        Ok(())
     }
 
     #[test]
@@ -117,15 +144,14 @@ mod tests {
     }
 
     #[test]
-    fn example_part2() {
-        let inventory = Inventory::new(
-            vec![3..=5, 10..=14, 16..=20, 12..=18],
-            vec![1, 5, 8, 11, 17, 32],
-        );
+    fn example_part2() -> AnyResult<()> {
+        let inventory = parse(EXAMPLE).context("parse example")?;
 
         let result = solve_part2(&inventory);
 
         assert_eq!(result, 14);
+
+        Ok(())
     }
 
     #[test]
@@ -137,4 +163,20 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn parse_inventory() {
+        let inventory = parse(EXAMPLE).unwrap();
+        assert_eq!(inventory.fresh_ranges, vec![3..=5, 10..=20]);
+        assert_eq!(inventory.ingredients, vec![1, 5, 8, 11, 17, 32]);
+
+        let error = parse("3-5\n10:14\n\n1\n").unwrap_err();
+        assert_eq!(error.to_string(), "line 2: invalid range: 10:14");
+
+        let error = parse("3-5\n\n1\nx\n").unwrap_err();
+        assert_eq!(error.to_string(), "line 4: invalid ingredient: x");
+
+        let inventory = parse("3-5\n\n1\n\n").unwrap();
+        assert_eq!(inventory.ingredients, vec![1]);
+    }
 }
@@ -1,5 +1,7 @@
 //! Advent of Code 2025
 
+use std::fmt;
+
 /// Solution for day 1.
 pub mod day01;
 /// Solution for day 2.
@@ -10,3 +12,33 @@ pub mod day03;
 pub mod day04;
 /// Solution for day 5.
 pub mod day05;
+
+/// Malformed puzzle input.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    line: usize,
+    reason: String,
+}
+
+impl ParseError {
+    pub(crate) fn new(line: usize, reason: impl Into<String>) -> Self {
+        Self {
+            line,
+            reason: reason.into(),
+        }
+    }
+
+    /// Line (starting from 1) where the error occurred.
+    #[must_use]
+    pub const fn line(&self) -> usize {
+        self.line
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.reason)
+    }
+}
+
+impl std::error::Error for ParseError {}
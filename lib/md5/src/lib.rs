@@ -0,0 +1,494 @@
+//! A safe and streaming implementation of MD5 (RFC 1321).
+
+// {{{ Lints
+
+#![deny(missing_debug_implementations,
+        missing_copy_implementations,
+        missing_docs,
+        trivial_casts,
+        trivial_numeric_casts,
+        unsafe_code,
+        unstable_features,
+        unused_import_braces,
+        unused_qualifications
+)]
+
+// }}}
+// {{{ MD5
+
+/// Size of a MD5 digest, in bytes.
+pub const DIGEST_SIZE: usize = 16;
+
+/// Size of a block processed by MD5, in bytes.
+const BLOCK_SIZE: usize = 64;
+
+/// Padding appended to the message: a `1` bit followed by `0` bits.
+const PADDING: [u8; BLOCK_SIZE] = {
+    let mut padding = [0; BLOCK_SIZE];
+    padding[0] = 0x80;
+    padding
+};
+
+/// An incremental MD5 hasher.
+///
+/// The state is cheap to copy: a common prefix can be hashed once and the
+/// resulting state reused to hash several messages sharing this prefix, only
+/// the blocks containing the suffix are processed again.
+#[derive(Clone, Copy, Debug)]
+pub struct Md5 {
+    state:  [u32; 4],
+    // Pending bytes, not yet processed because they don't fill a block.
+    buffer: [u8; BLOCK_SIZE],
+    buflen: usize,
+    // Length of the whole message, in bytes.
+    length: u64,
+}
+
+impl Md5 {
+    /// Initialize a new hasher.
+    pub fn new() -> Self {
+        Md5 {
+            state:  [0x6745_2301, 0xefcd_ab89, 0x98ba_dcfe, 0x1032_5476],
+            buffer: [0; BLOCK_SIZE],
+            buflen: 0,
+            length: 0,
+        }
+    }
+
+    /// Feed the hasher with more bytes.
+    pub fn update(&mut self, mut bytes: &[u8]) {
+        self.length = self.length.wrapping_add(bytes.len() as u64);
+
+        // Complete the pending block first.
+        if self.buflen != 0 {
+            let count = (BLOCK_SIZE - self.buflen).min(bytes.len());
+            self.buffer[self.buflen..self.buflen + count]
+                .copy_from_slice(&bytes[..count]);
+            self.buflen += count;
+            bytes = &bytes[count..];
+
+            if self.buflen < BLOCK_SIZE {
+                return;
+            }
+            let block = self.buffer;
+            self.compress(&block);
+            self.buflen = 0;
+        }
+
+        // Process the whole blocks directly from the input.
+        let mut blocks = bytes.chunks(BLOCK_SIZE);
+        let tail = blocks.next_back().unwrap_or(&[]);
+        for block in blocks {
+            self.compress(block);
+        }
+        if tail.len() == BLOCK_SIZE {
+            self.compress(tail);
+        } else {
+            self.buffer[..tail.len()].copy_from_slice(tail);
+            self.buflen = tail.len();
+        }
+    }
+
+    /// Pad the message and returns its digest.
+    pub fn finalize(mut self) -> [u8; DIGEST_SIZE] {
+        // Length in bits, modulo 2^64.
+        let length = self.length.wrapping_mul(8);
+
+        // Pad up to 56 bytes (modulo 64), leaving room for the length.
+        let padlen = if self.buflen < 56 {
+            56 - self.buflen
+        } else {
+            BLOCK_SIZE + 56 - self.buflen
+        };
+        self.update(&PADDING[..padlen]);
+        self.update(&length.to_le_bytes());
+        debug_assert_eq!(self.buflen, 0, "message should be an exact \
+                                          multiple of 512 bits");
+
+        let mut digest = [0; DIGEST_SIZE];
+        for (bytes, word) in digest.chunks_mut(4).zip(self.state.iter()) {
+            bytes.copy_from_slice(&word.to_le_bytes());
+        }
+        digest
+    }
+
+    // Process a 16-word block.
+    #[allow(clippy::many_single_char_names)]
+    fn compress(&mut self, block: &[u8]) {
+        let mut x = [0u32; 16];
+        for (word, bytes) in x.iter_mut().zip(block.chunks(4)) {
+            *word = u32::from_le_bytes([bytes[0], bytes[1],
+                                        bytes[2], bytes[3]]);
+        }
+        let state = &mut self.state;
+
+        let mut a = state[0];
+        let mut b = state[1];
+        let mut c = state[2];
+        let mut d = state[3];
+        // {{{ Round 1
+
+        a = apply(function_f, a, b, c, d, x[ 0],  7, 0xd76a_a478); // 1
+        d = apply(function_f, d, a, b, c, x[ 1], 12, 0xe8c7_b756); // 2
+        c = apply(function_f, c, d, a, b, x[ 2], 17, 0x2420_70db); // 3
+        b = apply(function_f, b, c, d, a, x[ 3], 22, 0xc1bd_ceee); // 4
+        a = apply(function_f, a, b, c, d, x[ 4],  7, 0xf57c_0faf); // 5
+        d = apply(function_f, d, a, b, c, x[ 5], 12, 0x4787_c62a); // 6
+        c = apply(function_f, c, d, a, b, x[ 6], 17, 0xa830_4613); // 7
+        b = apply(function_f, b, c, d, a, x[ 7], 22, 0xfd46_9501); // 8
+        a = apply(function_f, a, b, c, d, x[ 8],  7, 0x6980_98d8); // 9
+        d = apply(function_f, d, a, b, c, x[ 9], 12, 0x8b44_f7af); // 10
+        c = apply(function_f, c, d, a, b, x[10], 17, 0xffff_5bb1); // 11
+        b = apply(function_f, b, c, d, a, x[11], 22, 0x895c_d7be); // 12
+        a = apply(function_f, a, b, c, d, x[12],  7, 0x6b90_1122); // 13
+        d = apply(function_f, d, a, b, c, x[13], 12, 0xfd98_7193); // 14
+        c = apply(function_f, c, d, a, b, x[14], 17, 0xa679_438e); // 15
+        b = apply(function_f, b, c, d, a, x[15], 22, 0x49b4_0821); // 16
+
+        // }}}
+        // {{{ Round 2
+
+        a = apply(function_g, a, b, c, d, x[ 1],  5, 0xf61e_2562); // 17
+        d = apply(function_g, d, a, b, c, x[ 6],  9, 0xc040_b340); // 18
+        c = apply(function_g, c, d, a, b, x[11], 14, 0x265e_5a51); // 19
+        b = apply(function_g, b, c, d, a, x[ 0], 20, 0xe9b6_c7aa); // 20
+        a = apply(function_g, a, b, c, d, x[ 5],  5, 0xd62f_105d); // 21
+        d = apply(function_g, d, a, b, c, x[10],  9, 0x0244_1453); // 22
+        c = apply(function_g, c, d, a, b, x[15], 14, 0xd8a1_e681); // 23
+        b = apply(function_g, b, c, d, a, x[ 4], 20, 0xe7d3_fbc8); // 24
+        a = apply(function_g, a, b, c, d, x[ 9],  5, 0x21e1_cde6); // 25
+        d = apply(function_g, d, a, b, c, x[14],  9, 0xc337_07d6); // 26
+        c = apply(function_g, c, d, a, b, x[ 3], 14, 0xf4d5_0d87); // 27
+        b = apply(function_g, b, c, d, a, x[ 8], 20, 0x455a_14ed); // 28
+        a = apply(function_g, a, b, c, d, x[13],  5, 0xa9e3_e905); // 29
+        d = apply(function_g, d, a, b, c, x[ 2],  9, 0xfcef_a3f8); // 30
+        c = apply(function_g, c, d, a, b, x[ 7], 14, 0x676f_02d9); // 31
+        b = apply(function_g, b, c, d, a, x[12], 20, 0x8d2a_4c8a); // 32
+
+        // }}}
+        // {{{ Round 3
+
+        a = apply(function_h, a, b, c, d, x[ 5],  4, 0xfffa_3942); // 33
+        d = apply(function_h, d, a, b, c, x[ 8], 11, 0x8771_f681); // 34
+        c = apply(function_h, c, d, a, b, x[11], 16, 0x6d9d_6122); // 35
+        b = apply(function_h, b, c, d, a, x[14], 23, 0xfde5_380c); // 36
+        a = apply(function_h, a, b, c, d, x[ 1],  4, 0xa4be_ea44); // 37
+        d = apply(function_h, d, a, b, c, x[ 4], 11, 0x4bde_cfa9); // 38
+        c = apply(function_h, c, d, a, b, x[ 7], 16, 0xf6bb_4b60); // 39
+        b = apply(function_h, b, c, d, a, x[10], 23, 0xbebf_bc70); // 40
+        a = apply(function_h, a, b, c, d, x[13],  4, 0x289b_7ec6); // 41
+        d = apply(function_h, d, a, b, c, x[ 0], 11, 0xeaa1_27fa); // 42
+        c = apply(function_h, c, d, a, b, x[ 3], 16, 0xd4ef_3085); // 43
+        b = apply(function_h, b, c, d, a, x[ 6], 23, 0x0488_1d05); // 44
+        a = apply(function_h, a, b, c, d, x[ 9],  4, 0xd9d4_d039); // 45
+        d = apply(function_h, d, a, b, c, x[12], 11, 0xe6db_99e5); // 46
+        c = apply(function_h, c, d, a, b, x[15], 16, 0x1fa2_7cf8); // 47
+        b = apply(function_h, b, c, d, a, x[ 2], 23, 0xc4ac_5665); // 48
+
+        // }}}
+        // {{{ Round 4
+
+        a = apply(function_i, a, b, c, d, x[ 0],  6, 0xf429_2244); // 49
+        d = apply(function_i, d, a, b, c, x[ 7], 10, 0x432a_ff97); // 50
+        c = apply(function_i, c, d, a, b, x[14], 15, 0xab94_23a7); // 51
+        b = apply(function_i, b, c, d, a, x[ 5], 21, 0xfc93_a039); // 52
+        a = apply(function_i, a, b, c, d, x[12],  6, 0x655b_59c3); // 53
+        d = apply(function_i, d, a, b, c, x[ 3], 10, 0x8f0c_cc92); // 54
+        c = apply(function_i, c, d, a, b, x[10], 15, 0xffef_f47d); // 55
+        b = apply(function_i, b, c, d, a, x[ 1], 21, 0x8584_5dd1); // 56
+        a = apply(function_i, a, b, c, d, x[ 8],  6, 0x6fa8_7e4f); // 57
+        d = apply(function_i, d, a, b, c, x[15], 10, 0xfe2c_e6e0); // 58
+        c = apply(function_i, c, d, a, b, x[ 6], 15, 0xa301_4314); // 59
+        b = apply(function_i, b, c, d, a, x[13], 21, 0x4e08_11a1); // 60
+        a = apply(function_i, a, b, c, d, x[ 4],  6, 0xf753_7e82); // 61
+        d = apply(function_i, d, a, b, c, x[11], 10, 0xbd3a_f235); // 62
+        c = apply(function_i, c, d, a, b, x[ 2], 15, 0x2ad7_d2bb); // 63
+        b = apply(function_i, b, c, d, a, x[ 9], 21, 0xeb86_d391); // 64
+
+        // }}}
+        state[0] = state[0].wrapping_add(a);
+        state[1] = state[1].wrapping_add(b);
+        state[2] = state[2].wrapping_add(c);
+        state[3] = state[3].wrapping_add(d);
+    }
+}
+
+impl Default for Md5 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Computes the digest of a whole message.
+pub fn digest(bytes: &[u8]) -> [u8; DIGEST_SIZE] {
+    let mut md5 = Md5::new();
+    md5.update(bytes);
+    md5.finalize()
+}
+
+/// Number of messages processed together by `finalize_lanes`.
+pub const LANES: usize = 8;
+
+/// Finalize several hashers at once.
+///
+/// When every message ends within a single block (the common case when hashing
+/// short candidates sharing a prefix), the final blocks are processed in
+/// lockstep: each step of the rounds is applied to every lane, a layout the
+/// compiler can vectorize. Otherwise, the hashers are finalized one by one.
+pub fn finalize_lanes(hashers: &[Md5; LANES]) -> [[u8; DIGEST_SIZE]; LANES] {
+    let mut digests = [[0; DIGEST_SIZE]; LANES];
+
+    if hashers.iter().any(|hasher| hasher.buflen >= 56) {
+        for (digest, hasher) in digests.iter_mut().zip(hashers.iter()) {
+            *digest = hasher.finalize();
+        }
+        return digests;
+    }
+
+    // Transpose the padded blocks and the states: one array of lanes per word.
+    let mut x     = [[0u32; LANES]; 16];
+    let mut state = [[0u32; LANES]; 4];
+    for (lane, hasher) in hashers.iter().enumerate() {
+        let mut block = [0; BLOCK_SIZE];
+        block[..hasher.buflen].copy_from_slice(&hasher.buffer[..hasher.buflen]);
+        block[hasher.buflen] = 0x80;
+        block[56..].copy_from_slice(&hasher.length.wrapping_mul(8)
+                                                  .to_le_bytes());
+
+        for (word, bytes) in x.iter_mut().zip(block.chunks(4)) {
+            word[lane] = u32::from_le_bytes([bytes[0], bytes[1],
+                                             bytes[2], bytes[3]]);
+        }
+        for (word, &value) in state.iter_mut().zip(hasher.state.iter()) {
+            word[lane] = value;
+        }
+    }
+
+    compress_lanes(&mut state, &x);
+
+    for (lane, digest) in digests.iter_mut().enumerate() {
+        for (bytes, word) in digest.chunks_mut(4).zip(state.iter()) {
+            bytes.copy_from_slice(&word[lane].to_le_bytes());
+        }
+    }
+    digests
+}
+
+/// Shift amounts, per round.
+const SHIFTS: [[u32; 4]; 4] = [
+    [7, 12, 17, 22], [5, 9, 14, 20], [4, 11, 16, 23], [6, 10, 15, 21],
+];
+
+/// Index of the word used at each step.
+const INDICES: [usize; 64] = [
+     0,  1,  2,  3,  4,  5,  6,  7,  8,  9, 10, 11, 12, 13, 14, 15,
+     1,  6, 11,  0,  5, 10, 15,  4,  9, 14,  3,  8, 13,  2,  7, 12,
+     5,  8, 11, 14,  1,  4,  7, 10, 13,  0,  3,  6,  9, 12, 15,  2,
+     0,  7, 14,  5, 12,  3, 10,  1,  8, 15,  6, 13,  4, 11,  2,  9,
+];
+
+/// Constant added at each step (`floor(abs(sin(i + 1)) * 2^32)`).
+const CONSTANTS: [u32; 64] = [
+    0xd76a_a478, 0xe8c7_b756, 0x2420_70db, 0xc1bd_ceee,
+    0xf57c_0faf, 0x4787_c62a, 0xa830_4613, 0xfd46_9501,
+    0x6980_98d8, 0x8b44_f7af, 0xffff_5bb1, 0x895c_d7be,
+    0x6b90_1122, 0xfd98_7193, 0xa679_438e, 0x49b4_0821,
+    0xf61e_2562, 0xc040_b340, 0x265e_5a51, 0xe9b6_c7aa,
+    0xd62f_105d, 0x0244_1453, 0xd8a1_e681, 0xe7d3_fbc8,
+    0x21e1_cde6, 0xc337_07d6, 0xf4d5_0d87, 0x455a_14ed,
+    0xa9e3_e905, 0xfcef_a3f8, 0x676f_02d9, 0x8d2a_4c8a,
+    0xfffa_3942, 0x8771_f681, 0x6d9d_6122, 0xfde5_380c,
+    0xa4be_ea44, 0x4bde_cfa9, 0xf6bb_4b60, 0xbebf_bc70,
+    0x289b_7ec6, 0xeaa1_27fa, 0xd4ef_3085, 0x0488_1d05,
+    0xd9d4_d039, 0xe6db_99e5, 0x1fa2_7cf8, 0xc4ac_5665,
+    0xf429_2244, 0x432a_ff97, 0xab94_23a7, 0xfc93_a039,
+    0x655b_59c3, 0x8f0c_cc92, 0xffef_f47d, 0x8584_5dd1,
+    0x6fa8_7e4f, 0xfe2c_e6e0, 0xa301_4314, 0x4e08_11a1,
+    0xf753_7e82, 0xbd3a_f235, 0x2ad7_d2bb, 0xeb86_d391,
+];
+
+// Process a 16-word block for every lane.
+#[allow(clippy::needless_range_loop)]
+fn compress_lanes(state: &mut [[u32; LANES]; 4], x: &[[u32; LANES]; 16]) {
+    let (mut a, mut b, mut c, mut d) = (state[0], state[1], state[2], state[3]);
+
+    for step in 0..64 {
+        // Dispatch outside of the lanes loop, to keep it branchless.
+        let f = match step / 16 {
+            0 => map_lanes(function_f, &b, &c, &d),
+            1 => map_lanes(function_g, &b, &c, &d),
+            2 => map_lanes(function_h, &b, &c, &d),
+            _ => map_lanes(function_i, &b, &c, &d),
+        };
+        let s  = SHIFTS[step / 16][step % 4];
+        let xk = &x[INDICES[step]];
+
+        let mut tmp = [0; LANES];
+        for lane in 0..LANES {
+            tmp[lane] = a[lane].wrapping_add(f[lane])
+                               .wrapping_add(xk[lane])
+                               .wrapping_add(CONSTANTS[step])
+                               .rotate_left(s)
+                               .wrapping_add(b[lane]);
+        }
+        a = d;
+        d = c;
+        c = b;
+        b = tmp;
+    }
+
+    for lane in 0..LANES {
+        state[0][lane] = state[0][lane].wrapping_add(a[lane]);
+        state[1][lane] = state[1][lane].wrapping_add(b[lane]);
+        state[2][lane] = state[2][lane].wrapping_add(c[lane]);
+        state[3][lane] = state[3][lane].wrapping_add(d[lane]);
+    }
+}
+
+// Apply an auxiliary function on every lane.
+#[allow(clippy::needless_range_loop)]
+#[inline(always)]
+fn map_lanes<F>(fun: F, x: &[u32; LANES], y: &[u32; LANES], z: &[u32; LANES])
+    -> [u32; LANES]
+    where F: Fn(u32, u32, u32) -> u32 {
+    let mut res = [0; LANES];
+    for lane in 0..LANES {
+        res[lane] = fun(x[lane], y[lane], z[lane]);
+    }
+    res
+}
+
+#[inline(always)]
+fn function_f(x: u32, y : u32, z: u32) -> u32 {
+    (x & y) | (!x & z)
+}
+
+#[inline(always)]
+fn function_g(x: u32, y : u32, z: u32) -> u32 {
+    (x & z) | (y & !z)
+}
+
+#[inline(always)]
+fn function_h(x: u32, y : u32, z: u32) -> u32 {
+    x ^ y ^ z
+}
+
+#[inline(always)]
+fn function_i(x: u32, y : u32, z: u32) -> u32 {
+    y ^ (x | !z)
+}
+
+#[allow(clippy::too_many_arguments, clippy::many_single_char_names)]
+#[inline(always)]
+fn apply<F>(fun: F, a: u32, b: u32, c: u32, d: u32, xk: u32, s:u32, ti: u32)
+    -> u32
+    where F: Fn(u32, u32, u32) -> u32 {
+    let mut tmp = a.wrapping_add(fun(b, c, d));
+    tmp = tmp.wrapping_add(xk);
+    tmp = tmp.wrapping_add(ti);
+    b.wrapping_add(tmp.rotate_left(s))
+}
+
+// }}}
+// {{{ Tests
+
+#[cfg(test)]
+fn to_hex(digest: &[u8; DIGEST_SIZE]) -> String {
+    digest.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[test]
+fn rfc1321() {
+    // See §A.5 in RFC 1321.
+    let testcases: &[(&str, &[u8; 16])] = &[
+        ("",
+         &[0xd4, 0x1d, 0x8c, 0xd9, 0x8f, 0x00, 0xb2, 0x04,
+           0xe9, 0x80, 0x09, 0x98, 0xec, 0xf8, 0x42, 0x7e]
+        ),
+        ("a",
+         &[0x0c, 0xc1, 0x75, 0xb9, 0xc0, 0xf1, 0xb6, 0xa8,
+           0x31, 0xc3, 0x99, 0xe2, 0x69, 0x77, 0x26, 0x61]
+        ),
+        ("abc",
+         &[0x90, 0x01, 0x50, 0x98, 0x3c, 0xd2, 0x4f, 0xb0,
+           0xd6, 0x96, 0x3f, 0x7d, 0x28, 0xe1, 0x7f, 0x72]
+        ),
+        ("message digest",
+         &[0xf9, 0x6b, 0x69, 0x7d, 0x7c, 0xb7, 0x93, 0x8d,
+           0x52, 0x5a, 0x2f, 0x31, 0xaa, 0xf1, 0x61, 0xd0]
+        ),
+        ("abcdefghijklmnopqrstuvwxyz",
+         &[0xc3, 0xfc, 0xd3, 0xd7, 0x61, 0x92, 0xe4, 0x00,
+           0x7d, 0xfb, 0x49, 0x6c, 0xca, 0x67, 0xe1, 0x3b]
+        ),
+        ("ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789",
+         &[0xd1, 0x74, 0xab, 0x98, 0xd2, 0x77, 0xd9, 0xf5,
+           0xa5, 0x61, 0x1c, 0x2c, 0x9f, 0x41, 0x9d, 0x9f]
+        ),
+        ("12345678901234567890123456789012345678901234567890123456789012345678901234567890",
+         &[0x57, 0xed, 0xf4, 0xa2, 0x2b, 0xe3, 0xc9, 0x55,
+           0xac, 0x49, 0xda, 0x2e, 0x21, 0x07, 0xb6, 0x7a]
+        )
+    ];
+    for &(data, expected) in testcases.iter() {
+        assert_eq!(digest(data.as_bytes()), *expected);
+    }
+}
+
+#[test]
+fn long_messages() {
+    // The length of the message must not be truncated.
+    let bytes = (0..8193).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+    assert_eq!(to_hex(&digest(&bytes)), "d70940b0f2d0a8ac92655d222ce0ecd5");
+
+    let bytes = "0123456789".repeat(10_000);
+    assert_eq!(to_hex(&digest(bytes.as_bytes())),
+               "13572e9e296cff52b79c52148313c3a5");
+
+    let bytes = vec![b'a'; 1_000_000];
+    assert_eq!(to_hex(&digest(&bytes)), "7707d6ae4e027c70eea2a935c2296f21");
+}
+
+#[test]
+fn streaming() {
+    let bytes = (0..1000).map(|i| (i % 256) as u8).collect::<Vec<_>>();
+    let expected = digest(&bytes);
+
+    // Any split of the message must give the same digest.
+    for chunk_size in &[1, 3, 55, 56, 63, 64, 65, 128, 999] {
+        let mut md5 = Md5::new();
+        for chunk in bytes.chunks(*chunk_size) {
+            md5.update(chunk);
+        }
+        assert_eq!(md5.finalize(), expected);
+    }
+
+    // Reusing a prefix state.
+    let mut prefix = Md5::new();
+    prefix.update(&bytes[..100]);
+    for split in &[100, 128, 500] {
+        let mut md5 = prefix;
+        md5.update(&bytes[100..*split]);
+        md5.update(&bytes[*split..]);
+        assert_eq!(md5.finalize(), expected);
+    }
+}
+
+#[test]
+fn lanes() {
+    let bytes = (0..200).map(|i| (i * 7 % 256) as u8).collect::<Vec<_>>();
+
+    // Single final block for every lane, then some lanes needing two.
+    for &(prefix, step) in &[(0, 1), (10, 3), (64, 5), (40, 2)] {
+        let mut hashers = [Md5::new(); LANES];
+        let mut expected = [[0; DIGEST_SIZE]; LANES];
+        for lane in 0..LANES {
+            let message = &bytes[..prefix + lane * step];
+            hashers[lane].update(message);
+            expected[lane] = digest(message);
+        }
+        assert_eq!(finalize_lanes(&hashers), expected);
+    }
+}
+
+// }}}
@@ -0,0 +1,213 @@
+//! Recognition of the letters drawn on a pixel grid.
+
+// {{{ Lints
+
+#![deny(missing_debug_implementations,
+        missing_copy_implementations,
+        missing_docs,
+        trivial_casts,
+        trivial_numeric_casts,
+        unsafe_code,
+        unstable_features,
+        unused_import_braces,
+        unused_qualifications
+)]
+
+// }}}
+// {{{ Font
+
+use std::error;
+use std::fmt;
+
+/// Width of a letter, including the blank column that follows it.
+pub const GLYPH_WIDTH: usize = 5;
+
+/// Height of a letter.
+pub const GLYPH_HEIGHT: usize = 6;
+
+/// The known letters, as drawn on the screen.
+///
+/// Every letter but `Y` is 4 pixels wide, the missing columns are blank.
+const FONT: &[(char, [&str; GLYPH_HEIGHT])] = &[
+    ('A', [".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+    ('B', ["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+    ('C', [".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+    ('E', ["####", "#...", "###.", "#...", "#...", "####"]),
+    ('F', ["####", "#...", "###.", "#...", "#...", "#..."]),
+    ('G', [".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+    ('H', ["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+    ('I', [".###", "..#.", "..#.", "..#.", "..#.", ".###"]),
+    ('J', ["..##", "...#", "...#", "...#", "#..#", ".##."]),
+    ('K', ["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+    ('L', ["#...", "#...", "#...", "#...", "#...", "####"]),
+    ('O', [".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('P', ["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+    ('R', ["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+    ('S', [".###", "#...", "#...", ".##.", "...#", "###."]),
+    ('U', ["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('Y', ["#...#", "#...#", ".#.#.", "..#..", "..#..", "..#.."]),
+    ('Z', ["####", "...#", "..#.", ".#..", "#...", "####"]),
+];
+
+/// A glyph, one bit per pixel (row by row, the top-left pixel being the most
+/// significant one).
+type Glyph = u32;
+
+/// Returns the glyph drawn by the rows.
+fn glyph_from_art(rows: &[&str; GLYPH_HEIGHT]) -> Glyph {
+    rows.iter().fold(0, |glyph, row| {
+        (0..GLYPH_WIDTH).fold(glyph, |glyph, j| {
+            (glyph << 1) | Glyph::from(row.as_bytes().get(j) == Some(&b'#'))
+        })
+    })
+}
+
+/// Returns the character drawn by the glyph, if any.
+fn lookup(glyph: Glyph) -> Option<char> {
+    if glyph == 0 {
+        return Some(' ');
+    }
+    FONT.iter().find(|&(_, rows)| glyph_from_art(rows) == glyph)
+               .map(|&(letter, _)| letter)
+}
+
+// }}}
+// {{{ Recognition
+
+/// Reads the text drawn on a `width`-pixel wide grid of pixels.
+///
+/// The pixels are given row by row, and the grid must be exactly
+/// `GLYPH_HEIGHT` pixels tall.
+/// Letters start every `GLYPH_WIDTH` columns; the last one may omit its
+/// spacing column. A blank cell is read as a space.
+pub fn recognize(pixels: &[bool], width: usize) -> Result<String, Error> {
+    if width == 0 || pixels.len() != width * GLYPH_HEIGHT {
+        return Err(Error::InvalidSize { len: pixels.len(), width });
+    }
+
+    let count = width.div_ceil(GLYPH_WIDTH);
+    (0..count).map(|index| {
+        let glyph = extract(pixels, width, index);
+        lookup(glyph).ok_or(Error::UnknownGlyph { index, art: art(glyph) })
+    }).collect()
+}
+
+/// Extracts the `index`-th glyph of the grid.
+fn extract(pixels: &[bool], width: usize, index: usize) -> Glyph {
+    let mut glyph = 0;
+    for row in pixels.chunks(width) {
+        for j in index*GLYPH_WIDTH .. (index + 1)*GLYPH_WIDTH {
+            glyph = (glyph << 1) | Glyph::from(row.get(j) == Some(&true));
+        }
+    }
+    glyph
+}
+
+/// Draws a glyph, with `#` for the lit pixels and `.` for the others.
+fn art(glyph: Glyph) -> String {
+    let mut art = String::with_capacity((GLYPH_WIDTH + 1) * GLYPH_HEIGHT);
+    for i in (0..GLYPH_WIDTH*GLYPH_HEIGHT).rev() {
+        art.push(if glyph & (1 << i) != 0 { '#' } else { '.' });
+        if i % GLYPH_WIDTH == 0 {
+            art.push('\n');
+        }
+    }
+    art
+}
+
+// }}}
+// {{{ Error
+
+/// Reasons why a grid cannot be read.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The grid isn't made of `GLYPH_HEIGHT` rows of `width` pixels.
+    InvalidSize {
+        /// Number of pixels in the grid.
+        len: usize,
+        /// Expected width of a row.
+        width: usize,
+    },
+    /// A glyph doesn't match any letter of the font.
+    UnknownGlyph {
+        /// Position of the glyph in the text.
+        index: usize,
+        /// Drawing of the glyph, one line per row.
+        art: String,
+    },
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::InvalidSize { len, width } =>
+                write!(f, "{} pixels can't make {} rows of {} pixels",
+                       len, GLYPH_HEIGHT, width),
+            Error::UnknownGlyph { index, ref art } =>
+                write!(f, "unknown glyph at position {}:\n{}", index, art),
+        }
+    }
+}
+
+impl error::Error for Error {}
+
+// }}}
+// {{{ Tests
+
+/// Turns a drawing into a grid of pixels.
+#[cfg(test)]
+fn pixels(art: &[&str]) -> Vec<bool> {
+    art.iter().flat_map(|row| row.bytes().map(|b| b == b'#')).collect()
+}
+
+#[test]
+fn whole_font() {
+    // Draw every letter, the last one without trailing spacing.
+    let mut rows = vec![String::new(); GLYPH_HEIGHT];
+    for (n, (_, art)) in FONT.iter().enumerate() {
+        for (row, line) in rows.iter_mut().zip(art.iter()) {
+            let width = if n + 1 == FONT.len() { 4 } else { GLYPH_WIDTH };
+            row.push_str(&format!("{:.<1$}", line, width));
+        }
+    }
+    let rows  = rows.iter().map(String::as_str).collect::<Vec<_>>();
+    let text  = FONT.iter().map(|&(letter, _)| letter).collect::<String>();
+    let width = rows[0].len();
+
+    assert_eq!(recognize(&pixels(&rows), width), Ok(text));
+}
+
+#[test]
+fn screen() {
+    let rows = [
+        "###..#..#.###..#..#..##..####..##..####..###.#....",
+        "#..#.#..#.#..#.#..#.#..#.#....#..#.#......#..#....",
+        "#..#.#..#.#..#.#..#.#....###..#..#.###....#..#....",
+        "###..#..#.###..#..#.#....#....#..#.#......#..#....",
+        "#.#..#..#.#.#..#..#.#..#.#....#..#.#......#..#....",
+        "#..#..##..#..#..##...##..####..##..####..###.####.",
+    ];
+
+    assert_eq!(recognize(&pixels(&rows), 50), Ok("RURUCEOEIL".to_owned()));
+}
+
+#[test]
+fn errors() {
+    let rows = [
+        ".....#...#",
+        ".....##..#",
+        ".....#.#.#",
+        ".....#..##",
+        ".....#...#",
+        ".....#...#",
+    ];
+
+    assert_eq!(recognize(&pixels(&rows), 10), Err(Error::UnknownGlyph {
+        index: 1,
+        art:   "#...#\n##..#\n#.#.#\n#..##\n#...#\n#...#\n".to_owned(),
+    }));
+    assert_eq!(recognize(&pixels(&rows[..5]), 10),
+               Err(Error::InvalidSize { len: 50, width: 10 }));
+}
+
+// }}}